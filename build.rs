@@ -0,0 +1,29 @@
+// Select the directory of the admin UI files that the "embedded-admin-ui" feature compiles into the
+// binary, which is "admin" unless NMOS_ADMIN_UI_DIR is set, e.g. to the build output of the admin UI
+// The directory must be populated before building with the feature, e.g. by building the admin UI
+// and copying its output there
+
+use std::env;
+use std::path::Path;
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=NMOS_ADMIN_UI_DIR");
+    if env::var_os("CARGO_FEATURE_EMBEDDED_ADMIN_UI").is_none() {
+        return;
+    }
+
+    let dir = env::var("NMOS_ADMIN_UI_DIR")
+        .unwrap_or_else(|_| format!("{}/admin", env::var("CARGO_MANIFEST_DIR").unwrap()));
+    let populated = Path::new(&dir)
+        .read_dir()
+        .map(|mut entries| entries.next().is_some())
+        .unwrap_or(false);
+    if !populated {
+        panic!(
+            "the embedded-admin-ui feature requires the admin UI files in {}, so build the admin UI and copy its output there, or set NMOS_ADMIN_UI_DIR to its output directory",
+            dir
+        );
+    }
+    println!("cargo:rerun-if-changed={}", dir);
+    println!("cargo:rustc-env=NMOS_ADMIN_UI_DIR={}", dir);
+}
@@ -0,0 +1,25 @@
+#!/bin/sh
+# Fetch the JSON schemas of the supported versions of IS-04 and IS-05 from the AMWA repositories,
+# into is-04/<version> and is-05/<version>, to be compiled into the binary by src/json_schema.rs
+# The committed schemas were transcribed from the published specifications; run this to replace
+# them with the released files of every version
+# Only the versions whose schemas are here are validated, and the others are reported when they are
+# first requested, so run this again when a new version of a specification is released
+set -e
+cd "$(dirname "$0")"
+
+fetch() {
+    spec=$1
+    shift
+    for version in "$@"; do
+        checkout=$(mktemp -d)
+        git clone --quiet --depth 1 --branch "$version.x" "https://github.com/AMWA-TV/$spec.git" "$checkout"
+        rm -rf "$spec/$version"
+        mkdir -p "$spec/$version"
+        cp "$checkout"/APIs/schemas/*.json "$spec/$version/"
+        rm -rf "$checkout"
+    done
+}
+
+fetch is-04 v1.0 v1.1 v1.2 v1.3
+fetch is-05 v1.0 v1.1
@@ -1,83 +1,233 @@
-use crate::nmos::{api_utils::*, filesystem_route::*, slog::*, *};
-use std::convert::TryFrom;
 use std::sync::Arc;
-use tokio::sync::Mutex;
-use warp::http::{header::HeaderValue, header::LOCATION, Response, StatusCode};
+
+use serde::Deserialize;
+use serde_json::json;
+use slog::{warn, Logger};
+use warp::filters::path::FullPath;
+use warp::filters::BoxedFilter;
+use warp::http::header::{HeaderValue, CONTENT_SECURITY_POLICY};
+use warp::http::Uri;
+use warp::reply::Response;
 use warp::{Filter, Reply};
 
+use crate::filesystem_route::{
+    make_embedded_route, make_filesystem_route, make_relative_path_content_type_handler,
+    with_header, EmbeddedFiles, FilesystemRouteOptions,
+};
+use crate::header_policy::{make_header_policy, with_header_policy};
+use crate::metrics::{with_request_metrics, Metrics};
+use crate::server_timing::with_server_timing;
+use crate::settings::Settings;
+
+// Make the routes for an API at /api/<sub_route>, e.g. the admin UI, with the header policy of that
+// API, like every NMOS API
+// The root lists the API, and /api/<sub_route> is redirected to /api/<sub_route>/ so that relative
+// links in the files served there resolve correctly
 pub fn make_api_sub_route(
     sub_route: &str,
-    sub_router: warp::filters::BoxedFilter<(impl Reply,)>,
-    gate: Arc<Mutex<Box<dyn Gate>>>,
-) -> warp::filters::BoxedFilter<(impl Reply,)> {
-    let api = format!("/api/{}", sub_route);
-    let sub_routes = format!("{}/*", sub_route);
-
-    let router = warp::any().and_then(move || {
-        let gate = gate.clone();
-        let api_clone = api.clone();
-
-        async move {
-            Ok(Response::builder()
-                .header("Access-Control-Allow-Origin", "*")
-                .header("Access-Control-Allow-Methods", "GET, POST, PUT, DELETE, OPTIONS")
-                .header("Access-Control-Allow-Headers", "Content-Type")
-                .header("Access-Control-Max-Age", "86400")
-                .header("Cache-Control", "no-cache, no-store, must-revalidate")
-                .header("Pragma", "no-cache")
-                .header("Expires", "0")
-                .header("X-Content-Type-Options", "nosniff")
-                .header("X-Frame-Options", "SAMEORIGIN")
-                .header("X-XSS-Protection", "1; mode=block")
-                .header("Content-Security-Policy", "default-src 'none'; script-src 'self' 'unsafe-inline'; style-src 'self' 'unsafe-inline'; img-src 'self'; connect-src 'self'")
-                .header("Referrer-Policy", "strict-origin-when-cross-origin")
-                .status(StatusCode::OK)
-                .body(make_sub_routes_body(
-                    &[sub_routes.as_str().to_owned()],
-                    &api_clone,
-                    &HeaderValue::from_str("http://localhost:3209/").unwrap(),
-                    None,
-                    gate.lock().await.deref_mut(),
-                ))?)
-        }
-    });
+    sub_router: BoxedFilter<(Response,)>,
+    settings: &Settings,
+) -> BoxedFilter<(Response,)> {
+    let scheme = if settings.server_secure {
+        "https"
+    } else {
+        "http"
+    };
+    let href = format!("api/{}/", sub_route);
+
+    // The links are relative to the host to which the request was made
+    let root = warp::path::end()
+        .and(warp::get())
+        .and(warp::header::optional::<String>("host"))
+        .map(move |host: Option<String>| {
+            let base = format!("{}://{}/", scheme, host.as_deref().unwrap_or("localhost"));
+            warp::reply::json(&json!([format!("{}{}", base, href)])).into_response()
+        });
 
-    warp::path::end()
+    let api = warp::path("api").and(warp::path(sub_route.to_string()));
+    let location: Uri = format!("/api/{}/", sub_route)
+        .parse()
+        .expect("sub_route should be a valid path segment");
+    let redirect = api
+        .clone()
+        .and(warp::path::end())
         .and(warp::get())
-        .and(router)
-        .boxed()
-        .or(warp::path(api.as_str())
-            .and(warp::get())
-            .map(|| warp::redirect::temporary(Uri::try_from(sub_routes).unwrap())))
-        .or(warp::path(api)
-            .and(warp::path::tail())
-            .and(sub_router)
-            .boxed())
-        .boxed()
+        .and(warp::path::full())
+        .and_then(move |path: FullPath| {
+            let location = location.clone();
+            async move {
+                if path.as_str().ends_with('/') {
+                    Err(warp::reject::not_found())
+                } else {
+                    Ok(warp::redirect::temporary(location).into_response())
+                }
+            }
+        });
+
+    let routes = root.or(redirect).unify().or(api.and(sub_router)).unify();
+    with_header_policy(routes, make_header_policy(settings, sub_route)).boxed()
+}
+
+// To serve the admin UI, only a few HTML, JavaScript and CSS files are necessary
+pub const VALID_EXTENSIONS: &[(&str, &str)] = &[
+    ("ico", "image/x-icon"),
+    ("html", "text/html"),
+    ("js", "application/javascript"),
+    ("map", "application/json"),
+    ("json", "application/json"),
+    ("css", "text/css"),
+    ("png", "image/png"),
+];
+
+// Where the admin UI files are served from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AdminUiRoot {
+    // The files under the filesystem root, deployed alongside the binary
+    #[default]
+    Filesystem,
+    // The files compiled into the binary, if it was built with the "embedded-admin-ui" feature
+    Embedded,
+}
+
+// The directory is selected by build.rs
+#[cfg(feature = "embedded-admin-ui")]
+static EMBEDDED_ADMIN_UI: include_dir::Dir = include_dir::include_dir!("$NMOS_ADMIN_UI_DIR");
+
+// Collect the embedded admin UI files by their paths relative to the root
+#[cfg(feature = "embedded-admin-ui")]
+fn embedded_admin_ui_files() -> Option<EmbeddedFiles> {
+    fn collect(dir: &'static include_dir::Dir, files: &mut EmbeddedFiles) {
+        for entry in dir.entries() {
+            match entry {
+                include_dir::DirEntry::Dir(dir) => collect(dir, files),
+                include_dir::DirEntry::File(file) => {
+                    if let Some(relative_path) = file.path().to_str() {
+                        files.insert(relative_path.replace('\\', "/"), file.contents());
+                    }
+                }
+            }
+        }
+    }
+    let mut files = EmbeddedFiles::new();
+    collect(&EMBEDDED_ADMIN_UI, &mut files);
+    Some(files)
+}
+
+#[cfg(not(feature = "embedded-admin-ui"))]
+fn embedded_admin_ui_files() -> Option<EmbeddedFiles> {
+    None
+}
+
+// The admin UI needs to connect to the NMOS APIs, which may be on other ports or hosts than the
+// admin UI itself, so the connect-src sources are taken from the settings
+pub fn make_admin_ui_content_security_policy(connect_src: &[String]) -> HeaderValue {
+    let connect_src = if connect_src.is_empty() {
+        "'self'".to_string()
+    } else {
+        connect_src.join(" ")
+    };
+    let policy = format!("default-src 'none'; script-src 'self' 'unsafe-inline'; style-src 'self' 'unsafe-inline'; img-src 'self'; connect-src {}", connect_src);
+    HeaderValue::from_str(&policy).unwrap_or_else(|_| {
+        HeaderValue::from_static("default-src 'none'; script-src 'self' 'unsafe-inline'; style-src 'self' 'unsafe-inline'; img-src 'self'; connect-src 'self'")
+    })
 }
 
 pub fn make_admin_ui(
     filesystem_root: &str,
-    gate: Arc<Mutex<Box<dyn Gate>>>,
-) -> warp::filters::BoxedFilter<(impl Reply,)> {
-    // To serve the admin UI, only a few HTML, JavaScript and CSS files are necessary
-    let valid_extensions: HashMap<&str, &str> = vec![
-        ("ico", "image/x-icon"),
-        ("html", "text/html"),
-        ("js", "application/javascript"),
-        ("map", "application/json"),
-        ("json", "application/json"),
-        ("css", "text/css"),
-        ("png", "image/png"),
-    ]
-    .into_iter()
-    .collect();
-
-    let handler = make_relative_path_content_type_handler(valid_extensions);
-
-    make_api_sub_route(
-        "admin",
-        make_filesystem_route(filesystem_root, handler, gate),
-        gate,
-    )
+    settings: &Settings,
+    metrics: Option<Arc<Metrics>>,
+    gate: Logger,
+) -> BoxedFilter<(Response,)> {
+    let handler =
+        make_relative_path_content_type_handler(VALID_EXTENSIONS.iter().copied().collect());
+
+    // The admin UI is a single-page application, so its client-side routes are all served the index
+    let options = FilesystemRouteOptions {
+        fallback: Some("index.html".to_string()),
+    };
+    let files = match settings.admin_ui_root {
+        AdminUiRoot::Embedded => match embedded_admin_ui_files() {
+            Some(files) => make_embedded_route(files, handler, options, gate.clone()),
+            None => {
+                warn!(
+                    gate,
+                    "The admin UI files are not embedded in this build, serving them from: {}",
+                    filesystem_root
+                );
+                make_filesystem_route(filesystem_root, handler, options, gate.clone())
+            }
+        },
+        AdminUiRoot::Filesystem => {
+            make_filesystem_route(filesystem_root, handler, options, gate.clone())
+        }
+    };
+    let files = with_header(
+        files,
+        CONTENT_SECURITY_POLICY,
+        make_admin_ui_content_security_policy(&settings.admin_ui_connect_src),
+    );
+
+    let api = make_api_sub_route("admin", files, settings);
+    with_server_timing(with_request_metrics(api, "admin", metrics), "admin", gate).boxed()
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_admin_ui() {
+        let root = std::env::temp_dir().join(format!("nmos-admin-ui-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("index.html"), "<html></html>").unwrap();
+
+        // Without the embedded files, the files are served from the filesystem root instead
+        let admin_ui_root = if cfg!(feature = "embedded-admin-ui") {
+            AdminUiRoot::Filesystem
+        } else {
+            AdminUiRoot::Embedded
+        };
+        let settings = Settings {
+            admin_ui_root,
+            admin_ui_connect_src: vec!["'self'".to_string(), "ws:".to_string()],
+            ..Default::default()
+        };
+        let gate = Logger::root(slog::Discard, slog::o!());
+        let api = make_admin_ui(root.to_str().unwrap(), &settings, None, gate);
+
+        let response = warp::test::request()
+            .path("/")
+            .header("host", "192.0.2.1:3212")
+            .reply(&api)
+            .await;
+        assert_eq!(
+            response.body().as_ref(),
+            br#"["http://192.0.2.1:3212/api/admin/"]"#
+        );
+
+        let response = warp::test::request().path("/api/admin").reply(&api).await;
+        assert_eq!(response.status(), 307);
+        assert_eq!(response.headers()["location"], "/api/admin/");
+
+        for path in ["/api/admin/", "/api/admin/nodes"] {
+            let response = warp::test::request().path(path).reply(&api).await;
+            assert_eq!(response.status(), 200, "{}", path);
+            assert_eq!(response.body().as_ref(), b"<html></html>");
+            let policy = response.headers()["content-security-policy"]
+                .to_str()
+                .unwrap();
+            assert!(policy.ends_with("connect-src 'self' ws:"));
+        }
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[cfg(feature = "embedded-admin-ui")]
+    #[test]
+    fn test_embedded_admin_ui_files() {
+        let files = embedded_admin_ui_files().unwrap();
+        assert!(!files.is_empty());
+    }
 }
@@ -0,0 +1,519 @@
+// Experimental extension, to support human-readable HTML rendering of NMOS responses, for
+// clients such as browsers that prefer HTML to JSON
+// Links are made from the URLs in the response, from the ids of resources, and from the $href and
+// $_ keywords in the elements of "child resources" responses
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+
+use serde_json::Value;
+use slog::{debug, error, Logger};
+use warp::filters::path::FullPath;
+use warp::http::{header, HeaderValue, Response, StatusCode, Uri};
+use warp::hyper::{body, Body};
+use warp::{Filter, Rejection, Reply};
+
+const HEADERS_STYLESHEET: &str = r"
+    .headers {
+        font-family: monospace;
+        color: grey;
+        border-bottom: 1px solid lightgrey;
+    }
+    .headers ol {
+        list-style: none;
+        padding: 0;
+    }
+";
+
+const JSON_STYLESHEET: &str = r"
+    .json {
+        font-family: monospace;
+        white-space: pre-wrap;
+    }
+    .json ol {
+        list-style: none;
+        margin: 0;
+        padding-left: 2em;
+    }
+    .json details, .json summary {
+        display: inline;
+    }
+    .json details:not([open]) > summary::after {
+        content: '\2026';
+        color: grey;
+    }
+    .json summary {
+        cursor: pointer;
+    }
+    .json .key { color: purple; }
+    .json .string { color: green; }
+    .json .number { color: blue; }
+    .json .boolean, .json .null { color: firebrick; }
+";
+
+// The resource types, i.e. the plural strings used in the API endpoint routes
+const RESOURCE_TYPES: &[&str] = &[
+    "nodes",
+    "devices",
+    "sources",
+    "flows",
+    "senders",
+    "receivers",
+    "subscriptions",
+];
+
+// Determine whether the client prefers an HTML response to JSON, according to the q-values of
+// the Accept header, where the most specific matching media range determines each q-value
+// See https://www.rfc-editor.org/rfc/rfc9110#section-12.5.1
+pub fn accepts_html(accept: &str) -> bool {
+    let quality = |media_type: &str| {
+        let (type_, _) = media_type.split_once('/').unwrap_or((media_type, ""));
+        accept
+            .split(',')
+            .filter_map(|range| {
+                let mut params = range.split(';').map(str::trim);
+                let range = params.next()?.to_ascii_lowercase();
+                let q = params
+                    .find_map(|param| param.strip_prefix("q="))
+                    .and_then(|q| q.parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                let specificity = if range == media_type {
+                    2
+                } else if range == format!("{}/*", type_) {
+                    1
+                } else if range == "*/*" {
+                    0
+                } else {
+                    return None;
+                };
+                Some((specificity, q))
+            })
+            .max_by_key(|(specificity, _)| *specificity)
+            .map_or(0.0, |(_, q)| q)
+    };
+    let html = quality("text/html");
+    // JSON is preferred if the client doesn't mind, e.g. "*/*"
+    html > 0.0 && html > quality("application/json")
+}
+
+// Objects with the keywords $href and $_ and rendered as HTML anchor(a) tags.
+// This allows elements in NMOS "child resources" responses to be made into links,
+// and id values in resources can also be made into links to the appropriate resource.
+struct HtmlVisitor<'a, W> {
+    writer: W,
+    names: HashMap<&'static str, &'a str>,
+    // The path of the API version, e.g. "/x-nmos/query/v1.3", for links to resources
+    api_path: Option<&'a str>,
+    // The type of the resources in the response, e.g. "senders", for links from their ids
+    resource_type: Option<&'a str>,
+}
+
+impl<'a, W: std::fmt::Write> HtmlVisitor<'a, W> {
+    fn new(writer: W, api_path: Option<&'a str>, resource_type: Option<&'a str>) -> Self {
+        let mut names = HashMap::new();
+        names.insert("http", "http://");
+        names.insert("https", "https://");
+        names.insert("ws", "ws://");
+        names.insert("wss", "wss://");
+        HtmlVisitor {
+            writer,
+            names,
+            api_path,
+            resource_type,
+        }
+    }
+
+    fn visit(&mut self, value: &Value, key: Option<&str>) -> std::fmt::Result {
+        match value {
+            Value::Null => self.writer.write_str("<span class=\"null\">null</span>"),
+            Value::Bool(value) => {
+                write!(self.writer, "<span class=\"boolean\">{}</span>", value)
+            }
+            Value::Number(value) => {
+                write!(self.writer, "<span class=\"number\">{}</span>", value)
+            }
+            Value::String(value) => self.write_string(value, key),
+            Value::Array(values) => {
+                if values.is_empty() {
+                    return self.writer.write_str("[]");
+                }
+                self.writer
+                    .write_str("<details open><summary>[</summary><ol>")?;
+                for (index, element) in values.iter().enumerate() {
+                    self.writer.write_str("<li>")?;
+                    self.visit(element, key)?;
+                    if index + 1 != values.len() {
+                        self.writer.write_char(',')?;
+                    }
+                    self.writer.write_str("</li>")?;
+                }
+                self.writer.write_str("</ol></details>]")
+            }
+            Value::Object(object) => {
+                if let (Some(Value::String(href)), Some(Value::String(text)), 2) =
+                    (object.get("$href"), object.get("$_"), object.len())
+                {
+                    return self.write_anchor(href, text);
+                }
+                if object.is_empty() {
+                    return self.writer.write_str("{}");
+                }
+                self.writer
+                    .write_str("<details open><summary>{</summary><ol>")?;
+                for (index, (name, element)) in object.iter().enumerate() {
+                    write!(
+                        self.writer,
+                        "<li><span class=\"key\">{}</span>: ",
+                        html_escape(&Value::from(name.as_str()).to_string())
+                    )?;
+                    self.visit(element, Some(name))?;
+                    if index + 1 != object.len() {
+                        self.writer.write_char(',')?;
+                    }
+                    self.writer.write_str("</li>")?;
+                }
+                self.writer.write_str("</ol></details>}")
+            }
+        }
+    }
+
+    fn write_string(&mut self, value: &str, key: Option<&str>) -> std::fmt::Result {
+        if self.is_href(value) {
+            return self.write_anchor(value, value);
+        }
+        if let Some(href) = self.resource_href(value, key) {
+            return self.write_anchor(&href, value);
+        }
+        write!(
+            self.writer,
+            "<span class=\"string\">{}</span>",
+            html_escape(&Value::from(value).to_string())
+        )
+    }
+
+    fn write_anchor(&mut self, href: &str, text: &str) -> std::fmt::Result {
+        write!(
+            self.writer,
+            "<a class=\"string\" href=\"{}\">{}</a>",
+            html_escape(href),
+            html_escape(&Value::from(text).to_string())
+        )
+    }
+
+    fn is_href(&self, href: &str) -> bool {
+        self.names
+            .iter()
+            .any(|(_, scheme)| href.starts_with(scheme))
+    }
+
+    // Make the link to the resource identified by the value of the specified key, e.g. the
+    // "device_id" of a sender, or the "id" of a resource in the response
+    fn resource_href(&self, id: &str, key: Option<&str>) -> Option<String> {
+        let api_path = self.api_path?;
+        if !is_uuid(id) {
+            return None;
+        }
+        let resource_type = match key? {
+            "id" | "parents" => self.resource_type?,
+            key => {
+                let singular = key.strip_suffix("_id")?;
+                RESOURCE_TYPES
+                    .iter()
+                    .find(|resource_type| resource_type.strip_suffix('s') == Some(singular))?
+            }
+        };
+        Some(format!("{}/{}/{}", api_path, resource_type, id))
+    }
+}
+
+fn is_uuid(value: &str) -> bool {
+    value.len() == 36
+        && value.char_indices().all(|(index, c)| match index {
+            8 | 13 | 18 | 23 => c == '-',
+            _ => c.is_ascii_hexdigit(),
+        })
+}
+
+// Split the request path into the path of the API version, e.g. "/x-nmos/query/v1.3", and the
+// resource type, e.g. "senders", if any
+fn split_api_path(path: &str) -> (Option<&str>, Option<&str>) {
+    let mut offset = 0;
+    let mut segments = path.split('/').map(|segment| {
+        offset += segment.len() + 1;
+        (offset - 1, segment)
+    });
+    let api_path = segments.find_map(|(end, segment)| {
+        let is_version = segment.strip_prefix('v').is_some_and(|version| {
+            version
+                .split('.')
+                .all(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+        });
+        is_version.then(|| &path[..end])
+    });
+    let resource_type = api_path.and_then(|_| {
+        segments
+            .next()
+            .map(|(_, segment)| segment)
+            .filter(|segment| RESOURCE_TYPES.contains(segment))
+    });
+    (api_path, resource_type)
+}
+
+// Construct an HTML rendering of an NMOS response
+pub fn make_html_response_body(uri: &Uri, res: &Response<Vec<u8>>, gate: &Logger) -> String {
+    let mut html = String::new();
+    write!(
+        &mut html,
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\">"
+    )
+    .unwrap();
+    write!(&mut html, "<title>{}</title>", html_escape(uri.path())).unwrap();
+    write!(
+        &mut html,
+        "<style>{}{}</style>",
+        HEADERS_STYLESHEET, JSON_STYLESHEET
+    )
+    .unwrap();
+    write!(&mut html, "</head><body>").unwrap();
+    write!(&mut html, "<div class=\"headers\"><ol>").unwrap();
+    write!(
+        &mut html,
+        "<li><span class=\"name\">Status</span>: <span class=\"value\">{}</span></li>",
+        res.status()
+    )
+    .unwrap();
+    for (header_name, header_value) in res.headers() {
+        write!(
+            &mut html,
+            "<li><span class=\"name\">{}</span>: <span class=\"value\">",
+            header_name.as_str()
+        )
+        .unwrap();
+        if header_name == header::LOCATION {
+            let html_value = html_escape(header_value.to_str().unwrap_or_default());
+            write!(&mut html, "<a href=\"{}\">{}</a>", html_value, html_value).unwrap();
+        } else if header_name == header::LINK {
+            let links = parse_links(header_value.to_str().unwrap_or_default())
+                .into_iter()
+                .map(|(link, rel)| {
+                    let html_link = html_escape(&link);
+                    let html_rel = html_escape(&rel);
+                    format!(
+                        "&lt;<a href=\"{}\" rel=\"{}\">{}</a>&gt;; rel=\"{}\"",
+                        html_link, html_rel, html_link, html_rel
+                    )
+                })
+                .collect::<Vec<_>>();
+            write!(&mut html, "{}", links.join(", ")).unwrap();
+        } else {
+            write!(
+                &mut html,
+                "{}",
+                html_escape(header_value.to_str().unwrap_or_default())
+            )
+            .unwrap();
+        }
+        write!(&mut html, "</span></li>").unwrap();
+    }
+    write!(&mut html, "</ol></div><br/>").unwrap();
+    write!(&mut html, "<div class=\"json gutter\">").unwrap();
+    match serde_json::from_slice::<Value>(res.body()) {
+        Ok(body) => {
+            let (api_path, resource_type) = split_api_path(uri.path());
+            HtmlVisitor::new(&mut html, api_path, resource_type)
+                .visit(&body, None)
+                .unwrap();
+        }
+        Err(e) => {
+            debug!(gate, "Rendering a response body that isn't JSON: {}", e);
+            write!(
+                &mut html,
+                "{}",
+                html_escape(&String::from_utf8_lossy(res.body()))
+            )
+            .unwrap();
+        }
+    }
+    write!(&mut html, "</div>").unwrap();
+    write!(&mut html, "</body></html>").unwrap();
+    html
+}
+
+fn html_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn parse_links(header_value: &str) -> Vec<(String, String)> {
+    header_value.split(',').filter_map(parse_link_rel).collect()
+}
+
+// Parse a link-value, e.g. <http://example.com/x-nmos/query/v1.3/nodes?paging.since=0:0>; rel="prev"
+// See https://www.rfc-editor.org/rfc/rfc8288#section-3
+fn parse_link_rel(link_rel: &str) -> Option<(String, String)> {
+    let mut parts = link_rel.split(';').map(str::trim);
+    let link = parts.next()?.strip_prefix('<')?.strip_suffix('>')?;
+    let rel = parts.find_map(|param| param.strip_prefix("rel="))?;
+    Some((link.to_string(), rel.trim_matches('"').to_string()))
+}
+
+// Render the JSON responses of the API as HTML, if the client prefers HTML according to its Accept
+// header, and indicate that the response depends on the Accept header
+pub fn with_html_rendering<F>(
+    api: F,
+    gate: Logger,
+) -> impl Filter<Extract = (warp::reply::Response,), Error = Rejection> + Clone
+where
+    F: Filter<Extract = (warp::reply::Response,), Error = Rejection>
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    warp::path::full()
+        .and(warp::header::optional::<String>("accept"))
+        .and(api)
+        .and_then(
+            move |path: FullPath, accept: Option<String>, mut response: warp::reply::Response| {
+                let gate = gate.clone();
+                async move {
+                    response
+                        .headers_mut()
+                        .append(header::VARY, HeaderValue::from_static("accept"));
+                    if !accept.as_deref().is_some_and(accepts_html) {
+                        return Ok::<_, Rejection>(response);
+                    }
+
+                    let (parts, contents) = response.into_parts();
+                    let contents = match body::to_bytes(contents).await {
+                        Ok(contents) => contents.to_vec(),
+                        Err(e) => {
+                            error!(gate, "Unable to read the response body: {}", e);
+                            return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+                        }
+                    };
+                    // Any JSON, e.g. a single resource as well as a list of them
+                    if serde_json::from_slice::<Value>(&contents).is_err() {
+                        return Ok(Response::from_parts(parts, Body::from(contents)));
+                    }
+
+                    let uri: Uri = path.as_str().parse().unwrap_or_default();
+                    let response = Response::from_parts(parts, contents);
+                    let html = make_html_response_body(&uri, &response, &gate);
+                    let (mut parts, _) = response.into_parts();
+                    parts.headers.remove(header::CONTENT_LENGTH);
+                    parts.headers.insert(
+                        header::CONTENT_TYPE,
+                        HeaderValue::from_static("text/html; charset=utf-8"),
+                    );
+                    Ok(Response::from_parts(parts, Body::from(html)))
+                }
+            },
+        )
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_accepts_html() {
+        assert!(accepts_html(
+            "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8"
+        ));
+        assert!(!accepts_html("*/*"));
+        assert!(!accepts_html("application/json"));
+        assert!(!accepts_html("text/html;q=0.5, application/json"));
+        assert!(!accepts_html("text/html;q=0"));
+        assert!(accepts_html("text/*, application/json;q=0.9"));
+    }
+
+    #[test]
+    fn test_make_html_response_body() {
+        let gate = Logger::root(slog::Discard, slog::o!());
+        let uri: Uri = "/x-nmos/query/v1.3/senders?paging.limit=10"
+            .parse()
+            .unwrap();
+        let body = json!([{
+            "id": "d5bb9aa8-b9c8-4d4b-9a41-2c6a8c8e4c6b",
+            "device_id": "0ed9d1c1-7e4f-4a43-8b8c-d8d3e3c5a2f0",
+            "manifest_href": "http://node.example.com/sdp/<1>",
+            "label": "<script>",
+            "tags": {},
+        }]);
+        let res = Response::builder()
+            .header(
+                header::LINK,
+                "<http://registry.example.com/x-nmos/query/v1.3/senders?paging.until=0:0>; rel=\"next\"",
+            )
+            .body(serde_json::to_vec(&body).unwrap())
+            .unwrap();
+        let html = make_html_response_body(&uri, &res, &gate);
+
+        assert!(html.contains("<a class=\"string\" href=\"/x-nmos/query/v1.3/senders/d5bb9aa8-b9c8-4d4b-9a41-2c6a8c8e4c6b\">"));
+        assert!(html
+            .contains("href=\"/x-nmos/query/v1.3/devices/0ed9d1c1-7e4f-4a43-8b8c-d8d3e3c5a2f0\""));
+        assert!(html.contains("href=\"http://node.example.com/sdp/&lt;1&gt;\""));
+        assert!(html.contains("&quot;&lt;script&gt;&quot;"));
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("<details open><summary>[</summary>"));
+        assert!(html.contains("rel=\"next\">http://registry.example.com/x-nmos/query/v1.3/senders?paging.until=0:0</a>"));
+
+        let res = Response::new(
+            serde_json::to_vec(&json!([{ "$href": "nodes/", "$_": "nodes/" }])).unwrap(),
+        );
+        let uri: Uri = "/x-nmos/query/v1.3/".parse().unwrap();
+        let html = make_html_response_body(&uri, &res, &gate);
+        assert!(html.contains("<a class=\"string\" href=\"nodes/\">&quot;nodes/&quot;</a>"));
+    }
+
+    #[tokio::test]
+    async fn test_with_html_rendering() {
+        let gate = Logger::root(slog::Discard, slog::o!());
+        let api = warp::path!("x-nmos" / "node" / "v1.3" / "self").map(|| {
+            warp::reply::json(&json!({
+                "label": "</span><script>alert(1)</script>",
+                "<script>": "'\"&",
+            }))
+            .into_response()
+        });
+        let api = with_html_rendering(api, gate);
+
+        let response = warp::test::request()
+            .path("/x-nmos/node/v1.3/self")
+            .header("accept", "text/html")
+            .reply(&api)
+            .await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            response.headers()["content-type"],
+            "text/html; charset=utf-8"
+        );
+        assert_eq!(response.headers()["vary"], "accept");
+        let html = String::from_utf8(response.body().to_vec()).unwrap();
+        assert!(!html.contains("<script"));
+        assert!(html.contains("&lt;/span&gt;&lt;script&gt;alert(1)&lt;/script&gt;"));
+        assert!(html.contains("&quot;&lt;script&gt;&quot;"));
+        assert!(html.contains("&quot;&apos;\\&quot;&amp;&quot;"));
+
+        // JSON is still the default
+        let response = warp::test::request()
+            .path("/x-nmos/node/v1.3/self")
+            .header("accept", "*/*")
+            .reply(&api)
+            .await;
+        assert_eq!(response.headers()["content-type"], "application/json");
+    }
+}
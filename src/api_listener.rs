@@ -0,0 +1,720 @@
+// API listeners, which serve either plain HTTP or HTTPS as recommended by BCP-003-01, with server
+// certificates that can be replaced while the listeners are running, so that they can be rotated
+// without a restart
+// See https://specs.amwa.tv/bcp-003-01/releases/v1.0.0/docs/1.0._Secure_Communication.html
+
+use std::convert::Infallible;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use rustls::crypto::{ring, CryptoProvider};
+use rustls::pki_types::CertificateDer;
+use rustls::server::{ClientHello, ResolvesServerCert, WebPkiClientVerifier};
+use rustls::sign::CertifiedKey;
+use rustls::{ServerConfig, SupportedCipherSuite, SupportedProtocolVersion};
+use serde::Deserialize;
+use slog::{debug, info, warn, Logger};
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+use warp::http::header::{HeaderValue, CONTENT_LENGTH};
+use warp::http::Method;
+use warp::hyper::body::to_bytes;
+use warp::hyper::server::conn::Http;
+use warp::hyper::service::{service_fn, Service};
+use warp::hyper::{Body, Request, Response};
+use warp::{Filter, Rejection, Reply};
+use x509_parser::prelude::{FromDer, GeneralName, X509Certificate};
+
+use crate::certificate::{
+    make_root_store, subject, Certificate, CertificateError, CertificateLoader,
+};
+use crate::certificate_handlers::make_server_certificate_loader;
+use crate::metrics::Metrics;
+use crate::settings::Settings;
+
+// Define which TLS versions and cipher suites are offered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CipherPolicy {
+    // TLS 1.3, and TLS 1.2 with the ECDHE and AEAD cipher suites recommended by BCP-003-01
+    // See https://specs.amwa.tv/bcp-003-01/releases/v1.0.0/docs/1.0._Secure_Communication.html#tls-versions
+    #[default]
+    #[serde(rename = "bcp_003_01")]
+    Bcp00301,
+    // TLS 1.3 only
+    Tls13,
+}
+
+static TLS13_ONLY: &[&SupportedProtocolVersion] = &[&rustls::version::TLS13];
+
+impl CipherPolicy {
+    fn protocol_versions(&self) -> &'static [&'static SupportedProtocolVersion] {
+        match self {
+            CipherPolicy::Bcp00301 => rustls::ALL_VERSIONS,
+            CipherPolicy::Tls13 => TLS13_ONLY,
+        }
+    }
+
+    fn cipher_suites(&self) -> Vec<SupportedCipherSuite> {
+        use ring::cipher_suite::*;
+        let mut suites = vec![
+            TLS13_AES_128_GCM_SHA256,
+            TLS13_AES_256_GCM_SHA384,
+            TLS13_CHACHA20_POLY1305_SHA256,
+        ];
+        if *self == CipherPolicy::Bcp00301 {
+            suites.extend([
+                TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256,
+                TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256,
+                TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384,
+                TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384,
+                TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256,
+                TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256,
+            ]);
+        }
+        suites
+    }
+
+    fn crypto_provider(&self) -> Arc<CryptoProvider> {
+        Arc::new(CryptoProvider {
+            cipher_suites: self.cipher_suites(),
+            ..ring::default_provider()
+        })
+    }
+}
+
+// Define whether clients must authenticate with a certificate (mutual TLS)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClientAuthentication {
+    #[default]
+    None,
+    // Client certificates are verified if presented, but clients without one are also accepted
+    Optional,
+    Required,
+}
+
+// The verified certificate of the client, which is added to the extensions of each request on a
+// mutual TLS connection, so that e.g. the Registration API can log which Node registered which
+// resource
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerCertificate {
+    pub subject: String,
+    pub certificate: CertificateDer<'static>,
+}
+
+// Extract the client certificate, if the client authenticated with one
+pub fn peer_certificate(
+) -> impl Filter<Extract = (Option<PeerCertificate>,), Error = Infallible> + Clone {
+    warp::ext::optional::<PeerCertificate>()
+}
+
+// A loaded server certificate, with the DNS names for which it is valid
+#[derive(Debug)]
+struct ServerCertificate {
+    names: Vec<String>,
+    key: Arc<CertifiedKey>,
+}
+
+impl ServerCertificate {
+    fn new(certificate: &Certificate, provider: &CryptoProvider) -> Result<Self, CertificateError> {
+        let chain = certificate.certificate_chain_der()?;
+        let key = provider
+            .key_provider
+            .load_private_key(certificate.private_key_der()?)
+            .map_err(|e| CertificateError::InvalidPem(e.to_string()))?;
+        Ok(ServerCertificate {
+            names: subject_names(&chain[0]),
+            key: Arc::new(CertifiedKey::new(chain, key)),
+        })
+    }
+
+    fn matches(&self, server_name: &str) -> bool {
+        let server_name = server_name.to_ascii_lowercase();
+        self.names.iter().any(|name| match name.strip_prefix("*.") {
+            Some(domain) => server_name
+                .split_once('.')
+                .is_some_and(|(_, parent)| parent == domain),
+            None => *name == server_name,
+        })
+    }
+}
+
+// The DNS names of the subject alternative name extension, or the subject common name if there are
+// none
+fn subject_names(certificate: &[u8]) -> Vec<String> {
+    let Ok((_, certificate)) = X509Certificate::from_der(certificate) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = match certificate.subject_alternative_name() {
+        Ok(Some(san)) => san
+            .value
+            .general_names
+            .iter()
+            .filter_map(|name| match name {
+                GeneralName::DNSName(name) => Some(name.to_ascii_lowercase()),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    if names.is_empty() {
+        names.extend(
+            certificate
+                .subject()
+                .iter_common_name()
+                .filter_map(|name| name.as_str().ok())
+                .map(str::to_ascii_lowercase),
+        );
+    }
+    names
+}
+
+// Select the server certificate for each TLS handshake, by the server name indication (SNI) and by
+// the signature schemes the client supports, so that e.g. an RSA and an ECDSA certificate chain can
+// be offered side by side
+// The certificates are replaced by reload, which affects new handshakes but not open connections
+#[derive(Debug)]
+pub struct CertificateResolver {
+    provider: Arc<CryptoProvider>,
+    certificates: RwLock<Vec<ServerCertificate>>,
+}
+
+impl CertificateResolver {
+    pub fn new(cipher_policy: CipherPolicy) -> Self {
+        CertificateResolver {
+            provider: cipher_policy.crypto_provider(),
+            certificates: RwLock::new(Vec::new()),
+        }
+    }
+
+    // Replace the server certificates, only if all of them are usable
+    pub fn load(&self, certificates: &[Certificate]) -> Result<(), CertificateError> {
+        let certificates = certificates
+            .iter()
+            .map(|certificate| ServerCertificate::new(certificate, &self.provider))
+            .collect::<Result<Vec<_>, _>>()?;
+        *self.certificates.write().unwrap() = certificates;
+        Ok(())
+    }
+
+    // Reload the server certificates, keeping the current ones if that fails
+    pub fn reload(&self, loader: &CertificateLoader, gate: &Logger) -> bool {
+        match loader().and_then(|certificates| self.load(&certificates)) {
+            Ok(()) => {
+                info!(gate, "Loaded server certificates");
+                true
+            }
+            Err(error) => {
+                warn!(gate, "Failed to load server certificates: {}", error);
+                false
+            }
+        }
+    }
+}
+
+impl ResolvesServerCert for CertificateResolver {
+    fn resolve(&self, client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        let certificates = self.certificates.read().unwrap();
+        let server_name = client_hello.server_name();
+        let schemes = client_hello.signature_schemes();
+
+        // Prefer a certificate for the requested server name, but otherwise fall back to any
+        // certificate with a suitable key, which the client may still accept, e.g. for an IP address
+        let named = certificates
+            .iter()
+            .filter(|certificate| server_name.is_some_and(|name| certificate.matches(name)));
+        named
+            .chain(certificates.iter())
+            .find(|certificate| certificate.key.key.choose_scheme(schemes).is_some())
+            .map(|certificate| certificate.key.clone())
+    }
+}
+
+// Make the TLS configuration for the API listeners, or None if they should serve plain HTTP
+// Client certificates are verified against the CA certificates, e.g. as loaded by
+// make_ca_certificate_loader, which are only required for client authentication
+pub fn make_server_config(
+    settings: &Settings,
+    resolver: Arc<CertificateResolver>,
+    ca_certificates: &[Certificate],
+) -> Result<Option<Arc<ServerConfig>>, rustls::Error> {
+    if !settings.server_secure {
+        return Ok(None);
+    }
+    let builder = ServerConfig::builder_with_provider(resolver.provider.clone())
+        .with_protocol_versions(settings.cipher_policy.protocol_versions())?;
+    let builder = match settings.client_authentication {
+        ClientAuthentication::None => builder.with_no_client_auth(),
+        client_authentication => {
+            let roots = make_root_store(ca_certificates)
+                .map_err(|e| rustls::Error::General(e.to_string()))?;
+            let verifier = WebPkiClientVerifier::builder_with_provider(
+                Arc::new(roots),
+                resolver.provider.clone(),
+            );
+            let verifier = if client_authentication == ClientAuthentication::Optional {
+                verifier.allow_unauthenticated()
+            } else {
+                verifier
+            };
+            let verifier = verifier.build().map_err(|e| {
+                rustls::Error::General(format!(
+                    "invalid CA certificates for client authentication: {}",
+                    e
+                ))
+            })?;
+            builder.with_client_cert_verifier(verifier)
+        }
+    };
+    let mut config = builder.with_cert_resolver(resolver);
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(Some(Arc::new(config)))
+}
+
+// Reload the server certificates from the files in the settings, at the configured interval and on
+// SIGHUP
+pub async fn run_certificate_reloader_from_settings(
+    resolver: Arc<CertificateResolver>,
+    settings: Arc<Settings>,
+    gate: Logger,
+) {
+    let interval = settings.certificate_reload_period();
+    let loader = make_server_certificate_loader(settings, gate.clone());
+    run_certificate_reloader(resolver, loader, interval, gate).await
+}
+
+// Reload the server certificates periodically, if an interval is specified, and on SIGHUP
+pub async fn run_certificate_reloader(
+    resolver: Arc<CertificateResolver>,
+    loader: CertificateLoader,
+    interval: Option<Duration>,
+    gate: Logger,
+) {
+    let mut hangup = make_hangup();
+    let mut reload_at = interval.map(|interval| tokio::time::Instant::now() + interval);
+    loop {
+        let timer = async move {
+            match reload_at {
+                Some(reload_at) => tokio::time::sleep_until(reload_at).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            _ = timer => {
+                debug!(gate, "Reloading server certificates after interval");
+            }
+            _ = recv_hangup(&mut hangup) => {
+                info!(gate, "Reloading server certificates on SIGHUP");
+            }
+        }
+        resolver.reload(&loader, &gate);
+        reload_at = interval.map(|interval| tokio::time::Instant::now() + interval);
+    }
+}
+
+#[cfg(unix)]
+type Hangup = Option<tokio::signal::unix::Signal>;
+
+#[cfg(unix)]
+fn make_hangup() -> Hangup {
+    use tokio::signal::unix::{signal, SignalKind};
+    signal(SignalKind::hangup()).ok()
+}
+
+#[cfg(not(unix))]
+type Hangup = Option<()>;
+
+#[cfg(not(unix))]
+fn make_hangup() -> Hangup {
+    None
+}
+
+// Wait for the next SIGHUP, or forever if signals are unavailable
+async fn recv_hangup(hangup: &mut Hangup) {
+    #[cfg(unix)]
+    if let Some(hangup) = hangup {
+        hangup.recv().await;
+        return;
+    }
+    #[cfg(not(unix))]
+    let _ = hangup;
+    std::future::pending().await
+}
+
+// Start an API listener on the specified address and port, which serves HTTPS if a TLS
+// configuration is specified, or plain HTTP otherwise
+// If metrics are specified, failed TLS handshakes are counted
+pub async fn start_api_listener<F, R>(
+    host_address: &str,
+    port: u16,
+    api: F,
+    tls_config: Option<Arc<ServerConfig>>,
+    metrics: Option<Arc<Metrics>>,
+    gate: Logger,
+) -> io::Result<()>
+where
+    F: Filter<Extract = (R,), Error = Rejection> + Clone + Send + Sync + 'static,
+    R: Reply,
+{
+    let host_address = if host_address.is_empty() {
+        "0.0.0.0"
+    } else {
+        host_address
+    };
+    let address: SocketAddr = format!("{}:{}", host_address, port)
+        .parse()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    match tls_config {
+        Some(tls_config) => {
+            let listener = TcpListener::bind(address).await?;
+            info!(gate, "Serving HTTPS on {}", address);
+            serve_tls(listener, api, tls_config, metrics, gate).await
+        }
+        None => {
+            let listener = TcpListener::bind(address).await?;
+            info!(gate, "Serving HTTP on {}", address);
+            serve_http(listener, api, gate).await
+        }
+    }
+}
+
+// Serve a request with the API, with the semantics of GET for HEAD requests, i.e. the same
+// headers, including the Content-Length, but no body
+// See https://www.rfc-editor.org/rfc/rfc9110#section-9.3.2
+async fn serve_request<S>(
+    mut service: S,
+    mut request: Request<Body>,
+) -> Result<Response<Body>, Infallible>
+where
+    S: Service<Request<Body>, Response = Response<Body>, Error = Infallible>,
+{
+    let head = request.method() == Method::HEAD;
+    if head {
+        *request.method_mut() = Method::GET;
+    }
+    let response = service.call(request).await?;
+    if !head {
+        return Ok(response);
+    }
+    let (mut parts, body) = response.into_parts();
+    if !parts.headers.contains_key(CONTENT_LENGTH) {
+        // The body is only read to determine its length
+        let length = to_bytes(body)
+            .await
+            .map(|body| body.len())
+            .unwrap_or_default();
+        parts
+            .headers
+            .insert(CONTENT_LENGTH, HeaderValue::from(length));
+    }
+    Ok(Response::from_parts(parts, Body::empty()))
+}
+
+// Serve plain HTTP on the listener
+pub async fn serve_http<F, R>(listener: TcpListener, api: F, gate: Logger) -> io::Result<()>
+where
+    F: Filter<Extract = (R,), Error = Rejection> + Clone + Send + Sync + 'static,
+    R: Reply,
+{
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let service = warp::service(api.clone());
+        let gate = gate.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |request| serve_request(service.clone(), request));
+            // Upgrades are needed for the WebSocket APIs
+            if let Err(error) = Http::new()
+                .serve_connection(stream, service)
+                .with_upgrades()
+                .await
+            {
+                debug!(gate, "Connection with {} failed: {}", peer, error);
+            }
+        });
+    }
+}
+
+// Serve HTTPS on the listener, performing each TLS handshake separately so that slow or failed
+// handshakes don't hold up other connections
+pub async fn serve_tls<F, R>(
+    listener: TcpListener,
+    api: F,
+    tls_config: Arc<ServerConfig>,
+    metrics: Option<Arc<Metrics>>,
+    gate: Logger,
+) -> io::Result<()>
+where
+    F: Filter<Extract = (R,), Error = Rejection> + Clone + Send + Sync + 'static,
+    R: Reply,
+{
+    let acceptor = TlsAcceptor::from(tls_config);
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let acceptor = acceptor.clone();
+        let service = warp::service(api.clone());
+        let metrics = metrics.clone();
+        let gate = gate.clone();
+        tokio::spawn(async move {
+            let stream = match acceptor.accept(stream).await {
+                Ok(stream) => stream,
+                Err(error) => {
+                    debug!(gate, "TLS handshake with {} failed: {}", peer, error);
+                    if let Some(metrics) = metrics {
+                        metrics.tls_handshake_failures.inc();
+                    }
+                    return;
+                }
+            };
+            let peer_certificate = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|chain| chain.first())
+                .map(|certificate| PeerCertificate {
+                    subject: subject(certificate),
+                    certificate: certificate.clone().into_owned(),
+                });
+            if let Some(peer_certificate) = &peer_certificate {
+                debug!(
+                    gate,
+                    "Client {} authenticated as {}", peer, peer_certificate.subject
+                );
+            }
+            let service = service_fn(move |mut request: Request<Body>| {
+                if let Some(peer_certificate) = &peer_certificate {
+                    request.extensions_mut().insert(peer_certificate.clone());
+                }
+                serve_request(service.clone(), request)
+            });
+            if let Err(error) = Http::new()
+                .serve_connection(stream, service)
+                .with_upgrades()
+                .await
+            {
+                debug!(gate, "Connection with {} failed: {}", peer, error);
+            }
+        });
+    }
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustls::pki_types::ServerName;
+    use rustls::{ClientConfig, RootCertStore};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::TlsConnector;
+
+    fn make_certificate(names: &[&str]) -> Certificate {
+        let names = names
+            .iter()
+            .map(|name| name.to_string())
+            .collect::<Vec<_>>();
+        let certified = rcgen::generate_simple_self_signed(names).unwrap();
+        Certificate::new(&certified.key_pair.serialize_pem(), &certified.cert.pem()).unwrap()
+    }
+
+    // Connect to the listener and make a request, optionally authenticating with a client
+    // certificate, returning the server's certificate and the response
+    async fn get_as(
+        address: SocketAddr,
+        server_name: &str,
+        roots: &[&Certificate],
+        client: Option<&Certificate>,
+    ) -> io::Result<(Vec<u8>, String)> {
+        let mut root_store = RootCertStore::empty();
+        for root in roots {
+            root_store.add_parsable_certificates(root.certificate_chain_der().unwrap());
+        }
+        let config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(root_store);
+        let config = match client {
+            Some(client) => config
+                .with_client_auth_cert(
+                    client.certificate_chain_der().unwrap(),
+                    client.private_key_der().unwrap(),
+                )
+                .unwrap(),
+            None => config.with_no_client_auth(),
+        };
+        let stream = tokio::net::TcpStream::connect(address).await?;
+        let server_name = ServerName::try_from(server_name.to_string()).unwrap();
+        let mut stream = TlsConnector::from(Arc::new(config))
+            .connect(server_name, stream)
+            .await?;
+        let peer_certificate = stream.get_ref().1.peer_certificates().unwrap()[0].to_vec();
+        stream
+            .write_all(b"GET /x-nmos HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        Ok((peer_certificate, response))
+    }
+
+    async fn get(
+        address: SocketAddr,
+        server_name: &str,
+        roots: &[&Certificate],
+    ) -> io::Result<(Vec<u8>, String)> {
+        get_as(address, server_name, roots, None).await
+    }
+
+    #[tokio::test]
+    async fn test_head() {
+        let gate = Logger::root(slog::Discard, slog::o!());
+        let api = warp::path!("x-nmos")
+            .and(warp::get())
+            .map(|| warp::reply::json(&["node/"]));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = tokio::spawn(serve_http(listener, api, gate));
+
+        // The same headers as GET, without the body
+        let mut stream = tokio::net::TcpStream::connect(address).await.unwrap();
+        stream
+            .write_all(b"HEAD /x-nmos HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.contains("content-length: 9\r\n"), "{}", response);
+        assert!(response.contains("content-type: application/json\r\n"));
+        assert!(response.ends_with("\r\n\r\n"));
+
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn test_serve_tls() {
+        let gate = Logger::root(slog::Discard, slog::o!());
+        let node = make_certificate(&["node.example.com"]);
+        let registry = make_certificate(&["*.registry.example.com"]);
+        let resolver = Arc::new(CertificateResolver::new(CipherPolicy::Bcp00301));
+        resolver.load(&[node.clone(), registry.clone()]).unwrap();
+
+        let settings = Settings {
+            server_secure: true,
+            ..Default::default()
+        };
+        let tls_config = make_server_config(&settings, resolver.clone(), &[])
+            .unwrap()
+            .unwrap();
+        let api = warp::path!("x-nmos").map(|| warp::reply::json(&["node/"]));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = tokio::spawn(serve_tls(listener, api, tls_config, None, gate.clone()));
+
+        // The certificate is selected by the server name
+        let roots = [&node, &registry];
+        let (peer, response) = get(address, "node.example.com", &roots).await.unwrap();
+        assert_eq!(peer, node.certificate_chain_der().unwrap()[0].to_vec());
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with(r#"["node/"]"#));
+        let (peer, _) = get(address, "api.registry.example.com", &roots)
+            .await
+            .unwrap();
+        assert_eq!(peer, registry.certificate_chain_der().unwrap()[0].to_vec());
+
+        // New connections use the reloaded certificates
+        let rotated = make_certificate(&["node.example.com"]);
+        let loader: CertificateLoader = {
+            let rotated = rotated.clone();
+            Box::new(move || Ok(vec![rotated.clone()]))
+        };
+        assert!(resolver.reload(&loader, &gate));
+        assert!(get(address, "node.example.com", &[&node]).await.is_err());
+        let (peer, _) = get(address, "node.example.com", &[&rotated]).await.unwrap();
+        assert_eq!(peer, rotated.certificate_chain_der().unwrap()[0].to_vec());
+
+        // The current certificates are kept if the reloaded ones are unusable
+        let loader: CertificateLoader = Box::new(|| Certificate::from_pem("").map(|c| vec![c]));
+        assert!(!resolver.reload(&loader, &gate));
+        assert!(get(address, "node.example.com", &[&rotated]).await.is_ok());
+
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn test_client_authentication() {
+        let gate = Logger::root(slog::Discard, slog::o!());
+        let mut ca_params = rcgen::CertificateParams::new(Vec::new()).unwrap();
+        ca_params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+        let ca_key = rcgen::KeyPair::generate().unwrap();
+        let ca_certificate = ca_params.self_signed(&ca_key).unwrap();
+        let ca = Certificate::from_pem(&ca_certificate.pem()).unwrap();
+
+        let mut node_params = rcgen::CertificateParams::new(Vec::new()).unwrap();
+        node_params
+            .distinguished_name
+            .push(rcgen::DnType::CommonName, "node.example.com");
+        let node_key = rcgen::KeyPair::generate().unwrap();
+        let node_certificate = node_params
+            .signed_by(&node_key, &ca_certificate, &ca_key)
+            .unwrap();
+        let node = Certificate::new(&node_key.serialize_pem(), &node_certificate.pem()).unwrap();
+        let stranger = make_certificate(&["stranger.example.com"]);
+
+        let registry = make_certificate(&["registry.example.com"]);
+        let resolver = Arc::new(CertificateResolver::new(CipherPolicy::Bcp00301));
+        resolver.load(std::slice::from_ref(&registry)).unwrap();
+        let settings = Settings {
+            server_secure: true,
+            client_authentication: ClientAuthentication::Required,
+            ..Default::default()
+        };
+        let tls_config = make_server_config(&settings, resolver, std::slice::from_ref(&ca))
+            .unwrap()
+            .unwrap();
+
+        // The handler can identify the client by its certificate
+        let api = warp::path!("x-nmos").and(peer_certificate()).map(
+            |peer_certificate: Option<PeerCertificate>| {
+                peer_certificate
+                    .map(|peer| peer.subject)
+                    .unwrap_or_default()
+            },
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let metrics = Arc::new(Metrics::new().unwrap());
+        let server = tokio::spawn(serve_tls(
+            listener,
+            api,
+            tls_config,
+            Some(metrics.clone()),
+            gate,
+        ));
+
+        let roots = [&registry];
+        let (_, response) = get_as(address, "registry.example.com", &roots, Some(&node))
+            .await
+            .unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with("CN=node.example.com"));
+
+        // Clients without a certificate issued by the CA are rejected
+        assert!(get(address, "registry.example.com", &roots).await.is_err());
+        assert!(
+            get_as(address, "registry.example.com", &roots, Some(&stranger))
+                .await
+                .is_err()
+        );
+        for _ in 0..100 {
+            if metrics.tls_handshake_failures.get() == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(metrics.tls_handshake_failures.get(), 2);
+
+        server.abort();
+    }
+}
@@ -0,0 +1,163 @@
+// The methods allowed by each route of an API, so that OPTIONS requests are answered with an
+// accurate Allow header, and requests with other methods get a 405 Method Not Allowed that lists
+// the allowed methods
+// HEAD requests are served with the semantics of GET by the API listeners
+// See https://www.rfc-editor.org/rfc/rfc9110#section-15.5.6
+
+use serde_json::json;
+use warp::http::header::{HeaderValue, ALLOW};
+use warp::http::{Method, StatusCode};
+use warp::path::FullPath;
+use warp::reply::Response;
+use warp::{Filter, Rejection, Reply};
+
+// A route, where "{}" matches any one path segment, and the methods it allows, e.g.
+// ("/x-nmos/configuration/v1.0/rolePaths/{}/methods/{}", &[Method::GET, Method::PATCH])
+pub type RouteMethods = (&'static str, &'static [Method]);
+
+// Match the request path to the route, ignoring any trailing slash
+fn matches(route: &str, path: &str) -> bool {
+    let mut route = route.split('/').filter(|segment| !segment.is_empty());
+    let mut path = path.split('/').filter(|segment| !segment.is_empty());
+    loop {
+        match (route.next(), path.next()) {
+            (None, None) => return true,
+            (Some(expected), Some(segment)) if expected == "{}" || expected == segment => {}
+            _ => return false,
+        }
+    }
+}
+
+// Find the methods allowed for the request path, including HEAD if GET is allowed, and OPTIONS
+pub fn allowed_methods(routes: &[RouteMethods], path: &str) -> Option<Vec<Method>> {
+    let (_, methods) = routes.iter().find(|(route, _)| matches(route, path))?;
+    let mut allowed = methods.to_vec();
+    if allowed.contains(&Method::GET) && !allowed.contains(&Method::HEAD) {
+        allowed.push(Method::HEAD);
+    }
+    if !allowed.contains(&Method::OPTIONS) {
+        allowed.push(Method::OPTIONS);
+    }
+    Some(allowed)
+}
+
+pub fn make_allow_header(methods: &[Method]) -> HeaderValue {
+    let methods = methods
+        .iter()
+        .map(Method::as_str)
+        .collect::<Vec<_>>()
+        .join(", ");
+    HeaderValue::from_str(&methods).expect("method names are valid header values")
+}
+
+fn make_method_not_allowed_response(allow: HeaderValue) -> Response {
+    let code = StatusCode::METHOD_NOT_ALLOWED;
+    let mut response = warp::reply::with_status(
+        warp::reply::json(&json!({
+            "code": code.as_u16(),
+            "error": code.canonical_reason(),
+            "debug": null,
+        })),
+        code,
+    )
+    .into_response();
+    response.headers_mut().insert(ALLOW, allow);
+    response
+}
+
+// Answer OPTIONS requests, and requests with methods that aren't allowed, for the routes of the API,
+// and pass any other requests on to the API
+pub fn with_allowed_methods<F>(
+    api: F,
+    routes: &'static [RouteMethods],
+) -> impl Filter<Extract = (Response,), Error = Rejection> + Clone
+where
+    F: Filter<Extract = (Response,), Error = Rejection> + Clone + Send + Sync + 'static,
+{
+    let methods = warp::method().and(warp::path::full()).and_then(
+        move |method: Method, path: FullPath| async move {
+            let allowed =
+                allowed_methods(routes, path.as_str()).ok_or_else(warp::reject::not_found)?;
+            // HEAD requests are passed on as GET requests by the API listeners
+            if method == Method::OPTIONS {
+                let mut response = Response::new(Default::default());
+                response
+                    .headers_mut()
+                    .insert(ALLOW, make_allow_header(&allowed));
+                Ok(response)
+            } else if !allowed.contains(&method) {
+                Ok(make_method_not_allowed_response(make_allow_header(
+                    &allowed,
+                )))
+            } else {
+                Err(warp::reject::not_found())
+            }
+        },
+    );
+
+    methods.or(api).unify()
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROUTES: &[RouteMethods] = &[
+        ("/x-nmos/connection/v1.1/single/senders", &[Method::GET]),
+        (
+            "/x-nmos/connection/v1.1/single/senders/{}/staged",
+            &[Method::GET, Method::PATCH],
+        ),
+    ];
+
+    #[tokio::test]
+    async fn test_allowed_methods() {
+        let api = warp::path!(
+            "x-nmos" / "connection" / "v1.1" / "single" / "senders" / String / "staged"
+        )
+        .and(warp::get().or(warp::patch()).unify())
+        .map(|_| warp::reply::json(&json!({})).into_response());
+        let api = with_allowed_methods(api, ROUTES);
+
+        let path = "/x-nmos/connection/v1.1/single/senders/abc/staged";
+        let response = warp::test::request()
+            .method("OPTIONS")
+            .path(path)
+            .reply(&api)
+            .await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()["allow"], "GET, PATCH, HEAD, OPTIONS");
+
+        let response = warp::test::request()
+            .method("DELETE")
+            .path(path)
+            .reply(&api)
+            .await;
+        assert_eq!(response.status(), 405);
+        assert_eq!(response.headers()["allow"], "GET, PATCH, HEAD, OPTIONS");
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["code"], 405);
+
+        let response = warp::test::request()
+            .method("PATCH")
+            .path(path)
+            .reply(&api)
+            .await;
+        assert_eq!(response.status(), 200);
+
+        let response = warp::test::request()
+            .method("POST")
+            .path("/x-nmos/connection/v1.1/single/senders/")
+            .reply(&api)
+            .await;
+        assert_eq!(response.headers()["allow"], "GET, HEAD, OPTIONS");
+
+        let response = warp::test::request()
+            .method("OPTIONS")
+            .path("/x-nmos/connection/v1.1/bulk")
+            .reply(&api)
+            .await;
+        assert_eq!(response.status(), 404);
+    }
+}
@@ -1,4 +1,4 @@
-use http::{header, StatusCode};
+use warp::http::{header, StatusCode};
 use hyper;
 use pplx;
 use serde_json;
@@ -65,27 +65,8 @@ async fn extract_json<T: hyper::Body + Send + 'static>(
         .map(|chunk| serde_json::from_slice(&chunk).unwrap_or_default())
 }
 
-// Add the NMOS-specific CORS response headers
-fn add_cors_preflight_headers(
-    req: &http::Request<hyper::Body>,
-    mut res: http::Response<hyper::Body>,
-) -> http::Response<hyper::Body> {
-    if let Some(methods) = res.headers().get(header::ALLOW) {
-        res.headers_mut()
-            .insert(header::ACCESS_CONTROL_ALLOW_METHODS, methods.clone());
-        res.headers_mut().remove(header::ALLOW);
-    }
-
-    if let Some(headers) = req.headers().get(header::ACCESS_CONTROL_REQUEST_HEADERS) {
-        res.headers_mut()
-            .insert(header::ACCESS_CONTROL_ALLOW_HEADERS, headers.clone());
-    }
-
-    res.headers_mut()
-        .insert(header::ACCESS_CONTROL_MAX_AGE, "86400".parse().unwrap());
-
-    res
-}
+// The CORS response headers, including those of preflight responses, are added to every API by
+// crate::header_policy::with_header_policy
 
 // Map from a resourceType, i.e the plural string used in the API endpoint routes, to a "proper"
 // type.
@@ -148,7 +129,9 @@ fn make_sub_routes_body(
 
     if let Some(body) = res.into_body().chunks().next() {
         if let Ok(body) = body {
-            if let Ok(body) = serde_json::from_slice::<Vec<serde_json::Value>>(&body) {
+            if let Ok(serde_json::Value::Array(body)) =
+                serde_json::from_slice::<serde_json::Value>(&body)
+            {
                 results.extend(body.into_iter());
             }
         }
@@ -157,7 +140,7 @@ fn make_sub_routes_body(
     // Experimental extension, to support human-readable HTML rendering of NMOS responses
     if let Some(accept) = req.headers().get(header::ACCEPT) {
         let accept = accept.to_str().unwrap_or_default();
-        if accept.contains("text/html") {
+        if crate::api_html::accepts_html(accept) {
             for sub_route in sub_routes {
                 results.insert(
                     json!({"$href": format!("{}{}", req.uri(), sub_route), "$_": sub_route}),
@@ -176,166 +159,22 @@ fn make_api_version_sub_routes(versions: &HashSet<api_version::ApiVersion>) -> H
 
 // Construct a standard NMOS error response, using the default reason phrase if no user error
 // information is specified.
-fn make_error_response_body(
+// See https://specs.amwa.tv/is-04/releases/v1.3.2/APIs/schemas/with-refs/error.html
+pub fn make_error_response_body(
     code: StatusCode,
     error: &str,
     debug: Option<&str>,
 ) -> serde_json::Value {
-    let mut body = serde_json::json!({
+    let error = if error.is_empty() {
+        code.canonical_reason().unwrap_or_default()
+    } else {
+        error
+    };
+    serde_json::json!({
         "code": code.as_u16(),
         "error": error,
-        "debug": debug.unwrap_or_default(),
-    });
-
-    serde_json::to_value(&body).unwrap();
-}
-
-mod experimental {
-    use http::{header, StatusCode};
-    use serde_json::Value;
-    use slog::Logger;
-    use std::collections::HashMap;
-    use std::gmt::Write as _;
-    use std::str::FromStr;
-    use url::Url;
-
-    const HEADERS_STYLESHEET: &str = r"-stylesheet-(
-        .headers {
-            font-family: monospace;
-            color: grey;
-            border-bottom: 1px solid lightgrey;
-        }
-        .headers ol {
-            list-style: none;
-            padding: 0;
-        }
-    )-stylesheet-";
-
-    // Objects with the keywords $href and $_ and rendered as HTML anchor(a) tags.
-    // This allows elements in NMOS "child resources" responses to be made into links,
-    // and id values in resources can also be made into links to the appropriate resource.
-    struct HtmlVisitor<'a, W> {
-        writer: W,
-        names: HashMap<&'static str, &'a str>,
-    }
-
-    impl<'a, W: Write> HtmlVisitor<'a, W> {
-        fn new(writer: W) -> Self {
-            let mut names = HashMap::new();
-            names.insert("http", "http://");
-            names.insert("https", "https://");
-            names.insert("ws", "ws://");
-            names.insert("wss", "wss://");
-            HtmlVisitor { writer, names }
-        }
-
-        fn write_href(&mut self, href: &str) -> std::fmt::Result {
-            let is_href = self.is_href(href);
-            self.writer.write_char('"')?;
-            if is_href {
-                self.writer.write_str("<a href=\"")?;
-            }
-            self.writer.write_str(href)?;
-            if is_href {
-                self.writer.write_str("\">")?;
-            }
-            self.writer.write_char('"');
-            Ok(())
-        }
-
-        fn is_href(&self, href: &str) -> bool {
-            self.names
-                .iter()
-                .any(|(_, scheme)| href.starts_with(scheme))
-        }
-    }
-
-    // Construct an HTML rendering of an NMOS response
-    pub fn make_html_response_body(res: &http::Response<Vec<u8>>, gate: &Logger) -> String {
-        let mut html = String::new();
-        write!(&mut html, "<html><head>").unwrap();
-        write!(&mut html, "<style>{}</style>", HEADERS_STYLESHEET).unwrap();
-        write!(&mut html, "</head><body>").unwrap();
-        write!(&mut html, "<div class=\"headers\"><ol>").unwrap();
-        for (header_namem, healder_value) in res.headers() {
-            write!(
-                &mut html,
-                "<li><span class=\"name\">{}</span>: <span class=\"value\">",
-                header_name.as_str()
-            )
-            .unwrap();
-            if header_name == header::LOCATION {
-                let html_value = html_escape(header_value.to_str().unwrap_or_default());
-                write!(&mut html, "<a href=\"{}\">{}</a>", html_value, html_value).unwrap();
-            } else if header_name == "Link" {
-                for (link, rel) in parse_links(header_value.to_str().unwrap_or_default()) {
-                    let html_link = html_escape(&link);
-                    let html_rel = html_escape(&rel);
-                    write!(
-                        &mut html,
-                        "<{}<a href=\"{}\" rel=\"{}\">{}</a>",
-                        html_link, html_link, html_link, html_rel,
-                    )
-                    .unwrap();
-                }
-            } else {
-                write!(
-                    &mut html,
-                    "{}",
-                    html_escape(header_value.to_str().unwrap_or_default())
-                )
-                .unwrap();
-            }
-            write!(&mut html, "</span></li>").unwrap();
-        }
-        write!(&mut html, "</ol></div><br/>").unwrap();
-        write!(&mut html, "<div class=\"json gutter\">").unwrap();
-        // Here you should include the JSON body of the response
-        //
-        // For now, we just include a placeholder
-        write!(&mut html, "</div>").unwrap();
-        write!(&mut html, "</body></html>").unwrap();
-        html
-    }
-
-    fn html_escape(text: &str) -> String {
-        text.chars()
-            .flat_map(|c| match c {
-                '<' => "&lt;".chars(),
-                '>' => "&gt;".chars(),
-                '&' => "&amp;".chars(),
-                '"' => "&quot;".chars(),
-                '\'' => "&apos;".chars(),
-                _ => Some(c),
-            })
-            .collect()
-    }
-
-    fn parse_links(header_value: &str) -> Vec<(String, String)> {
-        let mut links = Vec::new();
-        for link_rel in header_value.split(", ") {
-            if let Some((link, rel)) = parse_link_rel(link_rel) {
-                links.push((link, rel));
-            }
-        }
-        links
-    }
-
-    fn parse_link_rel(link_rel: &str) -> Option<(String, String)> {
-        let mut parts = link_rel.split(": ");
-        if let (Some(link), Some(rel)) = (parts.next(), parts.next()) {
-            if link.starts_with('<')
-                && link.ends_with('>')
-                && rel.starts_with("rel=\"")
-                && rel.ends_with('"')
-            {
-                let link = link[1..link.len() - 1].to_string();
-                let rel = rel[5..rel.len() - 1].to_string();
-                return Some((link, rel));
-            }
-        }
-        None
-    }
+        "debug": debug,
+    })
 }
 
 mod details {
@@ -343,8 +182,6 @@ mod details {
     use slog::Logger;
     use std::fmt::Write as _;
 
-    use super::add_cors_preflight_headers;
-
     // Make user error information (to be used with status_codes::NotFound)
     fn make_eased_resource_error() -> String {
         "resource has recently expired or been deleted".to_string()
@@ -362,53 +199,17 @@ mod details {
     ) -> pplx::task<bool> {
         move |mut req, mut res, _, _| {
             let gate = api_gate(&gate_, &req, &parameters);
-            let received_time = req
-                .headers()
-                .get("Received-Time")
-                .map(|val| val.to_str().unwrap_or_default());
-            let processing_dur = received_time.map_or(0.0, |time| {
-                let received_time = chrono::DateTime::parse_from_rfc2822(time).unwrap_or_default();
-                (chrono::Utc::now() - received_time).num_milliseconds() as f64
-            });
-
-            // Add Server-Timing response header
-            if let Some(received_time) = received_time {
-                req.headers_mut().remove("Received-Time");
-                res.headers_mut()
-                    .insert(header::SERVER_TIMING, received_time);
-                res.headers_mut().insert(header::TIMING_ALLOW_ORIGIN, "*");
-            }
+
+            // The Server-Timing header, and the log of the request with its duration, are added by
+            // crate::server_timing::with_server_timing
 
             if let Some(hsts) = hsts {
                 res.headers_mut()
                     .insert(header::STRICT_TRANSPORT_SECURITY, hsts);
             }
 
-            // If it was a HEAD request, restore that and discard any response body.
-            if let Some(method) = req.headers().get("Actual-Method") {
-                if method == "HEAD" {
-                    req.set_method(http::Method::HEAD);
-                    req.headers_mut().remove("Actual-Method");
-                    if res.body().is_some() {
-                        res.set_body(hyper::Body::empty());
-                    }
-                }
-            }
-
-            if res.status().is_success() {
-                res.set_status_code(StatusCode::NOT_FOUND);
-            } // ??
-
-            if res.status() == StatusCode::METHOD_NOT_ALLOWED {
-                res.headers_mut()
-                    .insert(header::ALLOW, methods::OPTIONS.to_string().parse().unwrap());
-                if req.method() == http::Method::OPTIONS {
-                    res.set_status_code(StatusCode::OK);
-                    if req.headers().contains_key("Access-Control-Request-Method") {
-                        add_cors_preflight_headers(&mut req, &mut res);
-                    }
-                }
-            }
+            // HEAD requests are served with the semantics of GET by the API listeners, and OPTIONS
+            // requests and 405 responses get an Allow header from crate::api_methods
 
             if res.status() == StatusCode::NOT_FOUND {
                 slog::error!(gate, "Route not found");
@@ -418,29 +219,8 @@ mod details {
                 res.set_body(make_error_response_body(res.status()));
             }
 
-            // Indicate that the Accept request header may affect the response
-            res.headers_mut().insert(header::VARY, header::ACCEPT);
-
-            // Experimental extension to support human-readable HTML rendering of NMOS responses
-            if let Some(accept) = req.headers().get(header::ACCEPT) {
-                if accept.to_str().unwrap_or_default().contains("text/html") {
-                    if let Some(body) = res.body_mut() {
-                        if let Ok(body) = body.bytes() {
-                            if let Ok(body) =
-                                serde_json::from_slice::<Vec<serde_json::Value>>(&body)
-                            {
-                                res.set_body(hyper::Body::from(
-                                    experimental::make_html_response_body(&res, &gate),
-                                ));
-                                res.headers_mut()
-                                    .insert(header::CONTENT_TYPE, "text/html".parse().unwrap());
-                            }
-                        }
-                    }
-                }
-            }
-
-            slog::info!(gate, "Sending response after {}ms", processing_dur);
+            // The Vary header, and the experimental human-readable HTML rendering of NMOS
+            // responses, are added by crate::api_html::with_html_rendering
 
             // The task returned by reply() silently 'observes' any exception thrown from the
             // underlying server reply() itself can throw http::exception if a response has already
@@ -460,7 +240,7 @@ mod details {
     }
 }
 
-use slog::{error, info, Logger};
+use slog::{error, Logger};
 use std::convert::Infallible;
 use std::error::Error;
 use std::fmt::Write as _;
@@ -567,33 +347,9 @@ fn make_api_finally_handler(
             move |req: http::Request<Vec<u8>>| {
                 let gate = gate.clone();
                 async move {
-                    let processing_dur = match req.headers().get("Received-Time") {
-                        Some(received_time) => {
-                            let received_time = String::from_utf8_lossy(received_time.as_bytes());
-                            let received_time =
-                                chrono::DateTime::parse_from_rfc2822(&received_time.to_string())
-                                    .unwrap_or_default();
-                            (chrono::Utc::now() - received_time).num_milliseconds() as f64
-                        }
-                        None => 0.0,
-                    };
-
-                    // Add Server-timing response header
+                    // The Server-Timing header, and the log of the request with its duration,
+                    // are added by crate::server_timing::with_server_timing
                     let mut response = warp::reply();
-                    let received_time = match req.headers().get("Received-Time") {
-                        Some(received_time) => {
-                            let received_time =
-                                String::from_utf8_lossy(received_time.as_bytes()).to_string();
-                            response
-                                .headers_mut()
-                                .insert("Server-Timing", received_time);
-                            response
-                                .headers_mut()
-                                .insert("Timing-Allow-Origin", "*".parse().unwrap());
-                            Some(received_time)
-                        }
-                        None => None,
-                    };
 
                     if let Some(hsts) = &hsts {
                         response
@@ -601,32 +357,9 @@ fn make_api_finally_handler(
                             .insert("Strict-Transport-Security", hsts.clone());
                     }
 
-                    // Set Status code
-                    if response.status() == http::StatusCode::OK {
-                        *response.status_mut() = http::StatusCode::NOT_FOUND;
-                    }
-
-                    // Handle OPTIONS method
-                    if req.method() == http::Method::OPTIONS {
-                        if let Some(method) = req.headers().get("Access-Control-Request-Method") {
-                            response.headers_mut().insert(
-                                "Allow",
-                                method.to_str().unwrap_or_default().parse().unwrap(),
-                            );
-                            *response.status_mut() = http::StatusCode::OK;
-                        }
-                    }
-
-                    // Handle HEAD method
-                    if let Some(method) = req.headers().get("Actual-Method") {
-                        if method == "HEAD" {
-                            *req.method_mut() = http::Method::HEAD;
-                            req.headers_mut().remove("Actual-Method");
-                            *response.body_mut() = Vec::new();
-                        }
-                    }
-
-                    info!(gate, "Sending response after {}ms", processing_dur);
+                    // HEAD requests are served with the semantics of GET by the API listeners, and
+                    // OPTIONS requests and 405 responses get an Allow header from
+                    // crate::api_methods
 
                     Ok(response)
                 }
@@ -660,26 +393,5 @@ fn to_pem(public_key: &str) -> String {
     pem
 }
 
-// Start the API listener with the specified configurations
-async fn start_api_listener(
-    secure: bool,
-    host_address: String,
-    port: u16,
-    api: warp::Filter<impl warp::Reply>,
-    config: HttpListenerConfig,
-    gate: Logger,
-) -> Result<(), Box<dyn Error>> {
-    let addr = format!("{}:{}", host_address, port);
-    let listener = warp::serve(api)
-        .tls()
-        .cert_path(config.cert_path)
-        .key_path(config.key_path);
-    if let Some(hsts) = hsts {
-        listener
-            .h2()
-            .prepend(warp::reply::header("Strict-Transport-Security", hsts));
-    }
-
-    listener.run(addr.parse()?).await;
-    Ok(())
-}
+// The API listeners, which serve either plain HTTP or HTTPS, are started by
+// crate::api_listener::start_api_listener
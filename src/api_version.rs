@@ -1,7 +1,9 @@
 use std::fmt;
 
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
 // Define the API version structure
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct ApiVersion {
     pub major: u32,
     pub minor: u32,
@@ -16,12 +18,12 @@ impl fmt::Display for ApiVersion {
 
 // Implement methods for parsing and making API versions
 impl ApiVersion {
-    pub fn new(major: u32, minor: u32) -> Self {
+    pub const fn new(major: u32, minor: u32) -> Self {
         ApiVersion { major, minor }
     }
 
     pub fn parse(version: &str) -> Option<Self> {
-        let parts: Vec<&str> = version.split('.').collect();
+        let parts: Vec<&str> = version.strip_prefix('v')?.split('.').collect();
         if parts.len() != 2 {
             return None;
         }
@@ -33,6 +35,21 @@ impl ApiVersion {
     }
 }
 
+// API versions are represented in JSON as strings, e.g. "v1.3" in the api.versions of a Node
+impl Serialize for ApiVersion {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ApiVersion {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let version = String::deserialize(deserializer)?;
+        ApiVersion::parse(&version)
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid API version \"{}\"", version)))
+    }
+}
+
 // Unit tests for the API version structure
 #[cfg(test)]
 mod tests {
@@ -52,4 +69,12 @@ mod tests {
         assert_eq!(ApiVersion::parse("1.2"), None);
         assert_eq!(ApiVersion::parse("v1"), None);
     }
+
+    #[test]
+    fn test_serde() {
+        let versions: Vec<ApiVersion> = serde_json::from_str(r#"["v1.0", "v1.3"]"#).unwrap();
+        assert_eq!(versions, vec![ApiVersion::new(1, 0), ApiVersion::new(1, 3)]);
+        assert_eq!(serde_json::to_string(&versions[1]).unwrap(), r#""v1.3""#);
+        assert!(serde_json::from_str::<ApiVersion>(r#""1.3""#).is_err());
+    }
 }
@@ -0,0 +1,624 @@
+// A minimal IS-10 authorization server, for exercising secured NMOS APIs without any external
+// services
+// See https://specs.amwa.tv/is-10/releases/v1.0.0/docs/4.0._Behaviour.html
+// and https://specs.amwa.tv/is-10/releases/v1.0.0/docs/4.4._Behaviour_-_Access_Tokens.html
+//
+// This is only intended for integration testing, e.g. of make_validate_authorization_handler,
+// so e.g. client secrets are kept in memory and only the client_credentials grant is supported.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine as _;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use rsa::pkcs1::{EncodeRsaPrivateKey, LineEnding};
+use rsa::traits::PublicKeyParts;
+use rsa::RsaPrivateKey;
+use serde_json::{json, Map, Value};
+use slog::{info, warn, Logger};
+use warp::http::StatusCode;
+use warp::{Filter, Rejection, Reply};
+
+use crate::header_policy::{make_header_policy, with_header_policy};
+use crate::metrics::{with_request_metrics, Metrics};
+use crate::server_timing::with_server_timing;
+use crate::settings::Settings;
+
+// The IS-10 scopes, one for each NMOS API that may be protected
+// See https://specs.amwa.tv/is-10/releases/v1.0.0/docs/4.3._Behaviour_-_Scopes.html
+pub const SCOPES: &[&str] = &[
+    "connection",
+    "node",
+    "query",
+    "registration",
+    "events",
+    "channelmapping",
+];
+
+// Define the settings for the mock authorization server
+#[derive(Debug, Clone)]
+pub struct AuthorizationServerSettings {
+    // Used as the "iss" claim, and as the base of the endpoint URLs in the server metadata
+    pub issuer: String,
+    // Used as the "aud" claim, e.g. the host names of the Resource Servers under test
+    pub audience: Vec<String>,
+    // Lifetime of issued access tokens in seconds
+    pub token_lifetime: u64,
+    // Scopes granted when neither the token request nor the client registration specifies any
+    pub default_scopes: Vec<String>,
+    // Extra claims merged into every access token, e.g. to override the "x-nmos-*" claims
+    pub additional_claims: Map<String, Value>,
+    // Key ID used in the JWT header and the JSON Web Key Set
+    pub key_id: String,
+}
+
+impl Default for AuthorizationServerSettings {
+    fn default() -> Self {
+        AuthorizationServerSettings {
+            issuer: "http://localhost:8089".to_string(),
+            audience: vec!["*".to_string()],
+            token_lifetime: 3600,
+            default_scopes: SCOPES.iter().map(|scope| scope.to_string()).collect(),
+            additional_claims: Map::new(),
+            key_id: "mock-authorization-server".to_string(),
+        }
+    }
+}
+
+// Define the errors reported by the token and client registration endpoints
+// See https://www.rfc-editor.org/rfc/rfc6749#section-5.2
+// and https://www.rfc-editor.org/rfc/rfc7591#section-3.2.2
+#[derive(Debug)]
+pub enum AuthorizationServerError {
+    InvalidRequest(String),
+    InvalidClient,
+    InvalidScope(String),
+    UnsupportedGrantType(String),
+    InvalidClientMetadata(String),
+    Key(String),
+}
+
+impl AuthorizationServerError {
+    // The OAuth 2.0 error code for the response body
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            AuthorizationServerError::InvalidRequest(_) => "invalid_request",
+            AuthorizationServerError::InvalidClient => "invalid_client",
+            AuthorizationServerError::InvalidScope(_) => "invalid_scope",
+            AuthorizationServerError::UnsupportedGrantType(_) => "unsupported_grant_type",
+            AuthorizationServerError::InvalidClientMetadata(_) => "invalid_client_metadata",
+            AuthorizationServerError::Key(_) => "server_error",
+        }
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            AuthorizationServerError::InvalidClient => StatusCode::UNAUTHORIZED,
+            AuthorizationServerError::Key(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+impl fmt::Display for AuthorizationServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthorizationServerError::InvalidRequest(message) => write!(f, "{}", message),
+            AuthorizationServerError::InvalidClient => write!(f, "client authentication failed"),
+            AuthorizationServerError::InvalidScope(scope) => write!(f, "invalid scope: {}", scope),
+            AuthorizationServerError::UnsupportedGrantType(grant_type) => {
+                write!(f, "unsupported grant type: {}", grant_type)
+            }
+            AuthorizationServerError::InvalidClientMetadata(message) => write!(f, "{}", message),
+            AuthorizationServerError::Key(message) => write!(f, "signing key error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for AuthorizationServerError {}
+
+// Define a registered client
+#[derive(Debug, Clone)]
+pub struct ClientRegistration {
+    pub client_id: String,
+    // None for public clients, i.e. token_endpoint_auth_method "none"
+    pub client_secret: Option<String>,
+    pub client_name: String,
+    pub scopes: Vec<String>,
+    pub issued_at: u64,
+}
+
+// Define the mock authorization server state
+pub struct AuthorizationServer {
+    settings: AuthorizationServerSettings,
+    private_key: RsaPrivateKey,
+    encoding_key: EncodingKey,
+    clients: Mutex<HashMap<String, ClientRegistration>>,
+}
+
+impl AuthorizationServer {
+    // Create an authorization server with a freshly generated RSA signing key
+    pub fn new(settings: AuthorizationServerSettings) -> Result<Self, AuthorizationServerError> {
+        let private_key = RsaPrivateKey::new(&mut rand::thread_rng(), 2048)
+            .map_err(|e| AuthorizationServerError::Key(e.to_string()))?;
+        Self::with_private_key(settings, private_key)
+    }
+
+    // Create an authorization server with the specified RSA signing key, e.g. so that tokens
+    // remain valid across restarts
+    pub fn with_private_key(
+        settings: AuthorizationServerSettings,
+        private_key: RsaPrivateKey,
+    ) -> Result<Self, AuthorizationServerError> {
+        let pem = private_key
+            .to_pkcs1_pem(LineEnding::LF)
+            .map_err(|e| AuthorizationServerError::Key(e.to_string()))?;
+        let encoding_key = EncodingKey::from_rsa_pem(pem.as_bytes())
+            .map_err(|e| AuthorizationServerError::Key(e.to_string()))?;
+
+        Ok(AuthorizationServer {
+            settings,
+            private_key,
+            encoding_key,
+            clients: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn settings(&self) -> &AuthorizationServerSettings {
+        &self.settings
+    }
+
+    // Construct the Authorization Server Metadata
+    // See https://www.rfc-editor.org/rfc/rfc8414#section-2
+    pub fn metadata(&self) -> Value {
+        let issuer = self.settings.issuer.trim_end_matches('/');
+        json!({
+            "issuer": issuer,
+            "token_endpoint": format!("{}/token", issuer),
+            "jwks_uri": format!("{}/jwks", issuer),
+            "registration_endpoint": format!("{}/register", issuer),
+            "scopes_supported": SCOPES,
+            "grant_types_supported": ["client_credentials"],
+            "token_endpoint_auth_methods_supported": ["client_secret_basic", "client_secret_post", "none"],
+            "token_endpoint_auth_signing_alg_values_supported": ["RS256"],
+        })
+    }
+
+    // Construct the JSON Web Key Set containing the public key used to sign access tokens
+    // See https://www.rfc-editor.org/rfc/rfc7517#section-5
+    pub fn jwks(&self) -> Value {
+        json!({
+            "keys": [{
+                "kty": "RSA",
+                "n": URL_SAFE_NO_PAD.encode(self.private_key.n().to_bytes_be()),
+                "e": URL_SAFE_NO_PAD.encode(self.private_key.e().to_bytes_be()),
+                "alg": "RS256",
+                "use": "sig",
+                "kid": self.settings.key_id,
+            }]
+        })
+    }
+
+    // Register a client from the specified client metadata, returning the client information
+    // response
+    // See https://www.rfc-editor.org/rfc/rfc7591#section-3.2.1
+    pub fn register_client(&self, metadata: &Value) -> Result<Value, AuthorizationServerError> {
+        let metadata = metadata.as_object().ok_or_else(|| {
+            AuthorizationServerError::InvalidClientMetadata(
+                "client metadata must be an object".to_string(),
+            )
+        })?;
+
+        if let Some(grant_types) = metadata.get("grant_types").and_then(Value::as_array) {
+            if !grant_types
+                .iter()
+                .any(|grant_type| grant_type == "client_credentials")
+            {
+                return Err(AuthorizationServerError::InvalidClientMetadata(
+                    "only the client_credentials grant is supported".to_string(),
+                ));
+            }
+        }
+
+        let scopes = match metadata.get("scope").and_then(Value::as_str) {
+            Some(scope) => parse_scopes(scope)?,
+            None => self.settings.default_scopes.clone(),
+        };
+
+        let auth_method = metadata
+            .get("token_endpoint_auth_method")
+            .and_then(Value::as_str)
+            .unwrap_or("client_secret_basic");
+        let client_secret = match auth_method {
+            "none" => None,
+            "client_secret_basic" | "client_secret_post" => Some(make_random_string(32)),
+            _ => {
+                return Err(AuthorizationServerError::InvalidClientMetadata(format!(
+                    "unsupported token_endpoint_auth_method: {}",
+                    auth_method
+                )))
+            }
+        };
+
+        let registration = ClientRegistration {
+            client_id: make_random_string(24),
+            client_secret,
+            client_name: metadata
+                .get("client_name")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            scopes,
+            issued_at: now(),
+        };
+
+        let mut response = metadata.clone();
+        response.insert("client_id".to_string(), json!(registration.client_id));
+        response.insert(
+            "client_id_issued_at".to_string(),
+            json!(registration.issued_at),
+        );
+        response.insert("scope".to_string(), json!(registration.scopes.join(" ")));
+        response.insert("grant_types".to_string(), json!(["client_credentials"]));
+        response.insert("token_endpoint_auth_method".to_string(), json!(auth_method));
+        if let Some(client_secret) = &registration.client_secret {
+            response.insert("client_secret".to_string(), json!(client_secret));
+            response.insert("client_secret_expires_at".to_string(), json!(0));
+        }
+
+        self.clients
+            .lock()
+            .unwrap()
+            .insert(registration.client_id.clone(), registration);
+
+        Ok(Value::Object(response))
+    }
+
+    // Issue an access token to a registered client, returning the access token response
+    // See https://www.rfc-editor.org/rfc/rfc6749#section-4.4
+    pub fn issue_token(
+        &self,
+        client_id: &str,
+        client_secret: Option<&str>,
+        scope: Option<&str>,
+    ) -> Result<Value, AuthorizationServerError> {
+        let registration = self
+            .clients
+            .lock()
+            .unwrap()
+            .get(client_id)
+            .cloned()
+            .ok_or(AuthorizationServerError::InvalidClient)?;
+
+        if let Some(expected) = &registration.client_secret {
+            if client_secret != Some(expected.as_str()) {
+                return Err(AuthorizationServerError::InvalidClient);
+            }
+        }
+
+        let scopes = match scope {
+            Some(scope) => {
+                let requested = parse_scopes(scope)?;
+                if let Some(unregistered) =
+                    requested.iter().find(|s| !registration.scopes.contains(s))
+                {
+                    return Err(AuthorizationServerError::InvalidScope(unregistered.clone()));
+                }
+                requested
+            }
+            None => registration.scopes.clone(),
+        };
+
+        let access_token = self.make_access_token(client_id, &scopes)?;
+
+        Ok(json!({
+            "access_token": access_token,
+            "token_type": "Bearer",
+            "expires_in": self.settings.token_lifetime,
+            "scope": scopes.join(" "),
+        }))
+    }
+
+    // Construct a signed access token for the specified client and scopes, without checking any
+    // client registration
+    pub fn make_access_token(
+        &self,
+        client_id: &str,
+        scopes: &[String],
+    ) -> Result<String, AuthorizationServerError> {
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some(self.settings.key_id.clone());
+
+        jsonwebtoken::encode(
+            &header,
+            &self.make_claims(client_id, scopes),
+            &self.encoding_key,
+        )
+        .map_err(|e| AuthorizationServerError::Key(e.to_string()))
+    }
+
+    // Construct the access token claims, including an "x-nmos-*" claim for each scope
+    // See https://specs.amwa.tv/is-10/releases/v1.0.0/docs/4.4._Behaviour_-_Access_Tokens.html#claims
+    fn make_claims(&self, client_id: &str, scopes: &[String]) -> Value {
+        let issued_at = now();
+        let mut claims = json!({
+            "iss": self.settings.issuer.trim_end_matches('/'),
+            "sub": client_id,
+            "aud": self.settings.audience,
+            "iat": issued_at,
+            "nbf": issued_at,
+            "exp": issued_at + self.settings.token_lifetime,
+            "client_id": client_id,
+            "scope": scopes.join(" "),
+        });
+
+        let claims_object = claims.as_object_mut().unwrap();
+        for scope in scopes {
+            claims_object.insert(
+                format!("x-nmos-{}", scope),
+                json!({ "read": ["*"], "write": ["*"] }),
+            );
+        }
+        for (name, value) in &self.settings.additional_claims {
+            claims_object.insert(name.clone(), value.clone());
+        }
+
+        claims
+    }
+}
+
+// Split a space-delimited scope parameter, rejecting unknown scopes
+fn parse_scopes(scope: &str) -> Result<Vec<String>, AuthorizationServerError> {
+    let mut scopes = Vec::new();
+    let mut seen = HashSet::new();
+    for s in scope.split_whitespace() {
+        if !SCOPES.contains(&s) {
+            return Err(AuthorizationServerError::InvalidScope(s.to_string()));
+        }
+        if seen.insert(s) {
+            scopes.push(s.to_string());
+        }
+    }
+    Ok(scopes)
+}
+
+// Extract the client credentials from an HTTP Basic Authorization header
+// See https://www.rfc-editor.org/rfc/rfc6749#section-2.3.1
+fn parse_basic_credentials(authorization: &str) -> Option<(String, String)> {
+    let encoded = authorization.strip_prefix("Basic ")?;
+    let decoded = String::from_utf8(STANDARD.decode(encoded.trim()).ok()?).ok()?;
+    let (client_id, client_secret) = decoded.split_once(':')?;
+    Some((client_id.to_string(), client_secret.to_string()))
+}
+
+fn make_random_string(len: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn make_error_reply(error: &AuthorizationServerError) -> warp::reply::Response {
+    warp::reply::with_status(
+        warp::reply::json(&json!({
+            "error": error.error_code(),
+            "error_description": error.to_string(),
+        })),
+        error.status_code(),
+    )
+    .into_response()
+}
+
+// Make the routes for the Authorization Server Metadata, JSON Web Key Set, token and client
+// registration endpoints
+pub fn make_authorization_server_api(
+    server: Arc<AuthorizationServer>,
+    settings: &Settings,
+    metrics: Option<Arc<Metrics>>,
+    gate: Logger,
+) -> impl Filter<Extract = (warp::reply::Response,), Error = Rejection> + Clone {
+    let with_server = {
+        let server = server.clone();
+        warp::any().map(move || server.clone())
+    };
+
+    let metadata = warp::path!(".well-known" / "oauth-authorization-server")
+        .and(warp::get())
+        .and(with_server.clone())
+        .map(|server: Arc<AuthorizationServer>| {
+            warp::reply::json(&server.metadata()).into_response()
+        });
+
+    let jwks = warp::path!("jwks")
+        .and(warp::get())
+        .and(with_server.clone())
+        .map(|server: Arc<AuthorizationServer>| warp::reply::json(&server.jwks()).into_response());
+
+    let token_gate = gate.clone();
+    let token = warp::path!("token")
+        .and(warp::post())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(warp::body::form::<HashMap<String, String>>())
+        .and(with_server.clone())
+        .map(
+            move |authorization: Option<String>,
+                  form: HashMap<String, String>,
+                  server: Arc<AuthorizationServer>| {
+                let grant_type = form
+                    .get("grant_type")
+                    .map(String::as_str)
+                    .unwrap_or_default();
+                if grant_type != "client_credentials" {
+                    return make_error_reply(&AuthorizationServerError::UnsupportedGrantType(
+                        grant_type.to_string(),
+                    ));
+                }
+
+                // client_secret_basic takes precedence over client_secret_post
+                let (client_id, client_secret) =
+                    match authorization.as_deref().and_then(parse_basic_credentials) {
+                        Some((client_id, client_secret)) => (Some(client_id), Some(client_secret)),
+                        None => (
+                            form.get("client_id").cloned(),
+                            form.get("client_secret").cloned(),
+                        ),
+                    };
+                let Some(client_id) = client_id else {
+                    return make_error_reply(&AuthorizationServerError::InvalidClient);
+                };
+
+                match server.issue_token(
+                    &client_id,
+                    client_secret.as_deref(),
+                    form.get("scope").map(String::as_str),
+                ) {
+                    Ok(token) => {
+                        info!(token_gate, "Issued access token to client: {}", client_id);
+                        warp::reply::with_header(
+                            warp::reply::json(&token),
+                            "Cache-Control",
+                            "no-store",
+                        )
+                        .into_response()
+                    }
+                    Err(error) => {
+                        warn!(
+                            token_gate,
+                            "Token request from client: {} rejected: {}", client_id, error
+                        );
+                        make_error_reply(&error)
+                    }
+                }
+            },
+        );
+
+    let register_gate = gate.clone();
+    let register = warp::path!("register")
+        .and(warp::post())
+        .and(warp::body::json::<Value>())
+        .and(with_server)
+        .map(move |metadata: Value, server: Arc<AuthorizationServer>| {
+            match server.register_client(&metadata) {
+                Ok(registration) => {
+                    info!(
+                        register_gate,
+                        "Registered client: {}", registration["client_id"]
+                    );
+                    warp::reply::with_status(warp::reply::json(&registration), StatusCode::CREATED)
+                        .into_response()
+                }
+                Err(error) => {
+                    warn!(register_gate, "Client registration rejected: {}", error);
+                    make_error_reply(&error)
+                }
+            }
+        });
+
+    let api = metadata
+        .or(jwks)
+        .unify()
+        .or(token)
+        .unify()
+        .or(register)
+        .unify();
+    let api = with_header_policy(api, make_header_policy(settings, "auth"));
+    with_server_timing(with_request_metrics(api, "auth", metrics), "auth", gate)
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{DecodingKey, Validation};
+
+    lazy_static::lazy_static! {
+        // RSA key generation is slow, so share one server between tests
+        static ref SERVER: AuthorizationServer =
+            AuthorizationServer::new(AuthorizationServerSettings::default()).unwrap();
+    }
+
+    fn decode_claims(token: &str) -> Value {
+        let jwk = &SERVER.jwks()["keys"][0];
+        let key = DecodingKey::from_rsa_components(
+            jwk["n"].as_str().unwrap(),
+            jwk["e"].as_str().unwrap(),
+        )
+        .unwrap();
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.set_audience(&["*"]);
+        jsonwebtoken::decode::<Value>(token, &key, &validation)
+            .unwrap()
+            .claims
+    }
+
+    #[test]
+    fn test_metadata() {
+        let metadata = SERVER.metadata();
+        assert_eq!(metadata["issuer"], "http://localhost:8089");
+        assert_eq!(metadata["token_endpoint"], "http://localhost:8089/token");
+        assert_eq!(metadata["jwks_uri"], "http://localhost:8089/jwks");
+    }
+
+    #[test]
+    fn test_issue_token() {
+        let registration = SERVER
+            .register_client(&json!({ "client_name": "test", "scope": "registration query" }))
+            .unwrap();
+        let client_id = registration["client_id"].as_str().unwrap();
+        let client_secret = registration["client_secret"].as_str().unwrap();
+
+        let token = SERVER
+            .issue_token(client_id, Some(client_secret), Some("registration"))
+            .unwrap();
+        assert_eq!(token["token_type"], "Bearer");
+        assert_eq!(token["scope"], "registration");
+
+        let claims = decode_claims(token["access_token"].as_str().unwrap());
+        assert_eq!(claims["client_id"], client_id);
+        assert_eq!(claims["x-nmos-registration"]["write"], json!(["*"]));
+        assert!(claims.get("x-nmos-query").is_none());
+    }
+
+    #[test]
+    fn test_issue_token_rejected() {
+        let registration = SERVER.register_client(&json!({ "scope": "node" })).unwrap();
+        let client_id = registration["client_id"].as_str().unwrap();
+        let client_secret = registration["client_secret"].as_str().unwrap();
+
+        assert!(matches!(
+            SERVER.issue_token(client_id, Some("wrong"), None),
+            Err(AuthorizationServerError::InvalidClient)
+        ));
+        assert!(matches!(
+            SERVER.issue_token(client_id, Some(client_secret), Some("connection")),
+            Err(AuthorizationServerError::InvalidScope(_))
+        ));
+        assert!(matches!(
+            SERVER.issue_token("unknown", None, None),
+            Err(AuthorizationServerError::InvalidClient)
+        ));
+    }
+
+    #[test]
+    fn test_parse_basic_credentials() {
+        let authorization = format!("Basic {}", STANDARD.encode("id:secret"));
+        assert_eq!(
+            parse_basic_credentials(&authorization),
+            Some(("id".to_string(), "secret".to_string()))
+        );
+        assert_eq!(parse_basic_credentials("Bearer token"), None);
+    }
+}
@@ -0,0 +1,46 @@
+// A minimal IS-10 authorization server for integration testing of secured NMOS APIs
+//
+// Usage: authorization_server [port]
+//
+// Its Prometheus metrics are served at /metrics on the same port
+
+use std::env;
+use std::sync::Arc;
+
+use nmos::authorization_server::{
+    make_authorization_server_api, AuthorizationServer, AuthorizationServerSettings,
+};
+use nmos::metrics::{make_metrics_api, Metrics};
+use nmos::settings::Settings;
+use slog::{info, o, Drain};
+use warp::Filter;
+
+#[tokio::main]
+async fn main() {
+    let decorator = slog_term::TermDecorator::new().build();
+    let drain = slog_term::FullFormat::new(decorator).build().fuse();
+    let drain = slog_async::Async::new(drain).build().fuse();
+    let gate = slog::Logger::root(drain, o!());
+
+    let port = env::args()
+        .nth(1)
+        .and_then(|port| port.parse().ok())
+        .unwrap_or(8089u16);
+
+    let settings = AuthorizationServerSettings {
+        issuer: format!("http://localhost:{}", port),
+        ..Default::default()
+    };
+    let server = Arc::new(
+        AuthorizationServer::new(settings).expect("Unable to create authorization server"),
+    );
+
+    info!(gate, "Starting authorization server on port {}", port);
+    // The metrics of the authorization server are served alongside it, at /metrics
+    let metrics = Arc::new(Metrics::new().expect("Unable to create metrics"));
+    let api =
+        make_authorization_server_api(server, &Settings::default(), Some(metrics.clone()), gate)
+            .or(make_metrics_api(metrics))
+            .unify();
+    warp::serve(api).run(([0, 0, 0, 0], port)).await;
+}
@@ -0,0 +1,230 @@
+// Certificates and private keys, as loaded from PEM files by the certificate handlers
+
+use std::fmt;
+
+use rustls::crypto::ring::sign::any_supported_type;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::sign::CertifiedKey;
+use rustls::RootCertStore;
+use rustls_pemfile::Item;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+// Define the errors from loading certificates, with messages that say what to fix
+#[derive(Debug)]
+pub enum CertificateError {
+    // A setting that names a required file is missing
+    NoFileSpecified(String),
+    // A file could not be read, e.g. because it doesn't exist
+    MissingFile(String, std::io::Error),
+    // A file, or some other PEM data, couldn't be parsed
+    InvalidPem(String),
+    // The private key doesn't belong to the certificate, identified by its subject
+    KeyMismatch(String),
+    Expired { subject: String, not_after: String },
+    // The certificate, identified by its subject, has been revoked by its issuer
+    Revoked(String),
+    // The revocation status couldn't be determined, e.g. because the OCSP responder failed
+    RevocationCheck(String),
+}
+
+impl fmt::Display for CertificateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CertificateError::NoFileSpecified(what) => {
+                write!(f, "no {} file specified in the settings", what)
+            }
+            CertificateError::MissingFile(path, error) => {
+                write!(f, "failed to read {}: {}", path, error)
+            }
+            CertificateError::InvalidPem(message) => write!(f, "invalid PEM: {}", message),
+            CertificateError::KeyMismatch(subject) => write!(
+                f,
+                "the private key does not match the certificate for {}, check that the private key and certificate chain files belong together",
+                subject
+            ),
+            CertificateError::Expired { subject, not_after } => write!(
+                f,
+                "the certificate for {} expired at {}, and must be renewed",
+                subject, not_after
+            ),
+            CertificateError::Revoked(subject) => {
+                write!(f, "the certificate for {} has been revoked", subject)
+            }
+            CertificateError::RevocationCheck(message) => {
+                write!(f, "failed to check certificate revocation: {}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CertificateError {}
+
+// A certificate chain, leaf first, with its private key if this is a server certificate
+#[derive(Debug, Clone)]
+pub struct Certificate {
+    pub private_key: String,
+    pub certificate_chain: String,
+}
+
+// Load the certificates, e.g. each time the API listeners reload them
+pub type CertificateLoader =
+    Box<dyn Fn() -> Result<Vec<Certificate>, CertificateError> + Send + Sync>;
+
+impl Certificate {
+    // A server certificate, which is only accepted if the private key belongs to the leaf
+    // certificate and none of the certificates in the chain has expired
+    pub fn new(private_key: &str, certificate_chain: &str) -> Result<Self, CertificateError> {
+        let certificate = Certificate {
+            private_key: private_key.to_string(),
+            certificate_chain: certificate_chain.to_string(),
+        };
+        let chain = certificate.certificate_chain_der()?;
+        check_validity(&chain)?;
+        let key = any_supported_type(&certificate.private_key_der()?)
+            .map_err(|e| CertificateError::InvalidPem(format!("unsupported private key: {}", e)))?;
+        if CertifiedKey::new(chain.clone(), key).keys_match().is_err() {
+            return Err(CertificateError::KeyMismatch(subject(&chain[0])));
+        }
+        Ok(certificate)
+    }
+
+    // A certificate without a private key, e.g. a CA certificate, which isn't checked for expiry,
+    // so that one expired certificate in a CA bundle can be skipped rather than rejecting them all
+    pub fn from_pem(certificate_chain: &str) -> Result<Self, CertificateError> {
+        let certificate = Certificate {
+            private_key: String::new(),
+            certificate_chain: certificate_chain.to_string(),
+        };
+        certificate.certificate_chain_der()?;
+        Ok(certificate)
+    }
+
+    // Each of the certificates in a PEM bundle, e.g. of CA certificates, so that they can be
+    // checked separately
+    pub fn bundle_from_pem(bundle: &str) -> Result<Vec<Self>, CertificateError> {
+        let mut certificates = Vec::new();
+        let mut rest = bundle.as_bytes();
+        loop {
+            let start = rest;
+            let item = rustls_pemfile::read_one(&mut rest)
+                .map_err(|e| CertificateError::InvalidPem(e.to_string()))?;
+            match item {
+                Some(Item::X509Certificate(_)) => {
+                    let pem = String::from_utf8_lossy(&start[..start.len() - rest.len()]);
+                    certificates.push(Certificate::from_pem(&pem)?);
+                }
+                Some(_) => continue,
+                None => break,
+            }
+        }
+        if certificates.is_empty() {
+            return Err(CertificateError::InvalidPem(
+                "no certificates found".to_string(),
+            ));
+        }
+        Ok(certificates)
+    }
+
+    // Check that none of the certificates in the chain has expired
+    pub fn check_expiry(&self) -> Result<(), CertificateError> {
+        check_validity(&self.certificate_chain_der()?)
+    }
+
+    pub fn certificate_chain_der(&self) -> Result<Vec<CertificateDer<'static>>, CertificateError> {
+        let chain = rustls_pemfile::certs(&mut self.certificate_chain.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| CertificateError::InvalidPem(e.to_string()))?;
+        if chain.is_empty() {
+            return Err(CertificateError::InvalidPem(
+                "no certificates found".to_string(),
+            ));
+        }
+        Ok(chain)
+    }
+
+    pub fn private_key_der(&self) -> Result<PrivateKeyDer<'static>, CertificateError> {
+        rustls_pemfile::private_key(&mut self.private_key.as_bytes())
+            .map_err(|e| CertificateError::InvalidPem(e.to_string()))?
+            .ok_or_else(|| CertificateError::InvalidPem("no private key found".to_string()))
+    }
+}
+
+// Make the trust anchors for verifying peers from the CA certificates
+pub fn make_root_store(ca_certificates: &[Certificate]) -> Result<RootCertStore, CertificateError> {
+    let mut roots = RootCertStore::empty();
+    for certificate in ca_certificates {
+        for der in certificate.certificate_chain_der()? {
+            roots
+                .add(der)
+                .map_err(|e| CertificateError::InvalidPem(e.to_string()))?;
+        }
+    }
+    Ok(roots)
+}
+
+// The subject of a certificate, for error and log messages
+pub fn subject(certificate: &[u8]) -> String {
+    match X509Certificate::from_der(certificate) {
+        Ok((_, certificate)) => certificate.subject().to_string(),
+        Err(_) => "an unparseable certificate".to_string(),
+    }
+}
+
+// Check that none of the certificates has expired
+fn check_validity(chain: &[CertificateDer]) -> Result<(), CertificateError> {
+    for certificate in chain {
+        let (_, parsed) = X509Certificate::from_der(certificate)
+            .map_err(|e| CertificateError::InvalidPem(e.to_string()))?;
+        if parsed.validity().time_to_expiration().is_none() {
+            return Err(CertificateError::Expired {
+                subject: parsed.subject().to_string(),
+                not_after: parsed.validity().not_after.to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_certificate() {
+        let names = vec!["node.example.com".to_string()];
+        let first = rcgen::generate_simple_self_signed(names.clone()).unwrap();
+        let second = rcgen::generate_simple_self_signed(names.clone()).unwrap();
+        let key = first.key_pair.serialize_pem();
+
+        assert!(Certificate::new(&key, &first.cert.pem()).is_ok());
+        assert!(matches!(
+            Certificate::new(&key, &second.cert.pem()),
+            Err(CertificateError::KeyMismatch(subject)) if subject.contains("rcgen")
+        ));
+        assert!(matches!(
+            Certificate::new("", &first.cert.pem()),
+            Err(CertificateError::InvalidPem(_))
+        ));
+
+        let mut params = rcgen::CertificateParams::new(names).unwrap();
+        params.not_before = rcgen::date_time_ymd(2000, 1, 1);
+        params.not_after = rcgen::date_time_ymd(2001, 1, 1);
+        let key_pair = rcgen::KeyPair::generate().unwrap();
+        let expired = params.self_signed(&key_pair).unwrap();
+        assert!(matches!(
+            Certificate::new(&key_pair.serialize_pem(), &expired.pem()),
+            Err(CertificateError::Expired { not_after, .. }) if not_after.contains("2001")
+        ));
+
+        // An expired certificate in a bundle can be loaded, and then skipped
+        let bundle = format!("{}{}", first.cert.pem(), expired.pem());
+        let certificates = Certificate::bundle_from_pem(&bundle).unwrap();
+        assert_eq!(certificates.len(), 2);
+        assert!(certificates[0].check_expiry().is_ok());
+        assert!(matches!(
+            certificates[1].check_expiry(),
+            Err(CertificateError::Expired { not_after, .. }) if not_after.contains("2001")
+        ));
+    }
+}
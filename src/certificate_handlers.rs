@@ -1,97 +1,281 @@
-use std::fs::File;
-use std::io::prelude::*;
-use std::path::PathBuf;
+use std::fs;
 use std::sync::Arc;
 
-use serde_json::Value;
-use slog::Logger;
+use slog::{debug, info, warn, Logger};
 
 use crate::certificate::{Certificate, CertificateError, CertificateLoader};
 use crate::settings::Settings;
 
+// Read a PEM file, reporting the path if that fails
+fn read_file(path: &str) -> Result<String, CertificateError> {
+    fs::read_to_string(path).map_err(|e| CertificateError::MissingFile(path.to_owned(), e))
+}
+
 pub fn make_ca_certificate_loader(settings: Arc<Settings>, logger: Logger) -> CertificateLoader {
     let ca_certificate_file = settings.ca_certificate_file.clone();
 
     Box::new(move || {
         debug!(logger, "Loading CA certificate");
         if let Some(path) = ca_certificate_file.as_ref() {
-            let mut file = File::open(path)?;
-            let mut contents = String::new();
-            file.read_to_string(&mut contents)?;
-            Ok(vec![Certificate::from_pem(&contents)?])
+            // An expired root is skipped, rather than making the whole trust store unusable
+            let mut expired = None;
+            let mut certificates = Certificate::bundle_from_pem(&read_file(path)?)?;
+            certificates.retain(|certificate| match certificate.check_expiry() {
+                Ok(()) => true,
+                Err(e) => {
+                    warn!(logger, "Skipping CA certificate in {}: {}", path, e);
+                    expired = Some(e);
+                    false
+                }
+            });
+            match expired {
+                Some(e) if certificates.is_empty() => Err(e),
+                _ => Ok(certificates),
+            }
         } else {
-            Err(CertificateError::NoFileSpecified("CA certificate".to_owned()))
+            Err(CertificateError::NoFileSpecified(
+                "CA certificate".to_owned(),
+            ))
         }
     })
 }
 
-pub fn make_server_certificate_loader(settings: Arc<Settings>, logger: Logger) -> CertificateLoader {
-    let server_certificates = settings.server_certificates.clone();
-    let private_key_files = settings.private_key_files.clone();
-    let certificate_chain_files = settings.certificate_chain_files.clone();
+// The private key and certificate chain files of each server certificate
+fn server_certificate_files(settings: &Settings) -> Vec<(Option<String>, Option<String>)> {
+    // Each server certificate must specify its own files, or else the files of the earlier
+    // settings are paired by index, rather than using the same private key for every certificate
+    if !settings.server_certificates.is_empty() {
+        settings
+            .server_certificates
+            .iter()
+            .map(|cert| {
+                (
+                    cert.private_key_file.clone(),
+                    cert.certificate_chain_file.clone(),
+                )
+            })
+            .collect()
+    } else {
+        let count = settings
+            .private_key_files
+            .len()
+            .max(settings.certificate_chain_files.len());
+        (0..count)
+            .map(|index| {
+                (
+                    settings.private_key_files.get(index).cloned(),
+                    settings.certificate_chain_files.get(index).cloned(),
+                )
+            })
+            .collect()
+    }
+}
+
+pub fn make_server_certificate_loader(
+    settings: Arc<Settings>,
+    logger: Logger,
+) -> CertificateLoader {
+    let files = server_certificate_files(&settings);
 
     Box::new(move || {
         debug!(logger, "Loading server certificates");
 
-        if !server_certificates.is_empty() {
-            let mut certs = Vec::new();
-            for cert in server_certificates.iter() {
-                let key_path = cert
-                    .get("private_key_file")
-                    .and_then(|v| v.as_str())
-                    .map(PathBuf::from)
-                    .or_else(|| {
-                        private_key_files
-                            .get(0)
-                            .map(|v| PathBuf::from(v.clone()))
-                    })
-                    .ok_or_else(|| CertificateError::NoFileSpecified("private key".to_owned()))?;
-
-                let chain_path = cert
-                    .get("certificate_chain_file")
-                    .and_then(|v| v.as_str())
-                    .map(PathBuf::from)
-                    .or_else(|| {
-                        certificate_chain_files
-                            .get(0)
-                            .map(|v| PathBuf::from(v.clone()))
-                    })
-                    .ok_or_else(|| CertificateError::NoFileSpecified("certificate chain".to_owned()))?;
+        if files.is_empty() {
+            return Err(CertificateError::NoFileSpecified(
+                "server certificates".to_owned(),
+            ));
+        }
 
-                let mut key_file = File::open(&key_path)?;
-                let mut key_contents = String::new();
-                key_file.read_to_string(&mut key_contents)?;
+        let mut certs = Vec::new();
+        for (index, (key_path, chain_path)) in files.iter().enumerate() {
+            let key_path = key_path.as_ref().ok_or_else(|| {
+                CertificateError::NoFileSpecified(format!(
+                    "server certificate {} private key",
+                    index
+                ))
+            })?;
+            let chain_path = chain_path.as_ref().ok_or_else(|| {
+                CertificateError::NoFileSpecified(format!(
+                    "server certificate {} certificate chain",
+                    index
+                ))
+            })?;
 
-                let mut chain_file = File::open(&chain_path)?;
-                let mut chain_contents = String::new();
-                chain_file.read_to_string(&mut chain_contents)?;
+            let key_contents = read_file(key_path)?;
+            let chain_contents = read_file(chain_path)?;
 
-                certs.push(Certificate::new(
-                    key_contents.trim(),
-                    chain_contents.trim(),
-                )?);
-            }
-            Ok(certs)
-        } else {
-            Err(CertificateError::NoFileSpecified("server certificates".to_owned()))
+            // Validate that the key and chain belong together, and report which files don't
+            let cert =
+                Certificate::new(key_contents.trim(), chain_contents.trim()).map_err(|error| {
+                    match error {
+                        CertificateError::InvalidPem(message) => CertificateError::InvalidPem(
+                            format!("{} or {}: {}", key_path, chain_path, message),
+                        ),
+                        CertificateError::KeyMismatch(subject) => CertificateError::KeyMismatch(
+                            format!("{} ({} and {})", subject, key_path, chain_path),
+                        ),
+                        error => error,
+                    }
+                })?;
+            certs.push(cert);
         }
+        Ok(certs)
     })
 }
 
-fn make_load_dh_param_handler(settings: &nmos::settings, gate: &mut slog::Logger) -> impl FnMut() -> utility::string_t {
-    let dh_param_file = nmos::experimental::fields::dh_param_file(settings);
+// Make the loaders for the certificate health check, by role, which read the server certificate
+// chains and the CA certificates without rejecting those that have expired, so that the check can
+// report them
+pub fn make_certificate_health_loaders(
+    settings: Arc<Settings>,
+    logger: Logger,
+) -> Vec<(String, CertificateLoader)> {
+    let mut loaders: Vec<(String, CertificateLoader)> = Vec::new();
+
+    let chain_files = server_certificate_files(&settings)
+        .into_iter()
+        .map(|(_, chain_path)| chain_path)
+        .collect::<Vec<_>>();
+    if !chain_files.is_empty() {
+        let logger = logger.clone();
+        loaders.push((
+            "server".to_owned(),
+            Box::new(move || {
+                debug!(logger, "Loading server certificates for the health check");
+                chain_files
+                    .iter()
+                    .enumerate()
+                    .map(|(index, chain_path)| {
+                        let chain_path = chain_path.as_ref().ok_or_else(|| {
+                            CertificateError::NoFileSpecified(format!(
+                                "server certificate {} certificate chain",
+                                index
+                            ))
+                        })?;
+                        Certificate::from_pem(read_file(chain_path)?.trim())
+                    })
+                    .collect()
+            }),
+        ));
+    }
+
+    if let Some(path) = settings.ca_certificate_file.clone() {
+        loaders.push((
+            "ca".to_owned(),
+            Box::new(move || {
+                debug!(logger, "Loading CA certificate for the health check");
+                Certificate::bundle_from_pem(&read_file(&path)?)
+            }),
+        ));
+    }
+
+    loaders
+}
+
+// Load the DH parameters, which are only used by listeners offering the finite field DHE cipher
+// suites, so an unspecified file is not an error
+pub fn make_load_dh_param_handler(
+    settings: Arc<Settings>,
+    logger: Logger,
+) -> impl Fn() -> Result<Option<String>, CertificateError> {
+    let dh_param_file = settings.dh_param_file.clone();
 
     move || {
-        slog::info!(gate, "Load DH parameters");
+        info!(logger, "Load DH parameters");
 
-        if dh_param_file.is_empty() {
-            slog::warning!(gate, "Missing DH parameters file");
-        } else {
-            let mut dh_file = File::open(PathBuf::from(dh_param_file)).expect("Failed to open DH parameters file");
-            let mut dh_param = String::new();
-            dh_file.read_to_string(&mut dh_param).expect("Failed to read DH parameters file");
-            return dh_param;
+        match dh_param_file.as_ref() {
+            None => {
+                warn!(logger, "Missing DH parameters file");
+                Ok(None)
+            }
+            Some(path) => {
+                let dh_param = read_file(path)?;
+                if !dh_param.contains("-----BEGIN DH PARAMETERS-----") {
+                    return Err(CertificateError::InvalidPem(format!(
+                        "{}: no DH parameters found",
+                        path
+                    )));
+                }
+                Ok(Some(dh_param))
+            }
         }
-        return utility::string_t::new();
     }
-}
\ No newline at end of file
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::ServerCertificateSettings;
+
+    #[test]
+    fn test_server_certificate_loader() {
+        let logger = Logger::root(slog::Discard, slog::o!());
+        let dir = std::env::temp_dir().join(format!("nmos-certificates-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str, contents: &str| {
+            let path = dir.join(name);
+            fs::write(&path, contents).unwrap();
+            path.to_string_lossy().into_owned()
+        };
+
+        let names = vec!["node.example.com".to_string()];
+        let first = rcgen::generate_simple_self_signed(names.clone()).unwrap();
+        let second = rcgen::generate_simple_self_signed(names).unwrap();
+        let first_key = write("first.key", &first.key_pair.serialize_pem());
+        let first_chain = write("first.pem", &first.cert.pem());
+        let second_key = write("second.key", &second.key_pair.serialize_pem());
+        let second_chain = write("second.pem", &second.cert.pem());
+
+        // Paired by index rather than every certificate using the first private key
+        let settings = Settings {
+            private_key_files: vec![first_key.clone(), second_key.clone()],
+            certificate_chain_files: vec![first_chain.clone(), second_chain.clone()],
+            ..Default::default()
+        };
+        let loader = make_server_certificate_loader(Arc::new(settings), logger.clone());
+        assert_eq!(loader().unwrap().len(), 2);
+
+        let settings = Settings {
+            server_certificates: vec![
+                ServerCertificateSettings {
+                    private_key_file: Some(first_key.clone()),
+                    certificate_chain_file: Some(first_chain.clone()),
+                    ..Default::default()
+                },
+                ServerCertificateSettings {
+                    certificate_chain_file: Some(second_chain.clone()),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let loader = make_server_certificate_loader(Arc::new(settings), logger.clone());
+        assert!(matches!(
+            loader(),
+            Err(CertificateError::NoFileSpecified(_))
+        ));
+
+        let settings = Settings {
+            private_key_files: vec![first_key],
+            certificate_chain_files: vec![second_chain],
+            ..Default::default()
+        };
+        let loader = make_server_certificate_loader(Arc::new(settings), logger.clone());
+        assert!(matches!(loader(), Err(CertificateError::KeyMismatch(_))));
+
+        let settings = Settings {
+            dh_param_file: Some(dir.join("missing.pem").to_string_lossy().into_owned()),
+            ..Default::default()
+        };
+        let load_dh_param = make_load_dh_param_handler(Arc::new(settings), logger.clone());
+        assert!(matches!(
+            load_dh_param(),
+            Err(CertificateError::MissingFile(..))
+        ));
+        let load_dh_param = make_load_dh_param_handler(Arc::new(Settings::default()), logger);
+        assert!(matches!(load_dh_param(), Ok(None)));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,285 @@
+// Certificate health, which reports how long each loaded certificate remains valid, so that
+// certificates are renewed before BCP-003-01 secure communication fails
+// See https://specs.amwa.tv/bcp-003-01/releases/v1.0.0/docs/1.0._Secure_Communication.html#certificate-expiry
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use slog::{error, info, warn, Logger};
+use warp::{Filter, Rejection, Reply};
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+use crate::certificate::{Certificate, CertificateLoader};
+use crate::certificate_handlers::make_certificate_health_loaders;
+use crate::settings::Settings;
+
+// The default number of days before expiry from which a certificate is reported as expiring
+pub const DEFAULT_EXPIRY_WARNING_DAYS: i64 = 14;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpiryStatus {
+    Ok,
+    Expiring,
+    Expired,
+}
+
+// Define the health of one certificate in a chain
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CertificateStatus {
+    // e.g. "server" or "ca", as specified when the certificates were loaded
+    pub role: String,
+    pub subject: String,
+    pub issuer: String,
+    pub serial_number: String,
+    pub not_after: String,
+    // Negative once the certificate has expired
+    pub days_until_expiry: i64,
+    pub status: ExpiryStatus,
+}
+
+// Define the health of all the loaded certificates, including any that failed to load
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CertificateHealth {
+    pub certificates: Vec<CertificateStatus>,
+    pub errors: Vec<String>,
+}
+
+impl CertificateHealth {
+    // The worst status of any certificate, where a load failure counts as expired since the
+    // certificate can't be used
+    pub fn status(&self) -> ExpiryStatus {
+        if !self.errors.is_empty() {
+            return ExpiryStatus::Expired;
+        }
+        self.certificates
+            .iter()
+            .map(|certificate| certificate.status)
+            .max()
+            .unwrap_or(ExpiryStatus::Ok)
+    }
+}
+
+// Determine the health of every certificate in the chains
+pub fn certificate_statuses(
+    role: &str,
+    certificates: &[Certificate],
+    warning_days: i64,
+) -> Vec<CertificateStatus> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs() as i64)
+        .unwrap_or_default();
+    certificates
+        .iter()
+        .filter_map(|certificate| certificate.certificate_chain_der().ok())
+        .flatten()
+        .filter_map(|der| {
+            let (_, certificate) = X509Certificate::from_der(&der).ok()?;
+            let not_after = certificate.validity().not_after;
+            let days_until_expiry = (not_after.timestamp() - now).div_euclid(24 * 60 * 60);
+            let status = if not_after.timestamp() <= now {
+                ExpiryStatus::Expired
+            } else if days_until_expiry < warning_days {
+                ExpiryStatus::Expiring
+            } else {
+                ExpiryStatus::Ok
+            };
+            Some(CertificateStatus {
+                role: role.to_string(),
+                subject: certificate.subject().to_string(),
+                issuer: certificate.issuer().to_string(),
+                serial_number: certificate.raw_serial_as_string(),
+                not_after: not_after.to_string(),
+                days_until_expiry,
+                status,
+            })
+        })
+        .collect()
+}
+
+// Load the certificates of each role and determine their health, logging those that are near
+// expiry or that failed to load
+pub fn check_certificate_health(
+    loaders: &[(String, CertificateLoader)],
+    warning_days: i64,
+    gate: &Logger,
+) -> CertificateHealth {
+    let mut health = CertificateHealth::default();
+    for (role, loader) in loaders {
+        let certificates = match loader() {
+            Ok(certificates) => certificates,
+            Err(e) => {
+                error!(gate, "Failed to load {} certificates: {}", role, e);
+                health.errors.push(format!("{}: {}", role, e));
+                continue;
+            }
+        };
+        for certificate in certificate_statuses(role, &certificates, warning_days) {
+            match certificate.status {
+                ExpiryStatus::Ok => info!(
+                    gate,
+                    "The {} certificate for {} expires in {} days",
+                    role,
+                    certificate.subject,
+                    certificate.days_until_expiry
+                ),
+                ExpiryStatus::Expiring => warn!(
+                    gate,
+                    "The {} certificate for {} expires in {} days, at {}",
+                    role,
+                    certificate.subject,
+                    certificate.days_until_expiry,
+                    certificate.not_after
+                ),
+                ExpiryStatus::Expired => error!(
+                    gate,
+                    "The {} certificate for {} expired at {}",
+                    role,
+                    certificate.subject,
+                    certificate.not_after
+                ),
+            }
+            health.certificates.push(certificate);
+        }
+    }
+    health
+}
+
+// Check the certificate health periodically, e.g. daily, so that warnings are logged well before
+// expiry even if the certificates are never reloaded, or only once if no interval is specified
+pub async fn run_certificate_health_monitor(
+    health: Arc<Mutex<CertificateHealth>>,
+    loaders: Vec<(String, CertificateLoader)>,
+    interval: Option<Duration>,
+    warning_days: i64,
+    gate: Logger,
+) {
+    loop {
+        let update = check_certificate_health(&loaders, warning_days, &gate);
+        *health.lock().unwrap() = update;
+        match interval {
+            Some(interval) => tokio::time::sleep(interval).await,
+            None => break,
+        }
+    }
+}
+
+// Check the health of the certificate files in the settings, at the configured interval and with
+// the configured warning period
+pub async fn run_certificate_health_monitor_from_settings(
+    health: Arc<Mutex<CertificateHealth>>,
+    settings: Arc<Settings>,
+    gate: Logger,
+) {
+    let interval = settings.certificate_health_period();
+    let warning_days = settings.certificate_expiry_warning_days;
+    let loaders = make_certificate_health_loaders(settings, gate.clone());
+    run_certificate_health_monitor(health, loaders, interval, warning_days, gate).await
+}
+
+// Make the route for the diagnostic endpoint, at /diagnostics/certificates
+pub fn make_certificate_health_api(
+    health: Arc<Mutex<CertificateHealth>>,
+) -> impl Filter<Extract = (warp::reply::Response,), Error = Rejection> + Clone {
+    warp::path!("diagnostics" / "certificates")
+        .and(warp::get())
+        .map(move || {
+            let health = health.lock().unwrap().clone();
+            warp::reply::json(&serde_json::json!({
+                "status": health.status(),
+                "certificates": health.certificates,
+                "errors": health.errors,
+            }))
+            .into_response()
+        })
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::certificate::CertificateError;
+
+    fn make_certificate(days: i64) -> Certificate {
+        let mut params =
+            rcgen::CertificateParams::new(vec!["node.example.com".to_string()]).unwrap();
+        let not_after = SystemTime::now() + Duration::from_secs(days as u64 * 24 * 60 * 60 + 60);
+        params.not_after = not_after.into();
+        let key_pair = rcgen::KeyPair::generate().unwrap();
+        let certificate = params.self_signed(&key_pair).unwrap();
+        Certificate::new(&key_pair.serialize_pem(), &certificate.pem()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_certificate_health() {
+        let gate = Logger::root(slog::Discard, slog::o!());
+        let server = vec![make_certificate(90), make_certificate(3)];
+        let loaders: Vec<(String, CertificateLoader)> = vec![
+            ("server".to_string(), Box::new(move || Ok(server.clone()))),
+            (
+                "ca".to_string(),
+                Box::new(|| Err(CertificateError::NoFileSpecified("CA certificate".into()))),
+            ),
+        ];
+        let health = check_certificate_health(&loaders, DEFAULT_EXPIRY_WARNING_DAYS, &gate);
+        let days = health
+            .certificates
+            .iter()
+            .map(|certificate| (certificate.days_until_expiry, certificate.status))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            days,
+            vec![(90, ExpiryStatus::Ok), (3, ExpiryStatus::Expiring)]
+        );
+        assert_eq!(health.errors.len(), 1);
+        assert_eq!(health.status(), ExpiryStatus::Expired);
+
+        let health = Arc::new(Mutex::new(health));
+        let api = make_certificate_health_api(health);
+        let response = warp::test::request()
+            .path("/diagnostics/certificates")
+            .reply(&api)
+            .await;
+        assert_eq!(response.status(), 200);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["status"], "expired");
+        assert_eq!(body["certificates"][1]["status"], "expiring");
+        assert_eq!(body["certificates"][1]["role"], "server");
+    }
+
+    #[tokio::test]
+    async fn test_expired_certificate_health() {
+        let gate = Logger::root(slog::Discard, slog::o!());
+        let dir = std::env::temp_dir().join(format!("nmos-health-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut params =
+            rcgen::CertificateParams::new(vec!["node.example.com".to_string()]).unwrap();
+        params.not_before = rcgen::date_time_ymd(2000, 1, 1);
+        params.not_after = rcgen::date_time_ymd(2001, 1, 1);
+        let key_pair = rcgen::KeyPair::generate().unwrap();
+        let expired = params.self_signed(&key_pair).unwrap();
+        let key_file = dir.join("expired.key");
+        let chain_file = dir.join("expired.pem");
+        std::fs::write(&key_file, key_pair.serialize_pem()).unwrap();
+        std::fs::write(&chain_file, expired.pem()).unwrap();
+
+        // The expired certificate is reported, even though it can't be loaded for the listeners
+        let settings = Arc::new(Settings {
+            private_key_files: vec![key_file.to_string_lossy().into_owned()],
+            certificate_chain_files: vec![chain_file.to_string_lossy().into_owned()],
+            certificate_health_interval: 0,
+            ..Default::default()
+        });
+        let health = Arc::new(Mutex::new(CertificateHealth::default()));
+        run_certificate_health_monitor_from_settings(health.clone(), settings, gate).await;
+        let health = health.lock().unwrap();
+        assert_eq!(health.status(), ExpiryStatus::Expired);
+        assert!(health.errors.is_empty());
+        assert_eq!(health.certificates.len(), 1);
+        assert_eq!(health.certificates[0].status, ExpiryStatus::Expired);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
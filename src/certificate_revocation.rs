@@ -0,0 +1,601 @@
+// Client-side TLS, e.g. for a Node connecting to a Registration API, which verifies the server
+// certificate against the CA certificates and optionally checks that it hasn't been revoked,
+// either against CRL files or by asking a local OCSP responder
+// See https://specs.amwa.tv/bcp-003-01/releases/v1.0.0/docs/1.0._Secure_Communication.html
+// and https://www.rfc-editor.org/rfc/rfc6960
+
+use std::fs;
+use std::io;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ring::signature::{self, UnparsedPublicKey, VerificationAlgorithm};
+use rustls::client::WebPkiServerVerifier;
+use rustls::crypto::ring as provider;
+use rustls::pki_types::{CertificateDer, CertificateRevocationListDer, ServerName};
+use rustls::ClientConfig;
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::TlsConnector;
+use warp::hyper::{body, header, Body, Client, Method, Request};
+use x509_parser::der_parser::asn1_rs::{Any, Class, Tag};
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+use crate::certificate::{make_root_store, subject, Certificate, CertificateError};
+use crate::settings::Settings;
+
+// The tolerance for clock differences when checking that an OCSP response is current
+const OCSP_CLOCK_SKEW: i64 = 5 * 60;
+
+// Load the CRLs from PEM files
+pub fn load_crls(
+    paths: &[String],
+) -> Result<Vec<CertificateRevocationListDer<'static>>, CertificateError> {
+    let mut crls = Vec::new();
+    for path in paths {
+        let contents =
+            fs::read_to_string(path).map_err(|e| CertificateError::MissingFile(path.clone(), e))?;
+        let loaded = rustls_pemfile::crls(&mut contents.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| CertificateError::InvalidPem(format!("{}: {}", path, e)))?;
+        if loaded.is_empty() {
+            return Err(CertificateError::InvalidPem(format!(
+                "{}: no CRLs found",
+                path
+            )));
+        }
+        crls.extend(loaded);
+    }
+    Ok(crls)
+}
+
+// Define the client-side TLS configuration, including how revocation is checked
+#[derive(Clone)]
+pub struct ClientTls {
+    pub config: Arc<ClientConfig>,
+    // The URL of the local OCSP responder, if the server certificate should be checked with it
+    pub ocsp_responder: Option<String>,
+    ca_certificates: Vec<CertificateDer<'static>>,
+}
+
+impl ClientTls {
+    // Make the client-side TLS configuration from the CA certificates, checking revocation
+    // against the specified CRLs, if any, during the handshake
+    pub fn new(
+        ca_certificates: &[Certificate],
+        crls: Vec<CertificateRevocationListDer<'static>>,
+        ocsp_responder: Option<String>,
+    ) -> Result<Self, CertificateError> {
+        let roots = make_root_store(ca_certificates)?;
+        let ca_certificates = ca_certificates
+            .iter()
+            .map(|certificate| certificate.certificate_chain_der())
+            .collect::<Result<Vec<_>, _>>()?
+            .concat();
+
+        let provider = Arc::new(provider::default_provider());
+        let mut verifier =
+            WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone());
+        if !crls.is_empty() {
+            verifier = verifier.with_crls(crls);
+        }
+        let verifier = verifier.build().map_err(|e| {
+            CertificateError::InvalidPem(format!("invalid CA certificates or CRLs: {}", e))
+        })?;
+        let config = ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .map_err(|e| CertificateError::RevocationCheck(e.to_string()))?
+            .with_webpki_verifier(verifier)
+            .with_no_client_auth();
+
+        Ok(ClientTls {
+            config: Arc::new(config),
+            ocsp_responder,
+            ca_certificates,
+        })
+    }
+
+    // Make the client-side TLS configuration from the settings and the loaded CA certificates
+    pub fn from_settings(
+        settings: &Settings,
+        ca_certificates: &[Certificate],
+    ) -> Result<Self, CertificateError> {
+        ClientTls::new(
+            ca_certificates,
+            load_crls(&settings.crl_files)?,
+            settings.ocsp_responder.clone(),
+        )
+    }
+
+    // Connect to the server, failing if its certificate can't be verified, or has been revoked
+    pub async fn connect(
+        &self,
+        server_name: ServerName<'static>,
+        stream: TcpStream,
+    ) -> io::Result<TlsStream<TcpStream>> {
+        let stream = TlsConnector::from(self.config.clone())
+            .connect(server_name, stream)
+            .await?;
+        if let Some(responder) = &self.ocsp_responder {
+            let chain = stream.get_ref().1.peer_certificates().unwrap_or_default();
+            check_ocsp(responder, chain, &self.ca_certificates)
+                .await
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        }
+        Ok(stream)
+    }
+}
+
+// Ask the OCSP responder whether the leaf certificate of the chain has been revoked
+pub async fn check_ocsp(
+    responder: &str,
+    chain: &[CertificateDer<'_>],
+    ca_certificates: &[CertificateDer<'_>],
+) -> Result<(), CertificateError> {
+    let parse = |der| {
+        X509Certificate::from_der(der)
+            .map(|(_, certificate)| certificate)
+            .map_err(|e| CertificateError::InvalidPem(e.to_string()))
+    };
+    let leaf = parse(chain.first().ok_or_else(|| {
+        CertificateError::RevocationCheck("no certificate presented".to_string())
+    })?)?;
+    let issuer = chain[1..]
+        .iter()
+        .chain(ca_certificates)
+        .filter_map(|der| parse(der).ok())
+        .find(|issuer| issuer.subject().as_raw() == leaf.issuer().as_raw())
+        .ok_or_else(|| {
+            CertificateError::RevocationCheck(format!("no issuer found for {}", leaf.subject()))
+        })?;
+
+    let cert_id = make_ocsp_cert_id(&leaf, &issuer);
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(responder)
+        .header(header::CONTENT_TYPE, "application/ocsp-request")
+        .body(Body::from(make_ocsp_request(&cert_id)))
+        .map_err(|e| CertificateError::RevocationCheck(e.to_string()))?;
+    let response = Client::new()
+        .request(request)
+        .await
+        .map_err(|e| CertificateError::RevocationCheck(e.to_string()))?;
+    if !response.status().is_success() {
+        return Err(CertificateError::RevocationCheck(format!(
+            "OCSP responder returned {}",
+            response.status()
+        )));
+    }
+    let response = body::to_bytes(response.into_body())
+        .await
+        .map_err(|e| CertificateError::RevocationCheck(e.to_string()))?;
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs() as i64)
+        .unwrap_or_default();
+    check_ocsp_response(&response, &cert_id, &leaf, &issuer, now)
+}
+
+// Encode a DER TLV
+fn der(tag: u8, contents: &[u8]) -> Vec<u8> {
+    let mut encoded = vec![tag];
+    if contents.len() < 0x80 {
+        encoded.push(contents.len() as u8);
+    } else {
+        let length = contents.len().to_be_bytes();
+        let length = &length[length.iter().position(|byte| *byte != 0).unwrap_or(0)..];
+        encoded.push(0x80 | length.len() as u8);
+        encoded.extend(length);
+    }
+    encoded.extend(contents);
+    encoded
+}
+
+// The DER CertID identifying the certificate to the OCSP responder, using SHA-1 since that is
+// what responders are required to support
+fn make_ocsp_cert_id(leaf: &X509Certificate, issuer: &X509Certificate) -> Vec<u8> {
+    let sha1 = |data: &[u8]| {
+        ring::digest::digest(&ring::digest::SHA1_FOR_LEGACY_USE_ONLY, data)
+            .as_ref()
+            .to_vec()
+    };
+    let algorithm = der(
+        0x30,
+        &[der(0x06, &[0x2b, 0x0e, 0x03, 0x02, 0x1a]), vec![0x05, 0x00]].concat(),
+    );
+    let name_hash = sha1(leaf.issuer().as_raw());
+    let key_hash = sha1(&issuer.public_key().subject_public_key.data);
+    der(
+        0x30,
+        &[
+            algorithm,
+            der(0x04, &name_hash),
+            der(0x04, &key_hash),
+            der(0x02, leaf.raw_serial()),
+        ]
+        .concat(),
+    )
+}
+
+// The DER OCSPRequest, with a single request
+fn make_ocsp_request(cert_id: &[u8]) -> Vec<u8> {
+    der(0x30, &der(0x30, &der(0x30, &der(0x30, cert_id))))
+}
+
+fn invalid_response(what: &str) -> CertificateError {
+    CertificateError::RevocationCheck(format!("invalid OCSP response: {}", what))
+}
+
+// Parse the DER elements of a constructed value, along with their encodings
+fn parse_elements(mut data: &[u8]) -> Result<Vec<(&[u8], Any<'_>)>, CertificateError> {
+    let mut elements = Vec::new();
+    while !data.is_empty() {
+        let (rest, element) = Any::from_der(data).map_err(|_| invalid_response("bad DER"))?;
+        elements.push((&data[..data.len() - rest.len()], element));
+        data = rest;
+    }
+    Ok(elements)
+}
+
+fn parse_contents(data: &[u8]) -> Result<Vec<(&[u8], Any<'_>)>, CertificateError> {
+    let (_, element) = Any::from_der(data).map_err(|_| invalid_response("bad DER"))?;
+    parse_elements(element.data)
+}
+
+fn is_context(element: &Any, tag: u32) -> bool {
+    element.class() == Class::ContextSpecific && element.tag() == Tag(tag)
+}
+
+fn parse_time(element: &Any) -> Result<i64, CertificateError> {
+    element
+        .clone()
+        .generalizedtime()
+        .ok()
+        .and_then(|time| time.utc_datetime().ok())
+        .map(|time| time.unix_timestamp())
+        .ok_or_else(|| invalid_response("bad time"))
+}
+
+// Verify a signature with a public key, for the signature algorithms allowed by BCP-003-01
+fn verify_signature(algorithm: &str, public_key: &[u8], message: &[u8], signature: &[u8]) -> bool {
+    let algorithms: &[&dyn VerificationAlgorithm] = match algorithm {
+        "1.2.840.113549.1.1.11" => &[&signature::RSA_PKCS1_2048_8192_SHA256],
+        "1.2.840.113549.1.1.12" => &[&signature::RSA_PKCS1_2048_8192_SHA384],
+        "1.2.840.113549.1.1.13" => &[&signature::RSA_PKCS1_2048_8192_SHA512],
+        "1.2.840.10045.4.3.2" => &[
+            &signature::ECDSA_P256_SHA256_ASN1,
+            &signature::ECDSA_P384_SHA256_ASN1,
+        ],
+        "1.2.840.10045.4.3.3" => &[
+            &signature::ECDSA_P384_SHA384_ASN1,
+            &signature::ECDSA_P256_SHA384_ASN1,
+        ],
+        _ => &[],
+    };
+    algorithms.iter().any(|algorithm| {
+        UnparsedPublicKey::new(*algorithm, public_key)
+            .verify(message, signature)
+            .is_ok()
+    })
+}
+
+// Check the OCSP response for the certificate, which must be signed by its issuer, or by a
+// responder certificate that the issuer has delegated OCSP signing to
+// See https://www.rfc-editor.org/rfc/rfc6960#section-4.2.1
+fn check_ocsp_response(
+    response: &[u8],
+    cert_id: &[u8],
+    leaf: &X509Certificate,
+    issuer: &X509Certificate,
+    now: i64,
+) -> Result<(), CertificateError> {
+    let ocsp_response = parse_contents(response)?;
+    let status = ocsp_response
+        .first()
+        .filter(|(_, element)| element.tag() == Tag::Enumerated)
+        .and_then(|(_, element)| element.data.first())
+        .ok_or_else(|| invalid_response("no status"))?;
+    if *status != 0 {
+        return Err(CertificateError::RevocationCheck(format!(
+            "OCSP responder status {}",
+            status
+        )));
+    }
+
+    // responseBytes, which must be a BasicOCSPResponse
+    let response_bytes = ocsp_response
+        .get(1)
+        .filter(|(_, element)| is_context(element, 0))
+        .ok_or_else(|| invalid_response("no response bytes"))?;
+    let response_bytes = parse_contents(response_bytes.1.data)?;
+    let response_type = response_bytes
+        .first()
+        .and_then(|(_, element)| element.clone().oid().ok())
+        .map(|oid| oid.to_id_string());
+    if response_type.as_deref() != Some("1.3.6.1.5.5.7.48.1.1") {
+        return Err(invalid_response("unsupported response type"));
+    }
+    let basic_response = response_bytes
+        .get(1)
+        .ok_or_else(|| invalid_response("no basic response"))?;
+    let basic_response = parse_contents(basic_response.1.data)?;
+    let [(tbs_response_data, tbs), (_, algorithm), (_, signature), certs @ ..] =
+        &basic_response[..]
+    else {
+        return Err(invalid_response("incomplete basic response"));
+    };
+
+    // The signature
+    let algorithm = parse_elements(algorithm.data)?
+        .first()
+        .and_then(|(_, element)| element.clone().oid().ok())
+        .map(|oid| oid.to_id_string())
+        .unwrap_or_default();
+    let signature = signature.data.get(1..).unwrap_or_default();
+    let delegated = match certs.first() {
+        Some((_, certs)) if is_context(certs, 0) => parse_contents(certs.data)?
+            .into_iter()
+            .filter_map(|(der, _)| X509Certificate::from_der(der).ok().map(|(_, c)| c))
+            .filter(|responder| {
+                responder.issuer().as_raw() == issuer.subject().as_raw()
+                    && responder.verify_signature(Some(issuer.public_key())).is_ok()
+                    && matches!(responder.extended_key_usage(), Ok(Some(eku)) if eku.value.ocsp_signing)
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    let signed = std::iter::once(issuer).chain(&delegated).any(|signer| {
+        verify_signature(
+            &algorithm,
+            &signer.public_key().subject_public_key.data,
+            tbs_response_data,
+            signature,
+        )
+    });
+    if !signed {
+        return Err(invalid_response("bad signature"));
+    }
+
+    // The status of the certificate
+    let responses = parse_elements(tbs.data)?
+        .into_iter()
+        .find(|(_, element)| element.class() == Class::Universal && element.tag() == Tag::Sequence)
+        .ok_or_else(|| invalid_response("no responses"))?;
+    for (_, single_response) in parse_elements(responses.1.data)? {
+        let fields = parse_elements(single_response.data)?;
+        let [(response_cert_id, _), (_, cert_status), (_, this_update), extra @ ..] = &fields[..]
+        else {
+            return Err(invalid_response("incomplete single response"));
+        };
+        if *response_cert_id != cert_id {
+            continue;
+        }
+
+        if parse_time(this_update)? > now + OCSP_CLOCK_SKEW {
+            return Err(invalid_response("not yet valid"));
+        }
+        if let Some((_, next_update)) = extra.iter().find(|(_, element)| is_context(element, 0)) {
+            let (_, next_update) =
+                Any::from_der(next_update.data).map_err(|_| invalid_response("bad DER"))?;
+            if parse_time(&next_update)? < now - OCSP_CLOCK_SKEW {
+                return Err(invalid_response("out of date"));
+            }
+        }
+
+        return match cert_status.tag() {
+            Tag(0) if cert_status.class() == Class::ContextSpecific => Ok(()),
+            Tag(1) if cert_status.class() == Class::ContextSpecific => {
+                Err(CertificateError::Revoked(leaf.subject().to_string()))
+            }
+            _ => Err(CertificateError::RevocationCheck(format!(
+                "revocation status of {} is unknown",
+                subject(leaf.as_ref())
+            ))),
+        };
+    }
+    Err(invalid_response("no response for the certificate"))
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_listener::{make_server_config, serve_tls, CertificateResolver, CipherPolicy};
+    use ring::rand::SystemRandom;
+    use ring::signature::{EcdsaKeyPair, ECDSA_P256_SHA256_ASN1_SIGNING};
+    use slog::Logger;
+    use warp::Filter;
+
+    struct TestPki {
+        ca: Certificate,
+        ca_params: rcgen::CertificateParams,
+        ca_key: rcgen::KeyPair,
+        server: Certificate,
+    }
+
+    fn make_pki() -> TestPki {
+        let mut ca_params = rcgen::CertificateParams::new(Vec::new()).unwrap();
+        ca_params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+        ca_params.key_usages = vec![
+            rcgen::KeyUsagePurpose::KeyCertSign,
+            rcgen::KeyUsagePurpose::CrlSign,
+            rcgen::KeyUsagePurpose::DigitalSignature,
+        ];
+        let ca_key = rcgen::KeyPair::generate().unwrap();
+        let ca_certificate = ca_params.clone().self_signed(&ca_key).unwrap();
+
+        let mut server_params =
+            rcgen::CertificateParams::new(vec!["registry.example.com".to_string()]).unwrap();
+        server_params.serial_number = Some(rcgen::SerialNumber::from(42u64));
+        let server_key = rcgen::KeyPair::generate().unwrap();
+        let server_certificate = server_params
+            .signed_by(&server_key, &ca_certificate, &ca_key)
+            .unwrap();
+
+        TestPki {
+            ca: Certificate::from_pem(&ca_certificate.pem()).unwrap(),
+            ca_params,
+            ca_key,
+            server: Certificate::new(&server_key.serialize_pem(), &server_certificate.pem())
+                .unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_crl() {
+        let gate = Logger::root(slog::Discard, slog::o!());
+        let pki = make_pki();
+        let resolver = Arc::new(CertificateResolver::new(CipherPolicy::Bcp00301));
+        resolver.load(std::slice::from_ref(&pki.server)).unwrap();
+        let settings = Settings {
+            server_secure: true,
+            ..Default::default()
+        };
+        let tls_config = make_server_config(&settings, resolver, &[])
+            .unwrap()
+            .unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let api = warp::path::end().map(warp::reply);
+        let server = tokio::spawn(serve_tls(listener, api, tls_config, None, gate));
+
+        let connect = |client: ClientTls| async move {
+            let stream = TcpStream::connect(address).await.unwrap();
+            let server_name = ServerName::try_from("registry.example.com").unwrap();
+            client.connect(server_name, stream).await
+        };
+
+        let client = ClientTls::new(std::slice::from_ref(&pki.ca), Vec::new(), None).unwrap();
+        assert!(connect(client).await.is_ok());
+
+        let crl = rcgen::CertificateRevocationListParams {
+            this_update: rcgen::date_time_ymd(2000, 1, 1),
+            next_update: rcgen::date_time_ymd(2100, 1, 1),
+            crl_number: rcgen::SerialNumber::from(1u64),
+            issuing_distribution_point: None,
+            revoked_certs: vec![rcgen::RevokedCertParams {
+                serial_number: rcgen::SerialNumber::from(42u64),
+                revocation_time: rcgen::date_time_ymd(2001, 1, 1),
+                reason_code: None,
+                invalidity_date: None,
+            }],
+            key_identifier_method: rcgen::KeyIdMethod::Sha256,
+        };
+        let ca_certificate = pki.ca_params.clone().self_signed(&pki.ca_key).unwrap();
+        let crl = crl.signed_by(&ca_certificate, &pki.ca_key).unwrap();
+        let crls = vec![CertificateRevocationListDer::from(crl.der().to_vec())];
+        let client = ClientTls::new(std::slice::from_ref(&pki.ca), crls, None).unwrap();
+        assert!(connect(client).await.is_err());
+
+        server.abort();
+    }
+
+    // Make a signed OCSP response for the certificate with the specified status and next update
+    fn make_ocsp_response(
+        pki: &TestPki,
+        cert_id: &[u8],
+        cert_status: &[u8],
+        next_update: &[u8],
+    ) -> Vec<u8> {
+        let single_response = der(
+            0x30,
+            &[
+                cert_id.to_vec(),
+                cert_status.to_vec(),
+                der(0x18, b"20000101000000Z"),
+                der(0xa0, &der(0x18, next_update)),
+            ]
+            .concat(),
+        );
+        let tbs_response_data = der(
+            0x30,
+            &[
+                der(0xa2, &der(0x04, &[0; 20])),
+                der(0x18, b"20000101000000Z"),
+                der(0x30, &single_response),
+            ]
+            .concat(),
+        );
+        let rng = SystemRandom::new();
+        let key = EcdsaKeyPair::from_pkcs8(
+            &ECDSA_P256_SHA256_ASN1_SIGNING,
+            &pki.ca_key.serialize_der(),
+            &rng,
+        )
+        .unwrap();
+        let signature = key.sign(&rng, &tbs_response_data).unwrap();
+        let algorithm = der(
+            0x30,
+            &der(0x06, &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02]),
+        );
+        let basic_response = der(
+            0x30,
+            &[
+                tbs_response_data,
+                algorithm,
+                der(0x03, &[&[0], signature.as_ref()].concat()),
+            ]
+            .concat(),
+        );
+        let basic_oid = [0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x30, 0x01, 0x01];
+        let response_bytes = der(
+            0x30,
+            &[der(0x06, &basic_oid), der(0x04, &basic_response)].concat(),
+        );
+        der(
+            0x30,
+            &[vec![0x0a, 0x01, 0x00], der(0xa0, &response_bytes)].concat(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_ocsp() {
+        let pki = make_pki();
+        let chain = pki.server.certificate_chain_der().unwrap();
+        let cas = pki.ca.certificate_chain_der().unwrap();
+        let (_, leaf) = X509Certificate::from_der(&chain[0]).unwrap();
+        let (_, issuer) = X509Certificate::from_der(&cas[0]).unwrap();
+        let cert_id = make_ocsp_cert_id(&leaf, &issuer);
+        let now = 1_800_000_000;
+
+        let good = make_ocsp_response(&pki, &cert_id, &[0x80, 0x00], b"21000101000000Z");
+        assert!(check_ocsp_response(&good, &cert_id, &leaf, &issuer, now).is_ok());
+
+        let revoked_info = der(0x18, b"20010101000000Z");
+        let revoked = make_ocsp_response(
+            &pki,
+            &cert_id,
+            &der(0xa1, &revoked_info),
+            b"21000101000000Z",
+        );
+        assert!(matches!(
+            check_ocsp_response(&revoked, &cert_id, &leaf, &issuer, now),
+            Err(CertificateError::Revoked(_))
+        ));
+
+        let stale = make_ocsp_response(&pki, &cert_id, &[0x80, 0x00], b"20000102000000Z");
+        assert!(matches!(
+            check_ocsp_response(&stale, &cert_id, &leaf, &issuer, now),
+            Err(CertificateError::RevocationCheck(_))
+        ));
+
+        let mut tampered = good.clone();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        assert!(check_ocsp_response(&tampered, &cert_id, &leaf, &issuer, now).is_err());
+
+        // Over HTTP, the request identifies the certificate
+        let responder =
+            warp::post()
+                .and(warp::body::bytes())
+                .map(move |request: warp::hyper::body::Bytes| {
+                    assert_eq!(request.as_ref(), make_ocsp_request(&cert_id).as_slice());
+                    revoked.clone()
+                });
+        let (address, server) = warp::serve(responder).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        let result = check_ocsp(&format!("http://{}/", address), &chain, &cas).await;
+        assert!(matches!(result, Err(CertificateError::Revoked(_))));
+    }
+}
@@ -0,0 +1,514 @@
+// IS-14 Device Configuration API, a REST interface to the MS-05-02 objects of a device, which
+// supports backup and restore of their properties
+// See https://specs.amwa.tv/is-14/releases/v1.0.0/docs/Overview.html
+// and https://specs.amwa.tv/is-14/releases/v1.0.0/APIs/ConfigurationAPI.html
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use serde_json::{json, Value};
+use slog::Logger;
+use warp::http::{Method, StatusCode};
+use warp::path::Tail;
+use warp::{Filter, Rejection, Reply};
+
+use crate::api_methods::{with_allowed_methods, RouteMethods};
+use crate::control_protocol::{
+    method_ids, ControlProtocolState, NcElementId, NcMethodResult, NcMethodStatus,
+};
+use crate::control_protocol_bulk_properties::{
+    make_bulk_values_holder, property_descriptors, set_bulk_values, NcRestoreMode,
+};
+use crate::header_policy::{make_header_policy, with_header_policy};
+use crate::metrics::{with_request_metrics, Metrics};
+use crate::server_timing::with_server_timing;
+use crate::settings::Settings;
+
+// Role paths are represented in the API as the roles of each object, separated by '.'
+// See https://specs.amwa.tv/is-14/releases/v1.0.0/docs/Overview.html#role-paths
+pub const ROLE_PATH_SEPARATOR: char = '.';
+
+// Map a method result to the HTTP response, with the same status code unless it indicates success
+// See https://specs.amwa.tv/is-14/releases/v1.0.0/docs/Overview.html#status-codes
+fn make_method_result_reply(result: NcMethodResult) -> warp::reply::Response {
+    let status = result.status as u16;
+    let code = if status < 300 {
+        StatusCode::OK
+    } else {
+        StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    };
+    warp::reply::with_status(warp::reply::json(&result.to_json()), code).into_response()
+}
+
+fn make_sub_routes_reply(sub_routes: Vec<String>) -> warp::reply::Response {
+    warp::reply::json(&sub_routes).into_response()
+}
+
+fn not_found(message: impl Into<String>) -> NcMethodResult {
+    NcMethodResult::error(NcMethodStatus::BadOid, message)
+}
+
+// Construct a standard NMOS error response for an unsupported method on an existing resource
+fn method_not_allowed() -> Result<warp::reply::Response, NcMethodResult> {
+    let code = StatusCode::METHOD_NOT_ALLOWED;
+    Ok(warp::reply::with_status(
+        warp::reply::json(&json!({
+            "code": code.as_u16(),
+            "error": code.canonical_reason(),
+            "debug": null,
+        })),
+        code,
+    )
+    .into_response())
+}
+
+fn parse_body(body: &[u8]) -> Result<Value, NcMethodResult> {
+    serde_json::from_slice(body).map_err(|e| {
+        NcMethodResult::error(
+            NcMethodStatus::BadCommandFormat,
+            format!("invalid request body: {}", e),
+        )
+    })
+}
+
+// Parse the {propertyId} or {methodId} path parameter, e.g. "1p1" or "1m1"
+fn parse_element_id(id: &str, separator: char) -> Option<NcElementId> {
+    let (level, index) = id.split_once(separator)?;
+    Some(NcElementId::new(level.parse().ok()?, index.parse().ok()?))
+}
+
+// Handle the requests for a specific role path
+fn handle_role_path(
+    state: &mut ControlProtocolState,
+    method: &Method,
+    role_path: &str,
+    segments: &[&str],
+    query: &HashMap<String, String>,
+    body: &[u8],
+) -> Result<warp::reply::Response, NcMethodResult> {
+    let roles: Vec<&str> = role_path.split(ROLE_PATH_SEPARATOR).collect();
+    let object = state
+        .find_role_path(&roles)
+        .ok_or_else(|| not_found(format!("role path {} not found", role_path)))?;
+    let oid = object.oid;
+    let class_descriptor = {
+        let mut class_id = object.class_id.clone();
+        loop {
+            if let Ok(descriptor) = state.class_registry().control_class(&class_id, true) {
+                break descriptor;
+            }
+            class_id.pop();
+            if class_id.is_empty() {
+                return Err(NcMethodResult::error(
+                    NcMethodStatus::DeviceError,
+                    format!("no class descriptor for role path {}", role_path),
+                ));
+            }
+        }
+    };
+    let descriptors = |elements: &str| -> Vec<Value> {
+        class_descriptor[elements]
+            .as_array()
+            .cloned()
+            .unwrap_or_default()
+    };
+    let find_descriptor = |elements: &str, id: NcElementId| {
+        descriptors(elements)
+            .into_iter()
+            .find(|descriptor| NcElementId::from_json(&descriptor["id"]) == Some(id))
+    };
+
+    match (method, segments) {
+        (&Method::GET, []) => Ok(make_sub_routes_reply(vec![
+            "bulkProperties/".to_string(),
+            "descriptor/".to_string(),
+            "methods/".to_string(),
+            "properties/".to_string(),
+        ])),
+
+        (&Method::GET, ["descriptor"]) => Ok(make_method_result_reply(NcMethodResult::ok_with(
+            class_descriptor.clone(),
+        ))),
+
+        // Only the properties which the object actually implements are listed
+        (&Method::GET, ["properties"]) => {
+            let ids = state
+                .get(oid)
+                .map(|object| {
+                    property_descriptors(state.class_registry(), object)
+                        .iter()
+                        .filter_map(|descriptor| NcElementId::from_json(&descriptor["id"]))
+                        .filter(|id| object.properties.contains_key(id))
+                        .map(|id| format!("{}/", id))
+                        .collect()
+                })
+                .unwrap_or_default();
+            Ok(make_sub_routes_reply(ids))
+        }
+        (_, ["properties", property_id, rest @ ..]) => {
+            let id = parse_element_id(property_id, 'p')
+                .filter(|id| {
+                    state
+                        .get(oid)
+                        .is_some_and(|o| o.properties.contains_key(id))
+                })
+                .ok_or_else(|| not_found(format!("property {} not found", property_id)))?;
+            match (method, rest) {
+                (&Method::GET, []) => Ok(make_sub_routes_reply(vec![
+                    "descriptor/".to_string(),
+                    "value/".to_string(),
+                ])),
+                (&Method::GET, ["descriptor"]) => {
+                    let descriptor = find_descriptor("properties", id).ok_or_else(|| {
+                        not_found(format!("no descriptor for property {}", property_id))
+                    })?;
+                    Ok(make_method_result_reply(NcMethodResult::ok_with(
+                        descriptor,
+                    )))
+                }
+                (&Method::GET, ["value"]) => Ok(make_method_result_reply(state.invoke(
+                    oid,
+                    method_ids::GET,
+                    &json!({ "id": id.to_json() }),
+                ))),
+                (&Method::PUT, ["value"]) => {
+                    let body = parse_body(body)?;
+                    Ok(make_method_result_reply(state.invoke(
+                        oid,
+                        method_ids::SET,
+                        &json!({ "id": id.to_json(), "value": body["value"] }),
+                    )))
+                }
+                (_, [] | ["descriptor"] | ["value"]) => method_not_allowed(),
+                _ => Err(not_found("resource not found")),
+            }
+        }
+
+        (&Method::GET, ["methods"]) => Ok(make_sub_routes_reply(
+            descriptors("methods")
+                .iter()
+                .filter_map(|descriptor| NcElementId::from_json(&descriptor["id"]))
+                .map(|id| format!("{}m{}/", id.level, id.index))
+                .collect(),
+        )),
+        (_, ["methods", method_id, rest @ ..]) => {
+            let id = parse_element_id(method_id, 'm')
+                .ok_or_else(|| not_found(format!("method {} not found", method_id)))?;
+            let descriptor = find_descriptor("methods", id)
+                .ok_or_else(|| not_found(format!("method {} not found", method_id)))?;
+            match (method, rest) {
+                (&Method::GET, []) => Ok(make_sub_routes_reply(vec!["descriptor/".to_string()])),
+                (&Method::GET, ["descriptor"]) => Ok(make_method_result_reply(
+                    NcMethodResult::ok_with(descriptor),
+                )),
+                (&Method::PATCH, []) => {
+                    let body = parse_body(body)?;
+                    let arguments = body.get("arguments").cloned().unwrap_or(json!({}));
+                    Ok(make_method_result_reply(state.invoke(oid, id, &arguments)))
+                }
+                (_, [] | ["descriptor"]) => method_not_allowed(),
+                _ => Err(not_found("resource not found")),
+            }
+        }
+
+        // Backup, with recursion unless otherwise requested
+        (&Method::GET, ["bulkProperties"]) => {
+            let recurse = query.get("recurse").map(String::as_str) != Some("false");
+            Ok(make_method_result_reply(NcMethodResult::ok_with(
+                make_bulk_values_holder(state, oid, recurse),
+            )))
+        }
+        // Validate (PATCH) or restore (PUT) a backup dataset
+        (&Method::PATCH | &Method::PUT, ["bulkProperties"]) => {
+            let body = parse_body(body)?;
+            let arguments = &body["arguments"];
+            let recurse = arguments["recurse"].as_bool().unwrap_or(true);
+            let restore_mode =
+                NcRestoreMode::from_json(&arguments["restoreMode"]).ok_or_else(|| {
+                    NcMethodResult::error(
+                        NcMethodStatus::ParameterError,
+                        "invalid restoreMode argument",
+                    )
+                })?;
+            let apply = *method == Method::PUT;
+            let validations = set_bulk_values(
+                state,
+                oid,
+                &arguments["dataSet"],
+                recurse,
+                restore_mode,
+                apply,
+            )?;
+            Ok(make_method_result_reply(NcMethodResult::ok_with(json!(
+                validations
+            ))))
+        }
+
+        (_, [] | ["descriptor"] | ["properties"] | ["methods"] | ["bulkProperties"]) => {
+            method_not_allowed()
+        }
+        _ => Err(not_found("resource not found")),
+    }
+}
+
+fn handle_request(
+    state: &Mutex<ControlProtocolState>,
+    method: &Method,
+    segments: &[&str],
+    query: &HashMap<String, String>,
+    body: &[u8],
+) -> warp::reply::Response {
+    let result = match segments {
+        [] if *method == Method::GET => Ok(make_sub_routes_reply(vec!["rolePaths/".to_string()])),
+        ["rolePaths"] if *method == Method::GET => {
+            let state = state.lock().unwrap();
+            let role_paths = state
+                .objects()
+                .map(|object| {
+                    let role_path = state.role_path(object.oid);
+                    format!("{}/", role_path.join(&ROLE_PATH_SEPARATOR.to_string()))
+                })
+                .collect();
+            Ok(make_sub_routes_reply(role_paths))
+        }
+        ["rolePaths", role_path, rest @ ..] => {
+            let mut state = state.lock().unwrap();
+            handle_role_path(&mut state, method, role_path, rest, query, body)
+        }
+        [] | ["rolePaths"] => method_not_allowed(),
+        _ => Err(not_found("resource not found")),
+    };
+    result.unwrap_or_else(make_method_result_reply)
+}
+
+// The methods allowed by each route of the Configuration API
+// See https://specs.amwa.tv/is-14/releases/v1.0.0/APIs/ConfigurationAPI.html
+pub const CONFIGURATION_API_METHODS: &[RouteMethods] = &[
+    ("/x-nmos/configuration/v1.0", &[Method::GET]),
+    ("/x-nmos/configuration/v1.0/rolePaths", &[Method::GET]),
+    ("/x-nmos/configuration/v1.0/rolePaths/{}", &[Method::GET]),
+    (
+        "/x-nmos/configuration/v1.0/rolePaths/{}/descriptor",
+        &[Method::GET],
+    ),
+    (
+        "/x-nmos/configuration/v1.0/rolePaths/{}/properties",
+        &[Method::GET],
+    ),
+    (
+        "/x-nmos/configuration/v1.0/rolePaths/{}/properties/{}",
+        &[Method::GET],
+    ),
+    (
+        "/x-nmos/configuration/v1.0/rolePaths/{}/properties/{}/descriptor",
+        &[Method::GET],
+    ),
+    (
+        "/x-nmos/configuration/v1.0/rolePaths/{}/properties/{}/value",
+        &[Method::GET, Method::PUT],
+    ),
+    (
+        "/x-nmos/configuration/v1.0/rolePaths/{}/methods",
+        &[Method::GET],
+    ),
+    (
+        "/x-nmos/configuration/v1.0/rolePaths/{}/methods/{}",
+        &[Method::GET, Method::PATCH],
+    ),
+    (
+        "/x-nmos/configuration/v1.0/rolePaths/{}/methods/{}/descriptor",
+        &[Method::GET],
+    ),
+    (
+        "/x-nmos/configuration/v1.0/rolePaths/{}/bulkProperties",
+        &[Method::GET, Method::PUT, Method::PATCH],
+    ),
+];
+
+// Make the routes for the IS-14 Configuration API, at /x-nmos/configuration/v1.0
+pub fn make_configuration_api(
+    state: Arc<Mutex<ControlProtocolState>>,
+    settings: &Settings,
+    metrics: Option<Arc<Metrics>>,
+    gate: Logger,
+) -> impl Filter<Extract = (warp::reply::Response,), Error = Rejection> + Clone {
+    let api = warp::path!("x-nmos" / "configuration" / "v1.0" / ..)
+        .and(warp::method())
+        .and(warp::path::tail())
+        .and(warp::query::<HashMap<String, String>>())
+        .and(warp::body::bytes())
+        .map(
+            move |method: Method,
+                  tail: Tail,
+                  query: HashMap<String, String>,
+                  body: warp::hyper::body::Bytes| {
+                let segments: Vec<&str> = tail
+                    .as_str()
+                    .split('/')
+                    .filter(|segment| !segment.is_empty())
+                    .collect();
+                handle_request(&state, &method, &segments, &query, &body)
+            },
+        );
+    // Each request is logged with its timing
+    let api = with_header_policy(
+        with_allowed_methods(api, CONFIGURATION_API_METHODS),
+        make_header_policy(settings, "configuration"),
+    );
+    with_server_timing(
+        with_request_metrics(api, "configuration", metrics),
+        "configuration",
+        gate,
+    )
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control_protocol::{class_ids, property_ids, NcObject};
+    use crate::control_protocol_resources::{make_block, make_root_block, ROOT_BLOCK_OID};
+
+    fn make_api() -> impl Filter<Extract = (warp::reply::Response,), Error = Rejection> + Clone {
+        let mut state = ControlProtocolState::new();
+        state.insert(make_root_block(Some("Device")));
+        let receivers = state
+            .add_member(make_block(
+                state.next_oid(),
+                Some(ROOT_BLOCK_OID),
+                "receivers",
+                Some("Receivers"),
+            ))
+            .unwrap();
+        state
+            .add_member(
+                NcObject::new(
+                    state.next_oid(),
+                    class_ids::NC_IDENT_BEACON,
+                    "IdentBeacon",
+                    Some(receivers),
+                    None,
+                )
+                .with_property(
+                    property_ids::IDENT_BEACON_ACTIVE,
+                    json!(false),
+                    false,
+                ),
+            )
+            .unwrap();
+        let gate = Logger::root(slog::Discard, slog::o!());
+        make_configuration_api(
+            Arc::new(Mutex::new(state)),
+            &Settings::default(),
+            None,
+            gate,
+        )
+    }
+
+    async fn request(
+        api: &(impl Filter<Extract = (warp::reply::Response,), Error = Rejection> + Clone + 'static),
+        method: &str,
+        path: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let mut request = warp::test::request().method(method).path(path);
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response = request.reply(api).await;
+        let body = serde_json::from_slice(response.body()).unwrap_or(Value::Null);
+        (response.status(), body)
+    }
+
+    #[tokio::test]
+    async fn test_properties() {
+        let api = make_api();
+        const BASE: &str = "/x-nmos/configuration/v1.0/rolePaths/root.receivers.IdentBeacon";
+
+        let (status, body) =
+            request(&api, "GET", "/x-nmos/configuration/v1.0/rolePaths/", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body
+            .as_array()
+            .unwrap()
+            .contains(&json!("root.receivers.IdentBeacon/")));
+
+        let (status, body) = request(&api, "GET", &format!("{}/properties", BASE), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.as_array().unwrap().contains(&json!("3p1/")));
+
+        let path = format!("{}/properties/3p1/value", BASE);
+        let (status, _) = request(&api, "PUT", &path, Some(json!({ "value": true }))).await;
+        assert_eq!(status, StatusCode::OK);
+        let (_, body) = request(&api, "GET", &path, None).await;
+        assert_eq!(body["value"], true);
+
+        let path = format!("{}/properties/1p1/value", BASE);
+        let (status, body) = request(&api, "PUT", &path, Some(json!({ "value": [1] }))).await;
+        assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(body["status"], NcMethodStatus::Readonly as u16);
+
+        let (status, body) = request(&api, "GET", &format!("{}/descriptor", BASE), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["value"]["name"], "NcIdentBeacon");
+
+        let path = "/x-nmos/configuration/v1.0/rolePaths/root.senders/properties";
+        let (status, _) = request(&api, "GET", path, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, body) = request(&api, "DELETE", &format!("{}/properties", BASE), None).await;
+        assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(body["code"], 405);
+    }
+
+    #[tokio::test]
+    async fn test_methods() {
+        let api = make_api();
+        let path = "/x-nmos/configuration/v1.0/rolePaths/root/methods/2m1";
+        let body = json!({ "arguments": { "recurse": true } });
+        let (status, body) = request(&api, "PATCH", path, Some(body)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["value"].as_array().unwrap().len(), 2);
+
+        let path = "/x-nmos/configuration/v1.0/rolePaths/root/methods/9m1";
+        let (status, _) = request(&api, "GET", path, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_bulk_properties() {
+        let api = make_api();
+        let path = "/x-nmos/configuration/v1.0/rolePaths/root/bulkProperties";
+
+        let (status, backup) = request(&api, "GET", path, None).await;
+        assert_eq!(status, StatusCode::OK);
+        let mut data_set = backup["value"].clone();
+        assert_eq!(data_set["values"].as_array().unwrap().len(), 3);
+
+        // change the user label of the root block in the dataset
+        for value in data_set["values"][0]["values"].as_array_mut().unwrap() {
+            if value["name"] == "userLabel" {
+                value["value"] = json!("Restored");
+            }
+        }
+        let body = json!({
+            "arguments": { "dataSet": data_set, "recurse": true, "restoreMode": 1 },
+        });
+
+        let (status, validations) = request(&api, "PATCH", path, Some(body.clone())).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(validations["value"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|validation| validation["status"] == 200));
+        let label = "/x-nmos/configuration/v1.0/rolePaths/root/properties/1p6/value";
+        let (_, result) = request(&api, "GET", label, None).await;
+        assert_eq!(result["value"], "Device");
+
+        let (status, _) = request(&api, "PUT", path, Some(body)).await;
+        assert_eq!(status, StatusCode::OK);
+        let (_, result) = request(&api, "GET", label, None).await;
+        assert_eq!(result["value"], "Restored");
+    }
+}
@@ -0,0 +1,1227 @@
+// An implementation of the MS-05-02 NMOS Control Framework object model, as used by the IS-12
+// NMOS Control Protocol
+// See https://specs.amwa.tv/ms-05-02/releases/v1.0.0/docs/Framework.html
+// and https://specs.amwa.tv/is-12/releases/v1.0.0/docs/Protocol_messaging.html
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::Arc;
+
+use serde_json::{json, Value};
+use tokio::sync::{broadcast, Notify};
+use tokio::time::Instant;
+
+use crate::control_protocol_classes::{class_id_at_level, ClassRegistry};
+use crate::control_protocol_ident_beacon::add_ident_beacon_setters;
+use crate::control_protocol_methods::{
+    add_block_methods, add_class_manager_methods, add_receiver_monitor_methods,
+    add_sender_monitor_methods,
+};
+use crate::control_protocol_resources::make_member_descriptor;
+use crate::types::nmos::ResourceType;
+
+// Define the element id used for property, method and event ids, e.g. "1p1" is the first
+// property defined by the class at level 1 of the class hierarchy (NcObject)
+// See https://specs.amwa.tv/ms-05-02/releases/v1.0.0/docs/Framework.html#ncelementid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NcElementId {
+    pub level: u16,
+    pub index: u16,
+}
+
+impl NcElementId {
+    pub const fn new(level: u16, index: u16) -> Self {
+        NcElementId { level, index }
+    }
+
+    pub fn to_json(self) -> Value {
+        json!({ "level": self.level, "index": self.index })
+    }
+
+    pub fn from_json(value: &Value) -> Option<Self> {
+        let level = value.get("level")?.as_u64()?;
+        let index = value.get("index")?.as_u64()?;
+        Some(NcElementId::new(
+            u16::try_from(level).ok()?,
+            u16::try_from(index).ok()?,
+        ))
+    }
+
+    // Parse the string form of a property id, e.g. "1p1"
+    pub fn parse_property_id(id: &str) -> Option<Self> {
+        let (level, index) = id.split_once('p')?;
+        Some(NcElementId::new(level.parse().ok()?, index.parse().ok()?))
+    }
+}
+
+// Property ids are displayed in the "<level>p<index>" form
+impl fmt::Display for NcElementId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}p{}", self.level, self.index)
+    }
+}
+
+// NcObject properties
+pub mod property_ids {
+    use super::NcElementId;
+
+    pub const CLASS_ID: NcElementId = NcElementId::new(1, 1);
+    pub const OID: NcElementId = NcElementId::new(1, 2);
+    pub const CONSTANT_OID: NcElementId = NcElementId::new(1, 3);
+    pub const OWNER: NcElementId = NcElementId::new(1, 4);
+    pub const ROLE: NcElementId = NcElementId::new(1, 5);
+    pub const USER_LABEL: NcElementId = NcElementId::new(1, 6);
+    pub const TOUCHPOINTS: NcElementId = NcElementId::new(1, 7);
+    pub const RUNTIME_PROPERTY_CONSTRAINTS: NcElementId = NcElementId::new(1, 8);
+
+    // NcBlock properties
+    pub const BLOCK_ENABLED: NcElementId = NcElementId::new(2, 1);
+    pub const BLOCK_MEMBERS: NcElementId = NcElementId::new(2, 2);
+
+    // NcDeviceManager properties
+    pub const DEVICE_MANAGER_NC_VERSION: NcElementId = NcElementId::new(3, 1);
+    pub const DEVICE_MANAGER_MANUFACTURER: NcElementId = NcElementId::new(3, 2);
+    pub const DEVICE_MANAGER_PRODUCT: NcElementId = NcElementId::new(3, 3);
+    pub const DEVICE_MANAGER_SERIAL_NUMBER: NcElementId = NcElementId::new(3, 4);
+    pub const DEVICE_MANAGER_USER_INVENTORY_CODE: NcElementId = NcElementId::new(3, 5);
+    pub const DEVICE_MANAGER_DEVICE_NAME: NcElementId = NcElementId::new(3, 6);
+    pub const DEVICE_MANAGER_DEVICE_ROLE: NcElementId = NcElementId::new(3, 7);
+    pub const DEVICE_MANAGER_OPERATIONAL_STATE: NcElementId = NcElementId::new(3, 8);
+    pub const DEVICE_MANAGER_RESET_CAUSE: NcElementId = NcElementId::new(3, 9);
+    pub const DEVICE_MANAGER_MESSAGE: NcElementId = NcElementId::new(3, 10);
+
+    // NcClassManager properties
+    pub const CLASS_MANAGER_CONTROL_CLASSES: NcElementId = NcElementId::new(3, 1);
+    pub const CLASS_MANAGER_DATATYPES: NcElementId = NcElementId::new(3, 2);
+
+    // NcIdentBeacon properties
+    pub const IDENT_BEACON_ACTIVE: NcElementId = NcElementId::new(3, 1);
+
+    // NcStatusMonitor properties
+    pub const STATUS_MONITOR_OVERALL_STATUS: NcElementId = NcElementId::new(3, 1);
+    pub const STATUS_MONITOR_OVERALL_STATUS_MESSAGE: NcElementId = NcElementId::new(3, 2);
+    pub const STATUS_MONITOR_STATUS_REPORTING_DELAY: NcElementId = NcElementId::new(3, 3);
+
+    // NcReceiverMonitor properties
+    pub const RECEIVER_MONITOR_LINK_STATUS: NcElementId = NcElementId::new(4, 1);
+    pub const RECEIVER_MONITOR_LINK_STATUS_MESSAGE: NcElementId = NcElementId::new(4, 2);
+    pub const RECEIVER_MONITOR_LINK_STATUS_TRANSITION_COUNTER: NcElementId = NcElementId::new(4, 3);
+    pub const RECEIVER_MONITOR_CONNECTION_STATUS: NcElementId = NcElementId::new(4, 4);
+    pub const RECEIVER_MONITOR_CONNECTION_STATUS_MESSAGE: NcElementId = NcElementId::new(4, 5);
+    pub const RECEIVER_MONITOR_CONNECTION_STATUS_TRANSITION_COUNTER: NcElementId =
+        NcElementId::new(4, 6);
+    pub const RECEIVER_MONITOR_EXTERNAL_SYNCHRONIZATION_STATUS: NcElementId =
+        NcElementId::new(4, 7);
+    pub const RECEIVER_MONITOR_EXTERNAL_SYNCHRONIZATION_STATUS_MESSAGE: NcElementId =
+        NcElementId::new(4, 8);
+    pub const RECEIVER_MONITOR_EXTERNAL_SYNCHRONIZATION_STATUS_TRANSITION_COUNTER: NcElementId =
+        NcElementId::new(4, 9);
+    pub const RECEIVER_MONITOR_SYNCHRONIZATION_SOURCE_ID: NcElementId = NcElementId::new(4, 10);
+    pub const RECEIVER_MONITOR_STREAM_STATUS: NcElementId = NcElementId::new(4, 11);
+    pub const RECEIVER_MONITOR_STREAM_STATUS_MESSAGE: NcElementId = NcElementId::new(4, 12);
+    pub const RECEIVER_MONITOR_STREAM_STATUS_TRANSITION_COUNTER: NcElementId =
+        NcElementId::new(4, 13);
+    pub const RECEIVER_MONITOR_AUTO_RESET_COUNTERS_AND_MESSAGES: NcElementId =
+        NcElementId::new(4, 14);
+
+    // NcSenderMonitor properties
+    pub const SENDER_MONITOR_LINK_STATUS: NcElementId = NcElementId::new(4, 1);
+    pub const SENDER_MONITOR_LINK_STATUS_MESSAGE: NcElementId = NcElementId::new(4, 2);
+    pub const SENDER_MONITOR_LINK_STATUS_TRANSITION_COUNTER: NcElementId = NcElementId::new(4, 3);
+    pub const SENDER_MONITOR_TRANSMISSION_STATUS: NcElementId = NcElementId::new(4, 4);
+    pub const SENDER_MONITOR_TRANSMISSION_STATUS_MESSAGE: NcElementId = NcElementId::new(4, 5);
+    pub const SENDER_MONITOR_TRANSMISSION_STATUS_TRANSITION_COUNTER: NcElementId =
+        NcElementId::new(4, 6);
+    pub const SENDER_MONITOR_EXTERNAL_SYNCHRONIZATION_STATUS: NcElementId = NcElementId::new(4, 7);
+    pub const SENDER_MONITOR_EXTERNAL_SYNCHRONIZATION_STATUS_MESSAGE: NcElementId =
+        NcElementId::new(4, 8);
+    pub const SENDER_MONITOR_EXTERNAL_SYNCHRONIZATION_STATUS_TRANSITION_COUNTER: NcElementId =
+        NcElementId::new(4, 9);
+    pub const SENDER_MONITOR_SYNCHRONIZATION_SOURCE_ID: NcElementId = NcElementId::new(4, 10);
+    pub const SENDER_MONITOR_ESSENCE_STATUS: NcElementId = NcElementId::new(4, 11);
+    pub const SENDER_MONITOR_ESSENCE_STATUS_MESSAGE: NcElementId = NcElementId::new(4, 12);
+    pub const SENDER_MONITOR_ESSENCE_STATUS_TRANSITION_COUNTER: NcElementId =
+        NcElementId::new(4, 13);
+    pub const SENDER_MONITOR_AUTO_RESET_COUNTERS_AND_MESSAGES: NcElementId =
+        NcElementId::new(4, 14);
+}
+
+// NcObject methods
+pub mod method_ids {
+    use super::NcElementId;
+
+    pub const GET: NcElementId = NcElementId::new(1, 1);
+    pub const SET: NcElementId = NcElementId::new(1, 2);
+    pub const GET_SEQUENCE_ITEM: NcElementId = NcElementId::new(1, 3);
+    pub const SET_SEQUENCE_ITEM: NcElementId = NcElementId::new(1, 4);
+    pub const ADD_SEQUENCE_ITEM: NcElementId = NcElementId::new(1, 5);
+    pub const REMOVE_SEQUENCE_ITEM: NcElementId = NcElementId::new(1, 6);
+    pub const GET_SEQUENCE_LENGTH: NcElementId = NcElementId::new(1, 7);
+
+    // NcBlock methods
+    pub const GET_MEMBER_DESCRIPTORS: NcElementId = NcElementId::new(2, 1);
+    pub const FIND_MEMBERS_BY_PATH: NcElementId = NcElementId::new(2, 2);
+    pub const FIND_MEMBERS_BY_ROLE: NcElementId = NcElementId::new(2, 3);
+    pub const FIND_MEMBERS_BY_CLASS_ID: NcElementId = NcElementId::new(2, 4);
+
+    // NcClassManager methods
+    pub const GET_CONTROL_CLASS: NcElementId = NcElementId::new(3, 1);
+    pub const GET_DATATYPE: NcElementId = NcElementId::new(3, 2);
+
+    // NcReceiverMonitor methods
+    pub const RECEIVER_MONITOR_GET_LOST_PACKET_COUNTERS: NcElementId = NcElementId::new(4, 1);
+    pub const RECEIVER_MONITOR_GET_LATE_PACKET_COUNTERS: NcElementId = NcElementId::new(4, 2);
+    pub const RECEIVER_MONITOR_RESET_COUNTERS_AND_MESSAGES: NcElementId = NcElementId::new(4, 3);
+
+    // NcSenderMonitor methods
+    pub const SENDER_MONITOR_GET_TRANSMISSION_ERROR_COUNTERS: NcElementId = NcElementId::new(4, 1);
+    pub const SENDER_MONITOR_RESET_COUNTERS_AND_MESSAGES: NcElementId = NcElementId::new(4, 2);
+}
+
+// NcObject events
+pub mod event_ids {
+    use super::NcElementId;
+
+    pub const PROPERTY_CHANGED: NcElementId = NcElementId::new(1, 1);
+}
+
+// Class ids of the standard classes
+// See https://specs.amwa.tv/ms-05-02/releases/v1.0.0/docs/Framework.html#ncclassid
+pub mod class_ids {
+    pub const NC_OBJECT: &[i32] = &[1];
+    pub const NC_BLOCK: &[i32] = &[1, 1];
+    pub const NC_WORKER: &[i32] = &[1, 2];
+    pub const NC_MANAGER: &[i32] = &[1, 3];
+    pub const NC_DEVICE_MANAGER: &[i32] = &[1, 3, 1];
+    pub const NC_CLASS_MANAGER: &[i32] = &[1, 3, 2];
+
+    // See https://specs.amwa.tv/nmos-control-feature-sets/branches/main/identification/#ncidentbeacon
+    pub const NC_IDENT_BEACON: &[i32] = &[1, 2, 2];
+
+    // BCP-008 status monitoring classes
+    // See https://specs.amwa.tv/bcp-008-01/releases/v1.0.0/docs/Overview.html
+    pub const NC_STATUS_MONITOR: &[i32] = &[1, 2, 3];
+    pub const NC_RECEIVER_MONITOR: &[i32] = &[1, 2, 3, 1];
+    // See https://specs.amwa.tv/bcp-008-02/releases/v1.0.0/docs/Overview.html
+    pub const NC_SENDER_MONITOR: &[i32] = &[1, 2, 3, 2];
+}
+
+// Define the method result status codes
+// See https://specs.amwa.tv/ms-05-02/releases/v1.0.0/docs/Framework.html#ncmethodstatus
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NcMethodStatus {
+    Ok = 200,
+    PropertyDeprecated = 298,
+    MethodDeprecated = 299,
+    BadCommandFormat = 400,
+    Unauthorized = 401,
+    BadOid = 404,
+    Readonly = 405,
+    InvalidRequest = 406,
+    Conflict = 409,
+    BufferOverflow = 413,
+    IndexOutOfBounds = 414,
+    ParameterError = 417,
+    Locked = 423,
+    DeviceError = 500,
+    MethodNotImplemented = 501,
+    PropertyNotImplemented = 502,
+    NotReady = 503,
+    Timeout = 504,
+}
+
+// Define the result of a method invocation, i.e. NcMethodResult or one of its subtypes
+#[derive(Debug, Clone, PartialEq)]
+pub struct NcMethodResult {
+    pub status: NcMethodStatus,
+    pub value: Option<Value>,
+    pub error_message: Option<String>,
+}
+
+impl NcMethodResult {
+    pub fn ok() -> Self {
+        NcMethodResult {
+            status: NcMethodStatus::Ok,
+            value: None,
+            error_message: None,
+        }
+    }
+
+    pub fn ok_with(value: Value) -> Self {
+        NcMethodResult {
+            status: NcMethodStatus::Ok,
+            value: Some(value),
+            error_message: None,
+        }
+    }
+
+    pub fn error(status: NcMethodStatus, error_message: impl Into<String>) -> Self {
+        NcMethodResult {
+            status,
+            value: None,
+            error_message: Some(error_message.into()),
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        matches!(
+            self.status,
+            NcMethodStatus::Ok
+                | NcMethodStatus::PropertyDeprecated
+                | NcMethodStatus::MethodDeprecated
+        )
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut result = json!({ "status": self.status as u16 });
+        if let Some(value) = &self.value {
+            result["value"] = value.clone();
+        }
+        if let Some(error_message) = &self.error_message {
+            result["errorMessage"] = json!(error_message);
+        }
+        result
+    }
+}
+
+// Define the kind of change reported by a property changed event
+// See https://specs.amwa.tv/ms-05-02/releases/v1.0.0/docs/Framework.html#ncpropertychangetype
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NcPropertyChangeType {
+    ValueChanged = 0,
+    SequenceItemAdded = 1,
+    SequenceItemChanged = 2,
+    SequenceItemRemoved = 3,
+}
+
+// Define a property changed event, i.e. an IS-12 notification
+#[derive(Debug, Clone, PartialEq)]
+pub struct NcPropertyChangedEvent {
+    pub oid: u32,
+    pub property_id: NcElementId,
+    pub change_type: NcPropertyChangeType,
+    pub value: Value,
+    pub sequence_item_index: Option<usize>,
+}
+
+impl NcPropertyChangedEvent {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "oid": self.oid,
+            "eventId": event_ids::PROPERTY_CHANGED.to_json(),
+            "eventData": {
+                "propertyId": self.property_id.to_json(),
+                "changeType": self.change_type as u8,
+                "value": self.value,
+                "sequenceItemIndex": self.sequence_item_index,
+            }
+        })
+    }
+}
+
+// Define a property value, and whether it may be modified by a controller
+#[derive(Debug, Clone, PartialEq)]
+pub struct NcProperty {
+    pub value: Value,
+    pub read_only: bool,
+}
+
+// Define an object in the control model, holding the values of all the properties of its class
+#[derive(Debug, Clone, PartialEq)]
+pub struct NcObject {
+    pub oid: u32,
+    pub class_id: Vec<i32>,
+    pub properties: BTreeMap<NcElementId, NcProperty>,
+    // Counters which are reported by methods rather than properties, e.g. the lost packet
+    // counters of a receiver monitor, keyed by counter set and then counter name
+    pub counters: BTreeMap<String, BTreeMap<String, u64>>,
+    // Property changes which are only made once they are due, unless they are replaced first, e.g.
+    // the improvements in the statuses of a receiver monitor, with the time each is due
+    pub delayed_properties: BTreeMap<NcElementId, (Value, Instant)>,
+}
+
+impl NcObject {
+    // Create an object with the NcObject properties, owned by the block with the specified oid
+    // (or unowned, for the root block)
+    pub fn new(
+        oid: u32,
+        class_id: &[i32],
+        role: &str,
+        owner: Option<u32>,
+        user_label: Option<&str>,
+    ) -> Self {
+        NcObject {
+            oid,
+            class_id: class_id.to_vec(),
+            properties: BTreeMap::new(),
+            counters: BTreeMap::new(),
+            delayed_properties: BTreeMap::new(),
+        }
+        .with_property(property_ids::CLASS_ID, json!(class_id), true)
+        .with_property(property_ids::OID, json!(oid), true)
+        .with_property(property_ids::CONSTANT_OID, json!(true), true)
+        .with_property(property_ids::OWNER, json!(owner), true)
+        .with_property(property_ids::ROLE, json!(role), true)
+        .with_property(property_ids::USER_LABEL, json!(user_label), false)
+        .with_property(property_ids::TOUCHPOINTS, Value::Null, true)
+        .with_property(
+            property_ids::RUNTIME_PROPERTY_CONSTRAINTS,
+            Value::Null,
+            true,
+        )
+    }
+
+    // Add (or replace) a property, e.g. one defined by a derived class
+    pub fn with_property(mut self, id: NcElementId, value: Value, read_only: bool) -> Self {
+        self.properties.insert(id, NcProperty { value, read_only });
+        self
+    }
+
+    pub fn property(&self, id: NcElementId) -> Option<&Value> {
+        self.properties.get(&id).map(|property| &property.value)
+    }
+
+    pub fn role(&self) -> &str {
+        self.property(property_ids::ROLE)
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+    }
+
+    pub fn owner(&self) -> Option<u32> {
+        self.property(property_ids::OWNER)
+            .and_then(Value::as_u64)
+            .and_then(|owner| u32::try_from(owner).ok())
+    }
+
+    pub fn user_label(&self) -> Option<&str> {
+        self.property(property_ids::USER_LABEL)
+            .and_then(Value::as_str)
+    }
+
+    // The oids of the members of a block, in the order of its member descriptors
+    pub fn member_oids(&self) -> Vec<u32> {
+        self.property(property_ids::BLOCK_MEMBERS)
+            .and_then(Value::as_array)
+            .map(|members| {
+                members
+                    .iter()
+                    .filter_map(|member| member.get("oid").and_then(Value::as_u64))
+                    .filter_map(|oid| u32::try_from(oid).ok())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    // Check whether this object is an instance of the specified class, or a class derived from it
+    pub fn is_instance_of(&self, class_id: &[i32]) -> bool {
+        self.class_id.starts_with(class_id)
+    }
+
+    // Map to the resource type of the most-derived standard class of this object
+    pub fn resource_type(&self) -> ResourceType {
+        let standard_classes = [
+            (
+                class_ids::NC_RECEIVER_MONITOR,
+                ResourceType::NcReceieverMonitor,
+            ),
+            (class_ids::NC_SENDER_MONITOR, ResourceType::NcSenderMonitor),
+            (class_ids::NC_IDENT_BEACON, ResourceType::NcIdentBeacon),
+            (class_ids::NC_CLASS_MANAGER, ResourceType::NcClassManager),
+            (class_ids::NC_DEVICE_MANAGER, ResourceType::NcDeviceManager),
+            (class_ids::NC_MANAGER, ResourceType::NcManager),
+            (class_ids::NC_BLOCK, ResourceType::NcBlock),
+        ];
+        standard_classes
+            .into_iter()
+            .find(|(class_id, _)| self.is_instance_of(class_id))
+            .map(|(_, resource_type)| resource_type)
+            .unwrap_or(ResourceType::NcWorker)
+    }
+}
+
+// Define the handler for a method, invoked with the object's oid and the method arguments
+pub type MethodHandler =
+    Arc<dyn Fn(&mut ControlProtocolState, u32, &Value) -> NcMethodResult + Send + Sync>;
+
+// Define the handler for controllers setting a property of a class, e.g. to validate the value,
+// which is invoked with the object's oid, the property id and the new value
+pub type PropertySetter = Arc<
+    dyn Fn(&mut ControlProtocolState, u32, NcElementId, Value) -> Result<(), NcMethodResult>
+        + Send
+        + Sync,
+>;
+
+// Define the control model state shared by the IS-12 WebSocket connections and the device
+// implementation
+pub struct ControlProtocolState {
+    objects: BTreeMap<u32, NcObject>,
+    // Methods are registered against the class that defines them, i.e. the class at the level of
+    // the method id, not counting authority keys
+    methods: HashMap<(Vec<i32>, NcElementId), MethodHandler>,
+    // Setters are registered against the class that defines the property, like methods
+    setters: HashMap<(Vec<i32>, NcElementId), PropertySetter>,
+    classes: ClassRegistry,
+    notifications: broadcast::Sender<NcPropertyChangedEvent>,
+    // Notified when a delayed property change is added to any object
+    delayed_properties_changed: Arc<Notify>,
+}
+
+impl Default for ControlProtocolState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ControlProtocolState {
+    pub fn new() -> Self {
+        let (notifications, _) = broadcast::channel(1024);
+        let mut state = ControlProtocolState {
+            objects: BTreeMap::new(),
+            methods: HashMap::new(),
+            setters: HashMap::new(),
+            classes: ClassRegistry::standard(),
+            notifications,
+            delayed_properties_changed: Arc::new(Notify::new()),
+        };
+        add_object_methods(&mut state);
+        add_block_methods(&mut state);
+        add_class_manager_methods(&mut state);
+        add_receiver_monitor_methods(&mut state);
+        add_sender_monitor_methods(&mut state);
+        add_ident_beacon_setters(&mut state);
+        state
+    }
+
+    pub fn insert(&mut self, object: NcObject) {
+        self.objects.insert(object.oid, object);
+    }
+
+    pub fn remove(&mut self, oid: u32) -> Option<NcObject> {
+        self.objects.remove(&oid)
+    }
+
+    pub fn get(&self, oid: u32) -> Option<&NcObject> {
+        self.objects.get(&oid)
+    }
+
+    // Direct access to an object, e.g. to update its counters, bypassing property change
+    // notifications
+    pub fn get_mut(&mut self, oid: u32) -> Option<&mut NcObject> {
+        self.objects.get_mut(&oid)
+    }
+
+    pub fn objects(&self) -> impl Iterator<Item = &NcObject> {
+        self.objects.values()
+    }
+
+    // Allocate the oid for the next object, so that oids are stable as long as the control model
+    // is always declared in the same order
+    pub fn next_oid(&self) -> u32 {
+        self.objects.keys().next_back().map_or(1, |oid| oid + 1)
+    }
+
+    // Add an object as a member of the block identified by its owner property, returning its oid
+    pub fn add_member(&mut self, object: NcObject) -> Result<u32, NcMethodResult> {
+        let oid = object.oid;
+        if self.objects.contains_key(&oid) {
+            return Err(NcMethodResult::error(
+                NcMethodStatus::Conflict,
+                format!("oid {} is already in use", oid),
+            ));
+        }
+
+        let Some(owner) = object.owner() else {
+            return Err(NcMethodResult::error(
+                NcMethodStatus::InvalidRequest,
+                "only the root block may have no owner",
+            ));
+        };
+        let block = match self.objects.get(&owner) {
+            Some(block) if block.is_instance_of(class_ids::NC_BLOCK) => block,
+            _ => {
+                return Err(NcMethodResult::error(
+                    NcMethodStatus::BadOid,
+                    format!("no block with oid: {}", owner),
+                ))
+            }
+        };
+
+        // Role must be unique within the owner block
+        // See https://specs.amwa.tv/ms-05-02/releases/v1.0.0/docs/Blocks.html
+        if block
+            .member_oids()
+            .into_iter()
+            .filter_map(|member| self.objects.get(&member))
+            .any(|member| member.role() == object.role())
+        {
+            return Err(NcMethodResult::error(
+                NcMethodStatus::Conflict,
+                format!(
+                    "role {} is already in use in block {}",
+                    object.role(),
+                    owner
+                ),
+            ));
+        }
+
+        let descriptor = make_member_descriptor(&object);
+        self.insert(object);
+        if let Some(Value::Array(members)) = self
+            .objects
+            .get_mut(&owner)
+            .and_then(|block| block.properties.get_mut(&property_ids::BLOCK_MEMBERS))
+            .map(|property| &mut property.value)
+        {
+            members.push(descriptor);
+        }
+        Ok(oid)
+    }
+
+    // The members of the specified block
+    pub fn members(&self, block_oid: u32) -> Vec<&NcObject> {
+        self.objects
+            .get(&block_oid)
+            .map(|block| {
+                block
+                    .member_oids()
+                    .into_iter()
+                    .filter_map(|oid| self.objects.get(&oid))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    // The role path of the specified object, i.e. the roles of each of its owners, starting at
+    // the root block, followed by its own role
+    pub fn role_path(&self, oid: u32) -> Vec<String> {
+        let mut role_path = Vec::new();
+        let mut next = self.objects.get(&oid);
+        while let Some(object) = next {
+            role_path.push(object.role().to_string());
+            next = object.owner().and_then(|owner| self.objects.get(&owner));
+        }
+        role_path.reverse();
+        role_path
+    }
+
+    // Find an object by its role path, e.g. ["root", "receivers", "monitor-01"]
+    pub fn find_role_path<S: AsRef<str>>(&self, role_path: &[S]) -> Option<&NcObject> {
+        let (root_role, rest) = role_path.split_first()?;
+        let mut object = self
+            .objects
+            .values()
+            .find(|object| object.owner().is_none() && object.role() == root_role.as_ref())?;
+        for role in rest {
+            object = self
+                .members(object.oid)
+                .into_iter()
+                .find(|member| member.role() == role.as_ref())?;
+        }
+        Some(object)
+    }
+
+    // Register the handler for a method defined by the specified class
+    pub fn add_method(&mut self, class_id: &[i32], method_id: NcElementId, handler: MethodHandler) {
+        self.methods.insert((class_id.to_vec(), method_id), handler);
+    }
+
+    // Register the handler for controllers setting a property defined by the specified class,
+    // instead of just storing the value
+    pub fn add_property_setter(
+        &mut self,
+        class_id: &[i32],
+        property_id: NcElementId,
+        setter: PropertySetter,
+    ) {
+        self.setters
+            .insert((class_id.to_vec(), property_id), setter);
+    }
+
+    pub fn class_registry(&self) -> &ClassRegistry {
+        &self.classes
+    }
+
+    // Register a vendor-specific class, and publish it via the class manager
+    pub fn register_class(&mut self, descriptor: Value) -> Result<(), NcMethodResult> {
+        self.classes.register_class(descriptor)?;
+        self.update_class_manager();
+        Ok(())
+    }
+
+    // Register a vendor-specific datatype, and publish it via the class manager
+    pub fn register_datatype(&mut self, descriptor: Value) -> Result<(), NcMethodResult> {
+        self.classes.register_datatype(descriptor)?;
+        self.update_class_manager();
+        Ok(())
+    }
+
+    fn update_class_manager(&mut self) {
+        let class_managers: Vec<u32> = self
+            .objects
+            .values()
+            .filter(|object| object.is_instance_of(class_ids::NC_CLASS_MANAGER))
+            .map(|object| object.oid)
+            .collect();
+        let control_classes = json!(self.classes.control_classes());
+        let datatypes = json!(self.classes.datatypes());
+        for oid in class_managers {
+            let _ = self.set_property(
+                oid,
+                property_ids::CLASS_MANAGER_CONTROL_CLASSES,
+                control_classes.clone(),
+            );
+            let _ = self.set_property(
+                oid,
+                property_ids::CLASS_MANAGER_DATATYPES,
+                datatypes.clone(),
+            );
+        }
+    }
+
+    // Receive a property changed event for every change to any object
+    pub fn subscribe(&self) -> broadcast::Receiver<NcPropertyChangedEvent> {
+        self.notifications.subscribe()
+    }
+
+    // Wait for a delayed property change to be added, e.g. to schedule when it is made
+    pub fn delayed_properties_changed(&self) -> Arc<Notify> {
+        self.delayed_properties_changed.clone()
+    }
+
+    fn notify(&self, event: NcPropertyChangedEvent) {
+        // an error only means there are currently no subscribers
+        let _ = self.notifications.send(event);
+    }
+
+    // Notify subscribers of the current value of a property, even though it hasn't changed, e.g.
+    // when setting it again has an effect of its own
+    pub fn notify_property(&self, oid: u32, id: NcElementId) -> Result<(), NcMethodResult> {
+        let value = self.get_property(oid, id)?.clone();
+        self.notify(NcPropertyChangedEvent {
+            oid,
+            property_id: id,
+            change_type: NcPropertyChangeType::ValueChanged,
+            value,
+            sequence_item_index: None,
+        });
+        Ok(())
+    }
+
+    // Set a property value on behalf of a controller, using the setter of the class that defines
+    // the property, if it has one
+    fn set_property_by_controller(
+        &mut self,
+        oid: u32,
+        id: NcElementId,
+        value: Value,
+    ) -> Result<(), NcMethodResult> {
+        self.writable_property_mut(oid, id)?;
+        let setter = self
+            .objects
+            .get(&oid)
+            .and_then(|object| class_id_at_level(&object.class_id, id.level))
+            .and_then(|class_id| self.setters.get(&(class_id.to_vec(), id)))
+            .cloned();
+        match setter {
+            Some(setter) => setter(self, oid, id, value),
+            None => self.set_property(oid, id, value),
+        }
+    }
+
+    // Invoke a method on the specified object
+    pub fn invoke(
+        &mut self,
+        oid: u32,
+        method_id: NcElementId,
+        arguments: &Value,
+    ) -> NcMethodResult {
+        let Some(object) = self.objects.get(&oid) else {
+            return NcMethodResult::error(
+                NcMethodStatus::BadOid,
+                format!("no object with oid: {}", oid),
+            );
+        };
+
+        let handler = class_id_at_level(&object.class_id, method_id.level)
+            .and_then(|class_id| self.methods.get(&(class_id.to_vec(), method_id)))
+            .cloned();
+        let Some(handler) = handler else {
+            return NcMethodResult::error(
+                NcMethodStatus::MethodNotImplemented,
+                format!(
+                    "method {}m{} not implemented",
+                    method_id.level, method_id.index
+                ),
+            );
+        };
+
+        handler(self, oid, arguments)
+    }
+
+    // Get a property value
+    pub fn get_property(&self, oid: u32, id: NcElementId) -> Result<&Value, NcMethodResult> {
+        let object = self.objects.get(&oid).ok_or_else(|| {
+            NcMethodResult::error(
+                NcMethodStatus::BadOid,
+                format!("no object with oid: {}", oid),
+            )
+        })?;
+        object.property(id).ok_or_else(|| {
+            NcMethodResult::error(
+                NcMethodStatus::PropertyNotImplemented,
+                format!("property {} not implemented", id),
+            )
+        })
+    }
+
+    // Set a property value on behalf of the device, i.e. even if it is read-only for controllers,
+    // notifying subscribers if the value changed
+    pub fn set_property(
+        &mut self,
+        oid: u32,
+        id: NcElementId,
+        value: Value,
+    ) -> Result<(), NcMethodResult> {
+        let property = self.property_mut(oid, id)?;
+        if property.value == value {
+            return Ok(());
+        }
+        property.value = value.clone();
+
+        // Keep the owner block's member descriptor consistent with the object
+        if id == property_ids::USER_LABEL {
+            self.update_member_descriptor(oid);
+        }
+
+        self.notify(NcPropertyChangedEvent {
+            oid,
+            property_id: id,
+            change_type: NcPropertyChangeType::ValueChanged,
+            value,
+            sequence_item_index: None,
+        });
+        Ok(())
+    }
+
+    fn update_member_descriptor(&mut self, oid: u32) {
+        let Some(object) = self.objects.get(&oid) else {
+            return;
+        };
+        let descriptor = make_member_descriptor(object);
+        let Some(owner) = object.owner() else {
+            return;
+        };
+        if let Some(Value::Array(members)) = self
+            .objects
+            .get_mut(&owner)
+            .and_then(|block| block.properties.get_mut(&property_ids::BLOCK_MEMBERS))
+            .map(|property| &mut property.value)
+        {
+            if let Some(member) = members.iter_mut().find(|member| member["oid"] == oid) {
+                *member = descriptor;
+            }
+        }
+    }
+
+    fn property_mut(
+        &mut self,
+        oid: u32,
+        id: NcElementId,
+    ) -> Result<&mut NcProperty, NcMethodResult> {
+        let object = self.objects.get_mut(&oid).ok_or_else(|| {
+            NcMethodResult::error(
+                NcMethodStatus::BadOid,
+                format!("no object with oid: {}", oid),
+            )
+        })?;
+        object.properties.get_mut(&id).ok_or_else(|| {
+            NcMethodResult::error(
+                NcMethodStatus::PropertyNotImplemented,
+                format!("property {} not implemented", id),
+            )
+        })
+    }
+
+    // Look up a property that a controller is allowed to modify
+    fn writable_property_mut(
+        &mut self,
+        oid: u32,
+        id: NcElementId,
+    ) -> Result<&mut NcProperty, NcMethodResult> {
+        let property = self.property_mut(oid, id)?;
+        if property.read_only {
+            return Err(NcMethodResult::error(
+                NcMethodStatus::Readonly,
+                format!("property {} is read-only", id),
+            ));
+        }
+        Ok(property)
+    }
+
+    fn sequence_mut(
+        &mut self,
+        oid: u32,
+        id: NcElementId,
+    ) -> Result<&mut Vec<Value>, NcMethodResult> {
+        self.writable_property_mut(oid, id)?
+            .value
+            .as_array_mut()
+            .ok_or_else(|| {
+                NcMethodResult::error(
+                    NcMethodStatus::InvalidRequest,
+                    format!("property {} is not a sequence", id),
+                )
+            })
+    }
+}
+
+// Extract the property id argument common to the NcObject methods
+pub(crate) fn property_id_argument(arguments: &Value) -> Result<NcElementId, NcMethodResult> {
+    arguments
+        .get("id")
+        .and_then(NcElementId::from_json)
+        .ok_or_else(|| {
+            NcMethodResult::error(
+                NcMethodStatus::ParameterError,
+                "missing or invalid id argument",
+            )
+        })
+}
+
+pub(crate) fn index_argument(arguments: &Value) -> Result<usize, NcMethodResult> {
+    arguments
+        .get("index")
+        .and_then(Value::as_u64)
+        .and_then(|index| usize::try_from(index).ok())
+        .ok_or_else(|| {
+            NcMethodResult::error(
+                NcMethodStatus::ParameterError,
+                "missing or invalid index argument",
+            )
+        })
+}
+
+pub(crate) fn value_argument(arguments: &Value) -> Result<Value, NcMethodResult> {
+    arguments.get("value").cloned().ok_or_else(|| {
+        NcMethodResult::error(NcMethodStatus::ParameterError, "missing value argument")
+    })
+}
+
+fn index_out_of_bounds(index: usize) -> NcMethodResult {
+    NcMethodResult::error(
+        NcMethodStatus::IndexOutOfBounds,
+        format!("index {} is out of bounds", index),
+    )
+}
+
+// Register the NcObject methods, which apply to every object
+fn add_object_methods(state: &mut ControlProtocolState) {
+    state.add_method(
+        class_ids::NC_OBJECT,
+        method_ids::GET,
+        Arc::new(|state, oid, arguments| {
+            let id = match property_id_argument(arguments) {
+                Ok(id) => id,
+                Err(result) => return result,
+            };
+            match state.get_property(oid, id) {
+                Ok(value) => NcMethodResult::ok_with(value.clone()),
+                Err(result) => result,
+            }
+        }),
+    );
+
+    state.add_method(
+        class_ids::NC_OBJECT,
+        method_ids::SET,
+        Arc::new(|state, oid, arguments| {
+            let result = property_id_argument(arguments).and_then(|id| {
+                let value = value_argument(arguments)?;
+                state.set_property_by_controller(oid, id, value)
+            });
+            result.err().unwrap_or_else(NcMethodResult::ok)
+        }),
+    );
+
+    state.add_method(
+        class_ids::NC_OBJECT,
+        method_ids::GET_SEQUENCE_ITEM,
+        Arc::new(|state, oid, arguments| {
+            let result = property_id_argument(arguments).and_then(|id| {
+                let index = index_argument(arguments)?;
+                let sequence = state.get_property(oid, id)?.as_array().ok_or_else(|| {
+                    NcMethodResult::error(
+                        NcMethodStatus::InvalidRequest,
+                        format!("property {} is not a sequence", id),
+                    )
+                })?;
+                let item = sequence
+                    .get(index)
+                    .ok_or_else(|| index_out_of_bounds(index))?;
+                Ok(NcMethodResult::ok_with(item.clone()))
+            });
+            result.unwrap_or_else(|result| result)
+        }),
+    );
+
+    state.add_method(
+        class_ids::NC_OBJECT,
+        method_ids::SET_SEQUENCE_ITEM,
+        Arc::new(|state, oid, arguments| {
+            let result = property_id_argument(arguments).and_then(|id| {
+                let index = index_argument(arguments)?;
+                let value = value_argument(arguments)?;
+                let sequence = state.sequence_mut(oid, id)?;
+                let item = sequence
+                    .get_mut(index)
+                    .ok_or_else(|| index_out_of_bounds(index))?;
+                if *item != value {
+                    *item = value.clone();
+                    state.notify(NcPropertyChangedEvent {
+                        oid,
+                        property_id: id,
+                        change_type: NcPropertyChangeType::SequenceItemChanged,
+                        value,
+                        sequence_item_index: Some(index),
+                    });
+                }
+                Ok(NcMethodResult::ok())
+            });
+            result.unwrap_or_else(|result| result)
+        }),
+    );
+
+    state.add_method(
+        class_ids::NC_OBJECT,
+        method_ids::ADD_SEQUENCE_ITEM,
+        Arc::new(|state, oid, arguments| {
+            let result = property_id_argument(arguments).and_then(|id| {
+                let value = value_argument(arguments)?;
+                let sequence = state.sequence_mut(oid, id)?;
+                let index = sequence.len();
+                sequence.push(value.clone());
+                state.notify(NcPropertyChangedEvent {
+                    oid,
+                    property_id: id,
+                    change_type: NcPropertyChangeType::SequenceItemAdded,
+                    value,
+                    sequence_item_index: Some(index),
+                });
+                Ok(NcMethodResult::ok_with(json!(index)))
+            });
+            result.unwrap_or_else(|result| result)
+        }),
+    );
+
+    state.add_method(
+        class_ids::NC_OBJECT,
+        method_ids::REMOVE_SEQUENCE_ITEM,
+        Arc::new(|state, oid, arguments| {
+            let result = property_id_argument(arguments).and_then(|id| {
+                let index = index_argument(arguments)?;
+                let sequence = state.sequence_mut(oid, id)?;
+                if index >= sequence.len() {
+                    return Err(index_out_of_bounds(index));
+                }
+                sequence.remove(index);
+                state.notify(NcPropertyChangedEvent {
+                    oid,
+                    property_id: id,
+                    change_type: NcPropertyChangeType::SequenceItemRemoved,
+                    value: Value::Null,
+                    sequence_item_index: Some(index),
+                });
+                Ok(NcMethodResult::ok())
+            });
+            result.unwrap_or_else(|result| result)
+        }),
+    );
+
+    state.add_method(
+        class_ids::NC_OBJECT,
+        method_ids::GET_SEQUENCE_LENGTH,
+        Arc::new(|state, oid, arguments| {
+            let result = property_id_argument(arguments).and_then(|id| {
+                let value = state.get_property(oid, id)?;
+                match value {
+                    // a null sequence has no length
+                    Value::Null => Ok(NcMethodResult::ok_with(Value::Null)),
+                    Value::Array(sequence) => Ok(NcMethodResult::ok_with(json!(sequence.len()))),
+                    _ => Err(NcMethodResult::error(
+                        NcMethodStatus::InvalidRequest,
+                        format!("property {} is not a sequence", id),
+                    )),
+                }
+            });
+            result.unwrap_or_else(|result| result)
+        }),
+    );
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAIN: NcElementId = NcElementId::new(3, 1);
+    const CHANNELS: NcElementId = NcElementId::new(3, 2);
+
+    fn make_state() -> ControlProtocolState {
+        let mut state = ControlProtocolState::new();
+        state.insert(NcObject::new(
+            1,
+            class_ids::NC_BLOCK,
+            "root",
+            None,
+            Some("Root"),
+        ));
+        state.insert(
+            NcObject::new(2, &[1, 2, 0, 1], "gain", Some(1), None)
+                .with_property(GAIN, json!(0.0), false)
+                .with_property(CHANNELS, json!(["L", "R"]), false),
+        );
+        state
+    }
+
+    #[test]
+    fn test_element_id() {
+        assert_eq!(NcElementId::new(1, 1).to_string(), "1p1");
+        assert_eq!(
+            NcElementId::parse_property_id("3p12"),
+            Some(NcElementId::new(3, 12))
+        );
+        assert_eq!(NcElementId::parse_property_id("3m1"), None);
+        assert_eq!(
+            NcElementId::from_json(&json!({ "level": 2, "index": 1 })),
+            Some(NcElementId::new(2, 1))
+        );
+    }
+
+    #[test]
+    fn test_get() {
+        let mut state = make_state();
+        let result = state.invoke(
+            1,
+            method_ids::GET,
+            &json!({ "id": { "level": 1, "index": 6 } }),
+        );
+        assert_eq!(result, NcMethodResult::ok_with(json!("Root")));
+
+        let result = state.invoke(
+            42,
+            method_ids::GET,
+            &json!({ "id": { "level": 1, "index": 1 } }),
+        );
+        assert_eq!(result.status, NcMethodStatus::BadOid);
+
+        let result = state.invoke(
+            1,
+            method_ids::GET,
+            &json!({ "id": { "level": 9, "index": 9 } }),
+        );
+        assert_eq!(result.status, NcMethodStatus::PropertyNotImplemented);
+    }
+
+    #[test]
+    fn test_set() {
+        let mut state = make_state();
+        let mut notifications = state.subscribe();
+
+        let result = state.invoke(
+            2,
+            method_ids::SET,
+            &json!({ "id": GAIN.to_json(), "value": -6.0 }),
+        );
+        assert!(result.is_ok());
+        assert_eq!(state.get_property(2, GAIN).unwrap(), &json!(-6.0));
+
+        let event = notifications.try_recv().unwrap();
+        assert_eq!(event.oid, 2);
+        assert_eq!(event.property_id, GAIN);
+        assert_eq!(event.value, json!(-6.0));
+
+        let result = state.invoke(
+            2,
+            method_ids::SET,
+            &json!({ "id": property_ids::ROLE.to_json(), "value": "x" }),
+        );
+        assert_eq!(result.status, NcMethodStatus::Readonly);
+        assert!(notifications.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_sequence_methods() {
+        let mut state = make_state();
+
+        let result = state.invoke(
+            2,
+            method_ids::ADD_SEQUENCE_ITEM,
+            &json!({ "id": CHANNELS.to_json(), "value": "C" }),
+        );
+        assert_eq!(result, NcMethodResult::ok_with(json!(2)));
+
+        let result = state.invoke(
+            2,
+            method_ids::GET_SEQUENCE_LENGTH,
+            &json!({ "id": CHANNELS.to_json() }),
+        );
+        assert_eq!(result, NcMethodResult::ok_with(json!(3)));
+
+        let result = state.invoke(
+            2,
+            method_ids::REMOVE_SEQUENCE_ITEM,
+            &json!({ "id": CHANNELS.to_json(), "index": 0 }),
+        );
+        assert!(result.is_ok());
+
+        let result = state.invoke(
+            2,
+            method_ids::GET_SEQUENCE_ITEM,
+            &json!({ "id": CHANNELS.to_json(), "index": 0 }),
+        );
+        assert_eq!(result, NcMethodResult::ok_with(json!("R")));
+
+        let result = state.invoke(
+            2,
+            method_ids::GET_SEQUENCE_ITEM,
+            &json!({ "id": CHANNELS.to_json(), "index": 5 }),
+        );
+        assert_eq!(result.status, NcMethodStatus::IndexOutOfBounds);
+    }
+
+    #[test]
+    fn test_method_not_implemented() {
+        let mut state = make_state();
+        let result = state.invoke(2, NcElementId::new(5, 1), &json!({}));
+        assert_eq!(result.status, NcMethodStatus::MethodNotImplemented);
+        let result = state.invoke(2, NcElementId::new(2, 1), &json!({}));
+        assert_eq!(result.status, NcMethodStatus::MethodNotImplemented);
+    }
+
+    #[test]
+    fn test_vendor_method() {
+        let mut state = make_state();
+        // A method defined by the vendor class [1, 2, 0, 1], whose level is 3, after NcObject and
+        // NcWorker, since the authority key isn't a level
+        let reset = NcElementId::new(3, 1);
+        state.add_method(
+            &[1, 2, 0, 1],
+            reset,
+            Arc::new(
+                |state, oid, _| match state.set_property(oid, GAIN, json!(0.0)) {
+                    Ok(()) => NcMethodResult::ok(),
+                    Err(result) => result,
+                },
+            ),
+        );
+        state.set_property(2, GAIN, json!(-6.0)).unwrap();
+        assert!(state.invoke(2, reset, &json!({})).is_ok());
+        assert_eq!(state.get_property(2, GAIN).unwrap(), &json!(0.0));
+
+        // Methods of the standard classes are still found by their own level
+        let result = state.invoke(2, method_ids::GET, &json!({ "id": GAIN.to_json() }));
+        assert_eq!(result, NcMethodResult::ok_with(json!(0.0)));
+        let result = state.invoke(2, NcElementId::new(4, 1), &json!({}));
+        assert_eq!(result.status, NcMethodStatus::MethodNotImplemented);
+    }
+}
@@ -0,0 +1,545 @@
+// Backup and restore of the properties of MS-05-02 objects, as exposed by the IS-14 bulkProperties
+// endpoints
+// See https://specs.amwa.tv/is-14/releases/v1.0.0/docs/Backup_and_restore.html
+
+use serde_json::{json, Value};
+
+use crate::control_protocol::{
+    class_ids, property_ids, ControlProtocolState, NcElementId, NcMethodResult, NcMethodStatus,
+    NcObject,
+};
+use crate::control_protocol_classes::{datatype_types, ClassRegistry};
+
+// Restore modes
+// See https://specs.amwa.tv/is-14/releases/v1.0.0/docs/Backup_and_restore.html#restore-modes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NcRestoreMode {
+    // Only the properties of existing objects are modified
+    Modify = 1,
+    // Rebuildable blocks may also have their members added or removed
+    Rebuild = 2,
+}
+
+impl NcRestoreMode {
+    // The restore mode is an enum value, i.e. a number, and defaults to Modify if omitted
+    pub fn from_json(value: &Value) -> Option<Self> {
+        match value {
+            Value::Null => Some(NcRestoreMode::Modify),
+            value => match value.as_u64() {
+                Some(1) => Some(NcRestoreMode::Modify),
+                Some(2) => Some(NcRestoreMode::Rebuild),
+                _ => None,
+            },
+        }
+    }
+}
+
+// Validation status of each object in a restore
+// See https://specs.amwa.tv/is-14/releases/v1.0.0/docs/Backup_and_restore.html#ncrestorevalidationstatus
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NcRestoreValidationStatus {
+    Ok = 200,
+    Failed = 400,
+    NotFound = 404,
+    DeviceError = 500,
+}
+
+// Severity of a notice about a property in a restore
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NcPropertyRestoreNoticeType {
+    Warning = 300,
+    Error = 400,
+}
+
+fn make_property_restore_notice(
+    id: &Value,
+    name: &Value,
+    notice_type: NcPropertyRestoreNoticeType,
+    notice_message: &str,
+) -> Value {
+    json!({
+        "id": id,
+        "name": name,
+        "noticeType": notice_type as u16,
+        "noticeMessage": notice_message,
+    })
+}
+
+fn make_object_properties_set_validation(
+    path: &[String],
+    status: NcRestoreValidationStatus,
+    notices: Vec<Value>,
+    status_message: Option<&str>,
+) -> Value {
+    json!({
+        "path": path,
+        "status": status as u16,
+        "notices": notices,
+        "statusMessage": status_message,
+    })
+}
+
+// The property descriptors of an object's class, including inherited properties
+// For a vendor-specific class which has not been registered, those of the nearest registered
+// ancestor class are used
+pub fn property_descriptors(registry: &ClassRegistry, object: &NcObject) -> Vec<Value> {
+    let mut class_id = object.class_id.clone();
+    while !class_id.is_empty() {
+        if let Ok(descriptor) = registry.control_class(&class_id, true) {
+            return descriptor["properties"]
+                .as_array()
+                .cloned()
+                .unwrap_or_default();
+        }
+        class_id.pop();
+    }
+    Vec::new()
+}
+
+fn find_descriptor(descriptors: &[Value], id: NcElementId) -> Option<&Value> {
+    descriptors
+        .iter()
+        .find(|descriptor| NcElementId::from_json(&descriptor["id"]) == Some(id))
+}
+
+// The object with the specified oid and, optionally, all the objects nested within it, in
+// depth-first order
+fn collect_objects(state: &ControlProtocolState, oid: u32, recurse: bool) -> Vec<&NcObject> {
+    let mut objects = Vec::new();
+    if let Some(object) = state.get(oid) {
+        objects.push(object);
+        if recurse && object.is_instance_of(class_ids::NC_BLOCK) {
+            for member in state.members(oid) {
+                objects.extend(collect_objects(state, member.oid, recurse));
+            }
+        }
+    }
+    objects
+}
+
+// Construct the backup dataset for an object, optionally including all the objects nested within
+// it, as an NcBulkValuesHolder
+// See https://specs.amwa.tv/is-14/releases/v1.0.0/docs/Backup_and_restore.html#ncbulkvaluesholder
+pub fn make_bulk_values_holder(state: &ControlProtocolState, oid: u32, recurse: bool) -> Value {
+    let values: Vec<Value> = collect_objects(state, oid, recurse)
+        .into_iter()
+        .map(|object| {
+            let descriptors = property_descriptors(state.class_registry(), object);
+            let values: Vec<Value> = object
+                .properties
+                .iter()
+                .map(|(id, property)| {
+                    let descriptor = find_descriptor(&descriptors, *id);
+                    json!({
+                        "id": id.to_json(),
+                        "name": descriptor.map_or(Value::Null, |d| d["name"].clone()),
+                        "typeName": descriptor.map_or(Value::Null, |d| d["typeName"].clone()),
+                        "isReadOnly": property.read_only,
+                        "value": property.value,
+                    })
+                })
+                .collect();
+            json!({
+                "path": state.role_path(object.oid),
+                "values": values,
+                "isRebuildable": false,
+            })
+        })
+        .collect();
+
+    json!({
+        "validationFingerprint": null,
+        "values": values,
+    })
+}
+
+// Check a value against the type of a property, resolving typedefs
+fn check_value(
+    registry: &ClassRegistry,
+    type_name: &str,
+    is_nullable: bool,
+    is_sequence: bool,
+    value: &Value,
+) -> Result<(), String> {
+    if value.is_null() {
+        return if is_nullable {
+            Ok(())
+        } else {
+            Err("value must not be null".to_string())
+        };
+    }
+    if is_sequence {
+        let items = value
+            .as_array()
+            .ok_or_else(|| "value must be a sequence".to_string())?;
+        return items
+            .iter()
+            .try_for_each(|item| check_value(registry, type_name, false, false, item));
+    }
+
+    let valid = match type_name {
+        // Any type
+        "" => true,
+        "NcBoolean" => value.is_boolean(),
+        "NcString" => value.is_string(),
+        "NcInt16" | "NcInt32" | "NcInt64" => value.is_i64(),
+        "NcUint16" | "NcUint32" | "NcUint64" => value.is_u64(),
+        "NcFloat32" | "NcFloat64" => value.is_number(),
+        _ => match registry.datatype(type_name, false) {
+            Ok(datatype) => match datatype["type"].as_u64().map(|t| t as u8) {
+                Some(datatype_types::TYPEDEF) => {
+                    let parent_type = datatype["parentType"].as_str().unwrap_or_default();
+                    let is_sequence = datatype["isSequence"].as_bool().unwrap_or(false);
+                    return check_value(registry, parent_type, false, is_sequence, value);
+                }
+                Some(datatype_types::ENUM) => datatype["items"]
+                    .as_array()
+                    .is_some_and(|items| items.iter().any(|item| item["value"] == *value)),
+                Some(datatype_types::STRUCT) => value.is_object(),
+                _ => true,
+            },
+            // Vendor-specific types which have not been registered cannot be checked
+            Err(_) => true,
+        },
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("value is not a valid {}", type_name))
+    }
+}
+
+// Validate the property values for one object, returning the notices and the values which would
+// be restored
+fn validate_object_properties(
+    state: &ControlProtocolState,
+    object: &NcObject,
+    values: &[Value],
+) -> (Vec<Value>, Vec<(NcElementId, Value)>) {
+    let descriptors = property_descriptors(state.class_registry(), object);
+    let mut notices = Vec::new();
+    let mut changes = Vec::new();
+
+    for holder in values {
+        let id_json = &holder["id"];
+        let name = &holder["name"];
+        let notice = |notice_type, message: &str| {
+            make_property_restore_notice(id_json, name, notice_type, message)
+        };
+
+        let Some(id) = NcElementId::from_json(id_json) else {
+            notices.push(notice(
+                NcPropertyRestoreNoticeType::Error,
+                "invalid property id",
+            ));
+            continue;
+        };
+        let Some(property) = object.properties.get(&id) else {
+            notices.push(notice(
+                NcPropertyRestoreNoticeType::Error,
+                &format!("property {} not implemented", id),
+            ));
+            continue;
+        };
+        let value = &holder["value"];
+
+        // Read-only properties are part of the backup for information only
+        if property.read_only {
+            if property.value != *value && id != property_ids::BLOCK_MEMBERS {
+                notices.push(notice(
+                    NcPropertyRestoreNoticeType::Warning,
+                    &format!("read-only property {} was not restored", id),
+                ));
+            }
+            continue;
+        }
+
+        if let Some(descriptor) = find_descriptor(&descriptors, id) {
+            let checked = check_value(
+                state.class_registry(),
+                descriptor["typeName"].as_str().unwrap_or_default(),
+                descriptor["isNullable"].as_bool().unwrap_or(false),
+                descriptor["isSequence"].as_bool().unwrap_or(false),
+                value,
+            );
+            if let Err(message) = checked {
+                notices.push(notice(
+                    NcPropertyRestoreNoticeType::Error,
+                    &format!("property {}: {}", id, message),
+                ));
+                continue;
+            }
+        }
+
+        if property.value != *value {
+            changes.push((id, value.clone()));
+        }
+    }
+
+    (notices, changes)
+}
+
+// Validate a backup dataset against the object with the specified oid and, optionally, all the
+// objects nested within it, and apply it if requested and every object is valid
+// The result is a sequence of NcObjectPropertiesSetValidation
+// See https://specs.amwa.tv/is-14/releases/v1.0.0/docs/Backup_and_restore.html#validating-a-restore
+pub fn set_bulk_values(
+    state: &mut ControlProtocolState,
+    oid: u32,
+    data_set: &Value,
+    recurse: bool,
+    restore_mode: NcRestoreMode,
+    apply: bool,
+) -> Result<Vec<Value>, NcMethodResult> {
+    let holders = data_set["values"].as_array().ok_or_else(|| {
+        NcMethodResult::error(
+            NcMethodStatus::ParameterError,
+            "dataSet must contain a sequence of values",
+        )
+    })?;
+    let target = state.role_path(oid);
+
+    let mut validations = Vec::new();
+    let mut all_changes = Vec::new();
+    for holder in holders {
+        let path: Vec<String> = serde_json::from_value(holder["path"].clone()).unwrap_or_default();
+
+        let in_scope = if recurse {
+            path.starts_with(&target)
+        } else {
+            path == target
+        };
+        if !in_scope {
+            validations.push(make_object_properties_set_validation(
+                &path,
+                NcRestoreValidationStatus::Failed,
+                vec![],
+                Some("object is outside the scope of the restore"),
+            ));
+            continue;
+        }
+
+        // No blocks in this device are rebuildable, so a dataset which expects one is not
+        // compatible with it, and otherwise Rebuild behaves like Modify
+        if restore_mode == NcRestoreMode::Rebuild && holder["isRebuildable"] == true {
+            validations.push(make_object_properties_set_validation(
+                &path,
+                NcRestoreValidationStatus::Failed,
+                vec![],
+                Some("object is not rebuildable"),
+            ));
+            continue;
+        }
+
+        let Some(object) = state.find_role_path(&path) else {
+            validations.push(make_object_properties_set_validation(
+                &path,
+                NcRestoreValidationStatus::NotFound,
+                vec![],
+                Some("object not found"),
+            ));
+            continue;
+        };
+
+        let values = holder["values"].as_array().cloned().unwrap_or_default();
+        let (notices, changes) = validate_object_properties(state, object, &values);
+        let failed = notices
+            .iter()
+            .any(|notice| notice["noticeType"] == NcPropertyRestoreNoticeType::Error as u16);
+        let status = if failed {
+            NcRestoreValidationStatus::Failed
+        } else {
+            NcRestoreValidationStatus::Ok
+        };
+        all_changes.push((object.oid, changes));
+        validations.push(make_object_properties_set_validation(
+            &path, status, notices, None,
+        ));
+    }
+
+    // Changes are only applied if the whole dataset is valid
+    let valid = validations
+        .iter()
+        .all(|validation| validation["status"] == NcRestoreValidationStatus::Ok as u16);
+    if apply && valid {
+        for (oid, changes) in all_changes {
+            for (id, value) in changes {
+                state.set_property(oid, id, value)?;
+            }
+        }
+    }
+
+    Ok(validations)
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control_protocol_resources::{make_block, make_root_block, ROOT_BLOCK_OID};
+
+    // root
+    // +- receivers (2)
+    //    +- IdentBeacon (3)
+    fn make_state() -> ControlProtocolState {
+        let mut state = ControlProtocolState::new();
+        state.insert(make_root_block(Some("Device")));
+        let receivers = state
+            .add_member(make_block(
+                state.next_oid(),
+                Some(ROOT_BLOCK_OID),
+                "receivers",
+                Some("Receivers"),
+            ))
+            .unwrap();
+        state
+            .add_member(
+                NcObject::new(
+                    state.next_oid(),
+                    class_ids::NC_IDENT_BEACON,
+                    "IdentBeacon",
+                    Some(receivers),
+                    None,
+                )
+                .with_property(
+                    property_ids::IDENT_BEACON_ACTIVE,
+                    json!(false),
+                    false,
+                ),
+            )
+            .unwrap();
+        state
+    }
+
+    #[test]
+    fn test_backup() {
+        let state = make_state();
+
+        let backup = make_bulk_values_holder(&state, ROOT_BLOCK_OID, true);
+        let values = backup["values"].as_array().unwrap();
+        assert_eq!(values.len(), 3);
+        assert_eq!(
+            values[2]["path"],
+            json!(["root", "receivers", "IdentBeacon"])
+        );
+        let user_label = values[0]["values"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|value| value["name"] == "userLabel")
+            .unwrap();
+        assert_eq!(user_label["value"], "Device");
+        assert_eq!(user_label["isReadOnly"], false);
+
+        let backup = make_bulk_values_holder(&state, ROOT_BLOCK_OID, false);
+        assert_eq!(backup["values"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_validate_and_restore() {
+        let mut source = make_state();
+        source
+            .set_property(3, property_ids::USER_LABEL, json!("Find me"))
+            .unwrap();
+        source
+            .set_property(3, property_ids::IDENT_BEACON_ACTIVE, json!(true))
+            .unwrap();
+        let backup = make_bulk_values_holder(&source, ROOT_BLOCK_OID, true);
+
+        assert_eq!(
+            NcRestoreMode::from_json(&json!(1)),
+            Some(NcRestoreMode::Modify)
+        );
+        assert_eq!(
+            NcRestoreMode::from_json(&json!(2)),
+            Some(NcRestoreMode::Rebuild)
+        );
+        assert_eq!(NcRestoreMode::from_json(&json!(0)), None);
+        assert_eq!(NcRestoreMode::from_json(&json!("Modify")), None);
+
+        let mut state = make_state();
+        let validations =
+            set_bulk_values(&mut state, 2, &backup, true, NcRestoreMode::Modify, false).unwrap();
+        // the root block is outside the scope of a restore to the receivers block
+        assert_eq!(validations[0]["status"], 400);
+        assert_eq!(validations[1]["status"], 200);
+        assert_eq!(validations[2]["status"], 200);
+
+        // nothing is applied unless every object is valid
+        let validations = set_bulk_values(
+            &mut state,
+            ROOT_BLOCK_OID,
+            &backup,
+            true,
+            NcRestoreMode::Modify,
+            true,
+        )
+        .unwrap();
+        assert!(validations.iter().all(|v| v["status"] == 200));
+        assert_eq!(
+            state.get_property(3, property_ids::USER_LABEL).unwrap(),
+            "Find me"
+        );
+        assert_eq!(
+            state
+                .get_property(3, property_ids::IDENT_BEACON_ACTIVE)
+                .unwrap(),
+            true
+        );
+    }
+
+    #[test]
+    fn test_validation_notices() {
+        let mut state = make_state();
+        let data_set = json!({
+            "validationFingerprint": null,
+            "values": [
+                {
+                    "path": ["root", "receivers", "IdentBeacon"],
+                    "values": [
+                        { "id": { "level": 3, "index": 1 }, "name": "active", "value": "yes" },
+                        { "id": { "level": 1, "index": 5 }, "name": "role", "value": "Beacon" },
+                        { "id": { "level": 9, "index": 1 }, "name": "missing", "value": 1 },
+                    ],
+                    "isRebuildable": false,
+                },
+                { "path": ["root", "senders"], "values": [], "isRebuildable": false },
+            ],
+        });
+
+        let validations = set_bulk_values(
+            &mut state,
+            ROOT_BLOCK_OID,
+            &data_set,
+            true,
+            NcRestoreMode::Modify,
+            true,
+        )
+        .unwrap();
+        assert_eq!(validations[0]["status"], 400);
+        let notice_types: Vec<&Value> = validations[0]["notices"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|notice| &notice["noticeType"])
+            .collect();
+        assert_eq!(notice_types, vec![400, 300, 400]);
+        assert_eq!(validations[1]["status"], 404);
+        assert_eq!(
+            state
+                .get_property(3, property_ids::IDENT_BEACON_ACTIVE)
+                .unwrap(),
+            false
+        );
+
+        let result = set_bulk_values(
+            &mut state,
+            ROOT_BLOCK_OID,
+            &json!({}),
+            true,
+            NcRestoreMode::Modify,
+            false,
+        );
+        assert_eq!(result.unwrap_err().status, NcMethodStatus::ParameterError);
+    }
+}
@@ -0,0 +1,1218 @@
+// Class and datatype descriptors for MS-05-02 control classes, as published by NcClassManager
+// See https://specs.amwa.tv/ms-05-02/releases/v1.0.0/docs/Managers.html#class-manager
+// and https://specs.amwa.tv/ms-05-02/releases/v1.0.0/docs/Framework.html#ncclassdescriptor
+
+use std::collections::BTreeMap;
+
+use serde_json::{json, Value};
+
+use crate::control_protocol::{class_ids, NcMethodResult, NcMethodStatus};
+
+// Datatype kinds
+// See https://specs.amwa.tv/ms-05-02/releases/v1.0.0/docs/Framework.html#ncdatatypetype
+pub mod datatype_types {
+    pub const PRIMITIVE: u8 = 0;
+    pub const TYPEDEF: u8 = 1;
+    pub const STRUCT: u8 = 2;
+    pub const ENUM: u8 = 3;
+}
+
+// Construct a property descriptor
+// See https://specs.amwa.tv/ms-05-02/releases/v1.0.0/docs/Framework.html#ncpropertydescriptor
+pub fn make_property_descriptor(
+    description: &str,
+    id: (u16, u16),
+    name: &str,
+    type_name: &str,
+    is_read_only: bool,
+    is_nullable: bool,
+    is_sequence: bool,
+) -> Value {
+    json!({
+        "description": description,
+        "id": { "level": id.0, "index": id.1 },
+        "name": name,
+        "typeName": type_name,
+        "isReadOnly": is_read_only,
+        "isNullable": is_nullable,
+        "isSequence": is_sequence,
+        "isDeprecated": false,
+        "constraints": null,
+    })
+}
+
+// Construct a method parameter descriptor
+// See https://specs.amwa.tv/ms-05-02/releases/v1.0.0/docs/Framework.html#ncparameterdescriptor
+pub fn make_parameter_descriptor(
+    description: &str,
+    name: &str,
+    type_name: &str,
+    is_nullable: bool,
+    is_sequence: bool,
+) -> Value {
+    json!({
+        "description": description,
+        "name": name,
+        "typeName": type_name,
+        "isNullable": is_nullable,
+        "isSequence": is_sequence,
+        "constraints": null,
+    })
+}
+
+// Construct a method descriptor
+// See https://specs.amwa.tv/ms-05-02/releases/v1.0.0/docs/Framework.html#ncmethoddescriptor
+pub fn make_method_descriptor(
+    description: &str,
+    id: (u16, u16),
+    name: &str,
+    result_datatype: &str,
+    parameters: Vec<Value>,
+) -> Value {
+    json!({
+        "description": description,
+        "id": { "level": id.0, "index": id.1 },
+        "name": name,
+        "resultDatatype": result_datatype,
+        "parameters": parameters,
+        "isDeprecated": false,
+    })
+}
+
+// Construct an event descriptor
+// See https://specs.amwa.tv/ms-05-02/releases/v1.0.0/docs/Framework.html#nceventdescriptor
+pub fn make_event_descriptor(
+    description: &str,
+    id: (u16, u16),
+    name: &str,
+    event_datatype: &str,
+) -> Value {
+    json!({
+        "description": description,
+        "id": { "level": id.0, "index": id.1 },
+        "name": name,
+        "eventDatatype": event_datatype,
+        "isDeprecated": false,
+    })
+}
+
+// Construct a class descriptor, listing only the elements the class itself defines
+pub fn make_class_descriptor(
+    description: &str,
+    class_id: &[i32],
+    name: &str,
+    fixed_role: Option<&str>,
+    properties: Vec<Value>,
+    methods: Vec<Value>,
+    events: Vec<Value>,
+) -> Value {
+    json!({
+        "description": description,
+        "classId": class_id,
+        "name": name,
+        "fixedRole": fixed_role,
+        "properties": properties,
+        "methods": methods,
+        "events": events,
+    })
+}
+
+// Construct a primitive datatype descriptor
+pub fn make_primitive_datatype(description: &str, name: &str) -> Value {
+    json!({
+        "description": description,
+        "name": name,
+        "type": datatype_types::PRIMITIVE,
+        "constraints": null,
+    })
+}
+
+// Construct a typedef datatype descriptor
+pub fn make_typedef_datatype(
+    description: &str,
+    name: &str,
+    parent_type: &str,
+    is_sequence: bool,
+) -> Value {
+    json!({
+        "description": description,
+        "name": name,
+        "type": datatype_types::TYPEDEF,
+        "parentType": parent_type,
+        "isSequence": is_sequence,
+        "constraints": null,
+    })
+}
+
+// Construct a struct field descriptor
+pub fn make_field_descriptor(
+    description: &str,
+    name: &str,
+    type_name: &str,
+    is_nullable: bool,
+    is_sequence: bool,
+) -> Value {
+    make_parameter_descriptor(description, name, type_name, is_nullable, is_sequence)
+}
+
+// Construct a struct datatype descriptor, listing only the fields the struct itself defines
+pub fn make_struct_datatype(
+    description: &str,
+    name: &str,
+    fields: Vec<Value>,
+    parent_type: Option<&str>,
+) -> Value {
+    json!({
+        "description": description,
+        "name": name,
+        "type": datatype_types::STRUCT,
+        "fields": fields,
+        "parentType": parent_type,
+        "constraints": null,
+    })
+}
+
+// Construct an enum item descriptor
+pub fn make_enum_item(description: &str, name: &str, value: u16) -> Value {
+    json!({
+        "description": description,
+        "name": name,
+        "value": value,
+    })
+}
+
+// Construct an enum datatype descriptor
+pub fn make_enum_datatype(description: &str, name: &str, items: Vec<Value>) -> Value {
+    json!({
+        "description": description,
+        "name": name,
+        "type": datatype_types::ENUM,
+        "items": items,
+        "constraints": null,
+    })
+}
+
+fn class_id_of(descriptor: &Value) -> Vec<i32> {
+    serde_json::from_value(descriptor["classId"].clone()).unwrap_or_default()
+}
+
+// The class id of the ancestor of a class at the specified level of the class hierarchy, which
+// is the level of the ids of the properties, methods and events defined by that class, e.g. level 3
+// of [1, 2, 0, 1] is the vendor class itself, since authority keys don't count as levels
+pub fn class_id_at_level(class_id: &[i32], level: u16) -> Option<&[i32]> {
+    if level == 0 {
+        return None;
+    }
+    let mut levels = 0;
+    let end = class_id.iter().position(|field| {
+        if *field > 0 {
+            levels += 1;
+        }
+        levels == level
+    })?;
+    Some(&class_id[..=end])
+}
+
+fn parameter_error(message: String) -> NcMethodResult {
+    NcMethodResult::error(NcMethodStatus::ParameterError, message)
+}
+
+// Define the registry of the control classes and datatypes supported by a device
+#[derive(Debug, Clone, Default)]
+pub struct ClassRegistry {
+    classes: BTreeMap<Vec<i32>, Value>,
+    datatypes: BTreeMap<String, Value>,
+}
+
+impl ClassRegistry {
+    // Create a registry with the standard MS-05-02 classes and datatypes
+    pub fn standard() -> Self {
+        let mut registry = ClassRegistry::default();
+        for descriptor in standard_class_descriptors() {
+            registry
+                .classes
+                .insert(class_id_of(&descriptor), descriptor);
+        }
+        for descriptor in standard_datatype_descriptors() {
+            let name = descriptor["name"].as_str().unwrap_or_default().to_string();
+            registry.datatypes.insert(name, descriptor);
+        }
+        registry
+    }
+
+    // Register a vendor-specific class
+    // Non-standard class ids are formed from the class id of a registered class, followed by an
+    // authority key (zero, or the negated organization id of the vendor) and then one or more
+    // positive vendor-defined fields, e.g. [1, 2, 0, 1] or [1, 2, -1234, 1, 1]
+    // See https://specs.amwa.tv/ms-05-02/releases/v1.0.0/docs/Framework.html#ncclassid
+    pub fn register_class(&mut self, descriptor: Value) -> Result<(), NcMethodResult> {
+        let class_id = class_id_of(&descriptor);
+        let Some(authority_key_index) = class_id.iter().position(|field| *field <= 0) else {
+            return Err(parameter_error(format!(
+                "class id {:?} must include an authority key",
+                class_id
+            )));
+        };
+        if authority_key_index == 0 || authority_key_index + 1 == class_id.len() {
+            return Err(parameter_error(format!("invalid class id {:?}", class_id)));
+        }
+        if class_id[authority_key_index + 1..]
+            .iter()
+            .any(|field| *field <= 0)
+        {
+            return Err(parameter_error(format!(
+                "class id {:?} must have a single authority key",
+                class_id
+            )));
+        }
+
+        // The parent class must already be registered, either the standard class preceding the
+        // authority key, or a vendor-specific class
+        let parent = if authority_key_index + 2 == class_id.len() {
+            &class_id[..authority_key_index]
+        } else {
+            &class_id[..class_id.len() - 1]
+        };
+        if !self.classes.contains_key(parent) {
+            return Err(parameter_error(format!(
+                "parent class {:?} of class id {:?} is not registered",
+                parent, class_id
+            )));
+        }
+        if self.classes.contains_key(&class_id) {
+            return Err(NcMethodResult::error(
+                NcMethodStatus::Conflict,
+                format!("class id {:?} is already registered", class_id),
+            ));
+        }
+
+        self.classes.insert(class_id, descriptor);
+        Ok(())
+    }
+
+    // Register a vendor-specific datatype
+    pub fn register_datatype(&mut self, descriptor: Value) -> Result<(), NcMethodResult> {
+        let name = descriptor["name"].as_str().unwrap_or_default().to_string();
+        if name.is_empty() {
+            return Err(parameter_error("datatype must have a name".to_string()));
+        }
+        if self.datatypes.contains_key(&name) {
+            return Err(NcMethodResult::error(
+                NcMethodStatus::Conflict,
+                format!("datatype {} is already registered", name),
+            ));
+        }
+        self.datatypes.insert(name, descriptor);
+        Ok(())
+    }
+
+    // The descriptors of all registered classes, without inherited elements
+    pub fn control_classes(&self) -> Vec<Value> {
+        self.classes.values().cloned().collect()
+    }
+
+    // The descriptors of all registered datatypes, without inherited fields
+    pub fn datatypes(&self) -> Vec<Value> {
+        self.datatypes.values().cloned().collect()
+    }
+
+    // The parent of a registered class, skipping over the authority key
+    fn parent_class_id(&self, class_id: &[i32]) -> Option<Vec<i32>> {
+        let mut parent = class_id[..class_id.len().checked_sub(1)?].to_vec();
+        while parent.last().is_some_and(|field| *field <= 0) {
+            parent.pop();
+        }
+        (!parent.is_empty()).then_some(parent)
+    }
+
+    // Get a class descriptor, optionally including the properties, methods and events of all
+    // the classes it inherits from
+    pub fn control_class(
+        &self,
+        class_id: &[i32],
+        include_inherited: bool,
+    ) -> Result<Value, NcMethodResult> {
+        let mut descriptor = self
+            .classes
+            .get(class_id)
+            .cloned()
+            .ok_or_else(|| parameter_error(format!("class id {:?} not found", class_id)))?;
+
+        if include_inherited {
+            let mut next = self.parent_class_id(class_id);
+            while let Some(parent_id) = next {
+                if let Some(parent) = self.classes.get(&parent_id) {
+                    for elements in ["properties", "methods", "events"] {
+                        if let (Some(inherited), Some(own)) = (
+                            parent[elements].as_array(),
+                            descriptor[elements].as_array_mut(),
+                        ) {
+                            own.extend(inherited.iter().cloned());
+                        }
+                    }
+                }
+                next = self.parent_class_id(&parent_id);
+            }
+        }
+        Ok(descriptor)
+    }
+
+    // Get a datatype descriptor, optionally including the fields of any parent struct types
+    pub fn datatype(&self, name: &str, include_inherited: bool) -> Result<Value, NcMethodResult> {
+        let mut descriptor = self
+            .datatypes
+            .get(name)
+            .cloned()
+            .ok_or_else(|| parameter_error(format!("datatype {} not found", name)))?;
+
+        if include_inherited && descriptor["type"] == datatype_types::STRUCT {
+            let mut next = descriptor["parentType"].as_str().map(str::to_string);
+            while let Some(parent_name) = next {
+                let Some(parent) = self.datatypes.get(&parent_name) else {
+                    break;
+                };
+                if let (Some(inherited), Some(own)) = (
+                    parent["fields"].as_array(),
+                    descriptor["fields"].as_array_mut(),
+                ) {
+                    own.extend(inherited.iter().cloned());
+                }
+                next = parent["parentType"].as_str().map(str::to_string);
+            }
+        }
+        Ok(descriptor)
+    }
+}
+
+// The standard class descriptors
+// See https://specs.amwa.tv/ms-05-02/releases/v1.0.0/docs/Framework.html
+fn standard_class_descriptors() -> Vec<Value> {
+    let id_parameter =
+        || make_parameter_descriptor("Property id", "id", "NcPropertyId", false, false);
+    let index_parameter = || {
+        make_parameter_descriptor(
+            "Index of item in the sequence",
+            "index",
+            "NcId",
+            false,
+            false,
+        )
+    };
+    let value_parameter =
+        |description| make_parameter_descriptor(description, "value", "", true, false);
+    let recurse_parameter = || {
+        make_parameter_descriptor(
+            "If recurse is set to true, nested members are to be retrieved",
+            "recurse",
+            "NcBoolean",
+            false,
+            false,
+        )
+    };
+    let include_inherited_parameter = |description| {
+        make_parameter_descriptor(description, "includeInherited", "NcBoolean", false, false)
+    };
+
+    vec![
+        make_class_descriptor(
+            "NcObject class descriptor",
+            class_ids::NC_OBJECT,
+            "NcObject",
+            None,
+            vec![
+                make_property_descriptor("Static value. All instances of the same class will have the same identity value", (1, 1), "classId", "NcClassId", true, false, false),
+                make_property_descriptor("Object identifier", (1, 2), "oid", "NcOid", true, false, false),
+                make_property_descriptor("TRUE iff OID is hardwired into device", (1, 3), "constantOid", "NcBoolean", true, false, false),
+                make_property_descriptor("OID of containing block. Can only ever be null for the root block", (1, 4), "owner", "NcOid", true, true, false),
+                make_property_descriptor("Role of object in the containing block", (1, 5), "role", "NcString", true, false, false),
+                make_property_descriptor("Scribble strip", (1, 6), "userLabel", "NcString", false, true, false),
+                make_property_descriptor("Touchpoints to other contexts", (1, 7), "touchpoints", "NcTouchpoint", true, true, true),
+                make_property_descriptor("Runtime property constraints", (1, 8), "runtimePropertyConstraints", "NcPropertyConstraints", true, true, true),
+            ],
+            vec![
+                make_method_descriptor("Get property value", (1, 1), "Get", "NcMethodResultPropertyValue", vec![id_parameter()]),
+                make_method_descriptor("Set property value", (1, 2), "Set", "NcMethodResult", vec![id_parameter(), value_parameter("Property value")]),
+                make_method_descriptor("Get sequence item", (1, 3), "GetSequenceItem", "NcMethodResultPropertyValue", vec![id_parameter(), index_parameter()]),
+                make_method_descriptor("Set sequence item value", (1, 4), "SetSequenceItem", "NcMethodResult", vec![id_parameter(), index_parameter(), value_parameter("Value")]),
+                make_method_descriptor("Add item to sequence", (1, 5), "AddSequenceItem", "NcMethodResultId", vec![id_parameter(), value_parameter("Value")]),
+                make_method_descriptor("Delete sequence item", (1, 6), "RemoveSequenceItem", "NcMethodResult", vec![id_parameter(), index_parameter()]),
+                make_method_descriptor("Get sequence length", (1, 7), "GetSequenceLength", "NcMethodResultLength", vec![id_parameter()]),
+            ],
+            vec![make_event_descriptor("Property changed event", (1, 1), "PropertyChanged", "NcPropertyChangedEventData")],
+        ),
+        make_class_descriptor(
+            "NcBlock class descriptor",
+            class_ids::NC_BLOCK,
+            "NcBlock",
+            None,
+            vec![
+                make_property_descriptor("TRUE if block is functional", (2, 1), "enabled", "NcBoolean", true, false, false),
+                make_property_descriptor("Descriptors of this block's members", (2, 2), "members", "NcBlockMemberDescriptor", true, false, true),
+            ],
+            vec![
+                make_method_descriptor("Gets descriptors of members of the block", (2, 1), "GetMemberDescriptors", "NcMethodResultBlockMemberDescriptors", vec![recurse_parameter()]),
+                make_method_descriptor("Finds member(s) by path", (2, 2), "FindMembersByPath", "NcMethodResultBlockMemberDescriptors", vec![
+                    make_parameter_descriptor("Relative path to search for (MUST not include the role of the block targeted by oid)", "path", "NcRolePath", false, false),
+                ]),
+                make_method_descriptor("Finds members with given role name or fragment", (2, 3), "FindMembersByRole", "NcMethodResultBlockMemberDescriptors", vec![
+                    make_parameter_descriptor("Role text to search for", "role", "NcString", false, false),
+                    make_parameter_descriptor("Signals if the comparison should be case sensitive", "caseSensitive", "NcBoolean", false, false),
+                    make_parameter_descriptor("TRUE to only return exact matches", "matchWholeString", "NcBoolean", false, false),
+                    recurse_parameter(),
+                ]),
+                make_method_descriptor("Finds members with given class id", (2, 4), "FindMembersByClassId", "NcMethodResultBlockMemberDescriptors", vec![
+                    make_parameter_descriptor("Class id to search for", "classId", "NcClassId", false, false),
+                    make_parameter_descriptor("If TRUE it will also include derived class descriptors", "includeDerived", "NcBoolean", false, false),
+                    recurse_parameter(),
+                ]),
+            ],
+            vec![],
+        ),
+        make_class_descriptor("NcWorker class descriptor", class_ids::NC_WORKER, "NcWorker", None, vec![], vec![], vec![]),
+        make_class_descriptor("NcManager class descriptor", class_ids::NC_MANAGER, "NcManager", None, vec![], vec![], vec![]),
+        make_class_descriptor(
+            "NcDeviceManager class descriptor",
+            class_ids::NC_DEVICE_MANAGER,
+            "NcDeviceManager",
+            Some("DeviceManager"),
+            vec![
+                make_property_descriptor("Version of MS-05-02 that this device uses", (3, 1), "ncVersion", "NcVersionCode", true, false, false),
+                make_property_descriptor("Manufacturer descriptor", (3, 2), "manufacturer", "NcManufacturer", true, false, false),
+                make_property_descriptor("Product descriptor", (3, 3), "product", "NcProduct", true, false, false),
+                make_property_descriptor("Serial number", (3, 4), "serialNumber", "NcString", true, false, false),
+                make_property_descriptor("Asset tracking identifier (user specified)", (3, 5), "userInventoryCode", "NcString", false, true, false),
+                make_property_descriptor("Name of this device in the application. Instance name, not product name", (3, 6), "deviceName", "NcString", false, true, false),
+                make_property_descriptor("Role of this device in the application", (3, 7), "deviceRole", "NcString", false, true, false),
+                make_property_descriptor("Device operational state", (3, 8), "operationalState", "NcDeviceOperationalState", true, false, false),
+                make_property_descriptor("Reason for most recent reset", (3, 9), "resetCause", "NcResetCause", true, false, false),
+                make_property_descriptor("Arbitrary message from dev to controller", (3, 10), "message", "NcString", true, true, false),
+            ],
+            vec![],
+            vec![],
+        ),
+        make_class_descriptor(
+            "NcClassManager class descriptor",
+            class_ids::NC_CLASS_MANAGER,
+            "NcClassManager",
+            Some("ClassManager"),
+            vec![
+                make_property_descriptor("Descriptions of all control classes in the device (descriptors do not contain inherited elements)", (3, 1), "controlClasses", "NcClassDescriptor", true, false, true),
+                make_property_descriptor("Descriptions of all data types in the device (descriptors do not contain inherited elements)", (3, 2), "datatypes", "NcDatatypeDescriptor", true, false, true),
+            ],
+            vec![
+                make_method_descriptor("Get a single class descriptor", (3, 1), "GetControlClass", "NcMethodResultClassDescriptor", vec![
+                    make_parameter_descriptor("class ID", "classId", "NcClassId", false, false),
+                    include_inherited_parameter("If set the descriptor would contain all inherited elements"),
+                ]),
+                make_method_descriptor("Get a single datatype descriptor", (3, 2), "GetDatatype", "NcMethodResultDatatypeDescriptor", vec![
+                    make_parameter_descriptor("name of datatype", "name", "NcName", false, false),
+                    include_inherited_parameter("If set the descriptor would contain all inherited elements"),
+                ]),
+            ],
+            vec![],
+        ),
+        make_class_descriptor(
+            "NcIdentBeacon class descriptor",
+            class_ids::NC_IDENT_BEACON,
+            "NcIdentBeacon",
+            None,
+            vec![
+                make_property_descriptor("Indicator active state", (3, 1), "active", "NcBoolean", false, false, false),
+            ],
+            vec![],
+            vec![],
+        ),
+        make_class_descriptor(
+            "NcStatusMonitor class descriptor",
+            class_ids::NC_STATUS_MONITOR,
+            "NcStatusMonitor",
+            None,
+            vec![
+                make_property_descriptor("Overall status property", (3, 1), "overallStatus", "NcOverallStatus", true, false, false),
+                make_property_descriptor("Overall status message property", (3, 2), "overallStatusMessage", "NcString", true, true, false),
+                make_property_descriptor("Status reporting delay property (delay in seconds, 0 means no delay)", (3, 3), "statusReportingDelay", "NcUint32", false, false, false),
+            ],
+            vec![],
+            vec![],
+        ),
+        make_class_descriptor(
+            "NcReceiverMonitor class descriptor",
+            class_ids::NC_RECEIVER_MONITOR,
+            "NcReceiverMonitor",
+            None,
+            vec![
+                make_property_descriptor("Link status property", (4, 1), "linkStatus", "NcLinkStatus", true, false, false),
+                make_property_descriptor("Link status message property", (4, 2), "linkStatusMessage", "NcString", true, true, false),
+                make_property_descriptor("Link status transition counter property", (4, 3), "linkStatusTransitionCounter", "NcUint64", true, false, false),
+                make_property_descriptor("Connection status property", (4, 4), "connectionStatus", "NcConnectionStatus", true, false, false),
+                make_property_descriptor("Connection status message property", (4, 5), "connectionStatusMessage", "NcString", true, true, false),
+                make_property_descriptor("Connection status transition counter property", (4, 6), "connectionStatusTransitionCounter", "NcUint64", true, false, false),
+                make_property_descriptor("External synchronization status property", (4, 7), "externalSynchronizationStatus", "NcSynchronizationStatus", true, false, false),
+                make_property_descriptor("External synchronization status message property", (4, 8), "externalSynchronizationStatusMessage", "NcString", true, true, false),
+                make_property_descriptor("External synchronization status transition counter property", (4, 9), "externalSynchronizationStatusTransitionCounter", "NcUint64", true, false, false),
+                make_property_descriptor("Synchronization source id property", (4, 10), "synchronizationSourceId", "NcString", true, true, false),
+                make_property_descriptor("Stream status property", (4, 11), "streamStatus", "NcStreamStatus", true, false, false),
+                make_property_descriptor("Stream status message property", (4, 12), "streamStatusMessage", "NcString", true, true, false),
+                make_property_descriptor("Stream status transition counter property", (4, 13), "streamStatusTransitionCounter", "NcUint64", true, false, false),
+                make_property_descriptor("Automatic reset counters and messages property (default: true)", (4, 14), "autoResetCountersAndMessages", "NcBoolean", false, false, false),
+            ],
+            vec![
+                make_method_descriptor("Gets the lost packet counters", (4, 1), "GetLostPacketCounters", "NcMethodResultCounters", vec![]),
+                make_method_descriptor("Gets the late packet counters", (4, 2), "GetLatePacketCounters", "NcMethodResultCounters", vec![]),
+                make_method_descriptor("Resets ALL counters and messages", (4, 3), "ResetCountersAndMessages", "NcMethodResult", vec![]),
+            ],
+            vec![],
+        ),
+        make_class_descriptor(
+            "NcSenderMonitor class descriptor",
+            class_ids::NC_SENDER_MONITOR,
+            "NcSenderMonitor",
+            None,
+            vec![
+                make_property_descriptor("Link status property", (4, 1), "linkStatus", "NcLinkStatus", true, false, false),
+                make_property_descriptor("Link status message property", (4, 2), "linkStatusMessage", "NcString", true, true, false),
+                make_property_descriptor("Link status transition counter property", (4, 3), "linkStatusTransitionCounter", "NcUint64", true, false, false),
+                make_property_descriptor("Transmission status property", (4, 4), "transmissionStatus", "NcTransmissionStatus", true, false, false),
+                make_property_descriptor("Transmission status message property", (4, 5), "transmissionStatusMessage", "NcString", true, true, false),
+                make_property_descriptor("Transmission status transition counter property", (4, 6), "transmissionStatusTransitionCounter", "NcUint64", true, false, false),
+                make_property_descriptor("External synchronization status property", (4, 7), "externalSynchronizationStatus", "NcSynchronizationStatus", true, false, false),
+                make_property_descriptor("External synchronization status message property", (4, 8), "externalSynchronizationStatusMessage", "NcString", true, true, false),
+                make_property_descriptor("External synchronization status transition counter property", (4, 9), "externalSynchronizationStatusTransitionCounter", "NcUint64", true, false, false),
+                make_property_descriptor("Synchronization source id property", (4, 10), "synchronizationSourceId", "NcString", true, true, false),
+                make_property_descriptor("Essence status property", (4, 11), "essenceStatus", "NcEssenceStatus", true, false, false),
+                make_property_descriptor("Essence status message property", (4, 12), "essenceStatusMessage", "NcString", true, true, false),
+                make_property_descriptor("Essence status transition counter property", (4, 13), "essenceStatusTransitionCounter", "NcUint64", true, false, false),
+                make_property_descriptor("Automatic reset counters and messages property (default: true)", (4, 14), "autoResetCountersAndMessages", "NcBoolean", false, false, false),
+            ],
+            vec![
+                make_method_descriptor("Gets the transmission error counters", (4, 1), "GetTransmissionErrorCounters", "NcMethodResultCounters", vec![]),
+                make_method_descriptor("Resets ALL counters and messages", (4, 2), "ResetCountersAndMessages", "NcMethodResult", vec![]),
+            ],
+            vec![],
+        ),
+    ]
+}
+
+// The standard datatype descriptors
+// See https://specs.amwa.tv/ms-05-02/releases/v1.0.0/docs/Framework.html#datatypes
+fn standard_datatype_descriptors() -> Vec<Value> {
+    let field = make_field_descriptor;
+    let item = make_enum_item;
+
+    vec![
+        make_primitive_datatype("Boolean primitive type", "NcBoolean"),
+        make_primitive_datatype("short", "NcInt16"),
+        make_primitive_datatype("long", "NcInt32"),
+        make_primitive_datatype("longlong", "NcInt64"),
+        make_primitive_datatype("unsigned short", "NcUint16"),
+        make_primitive_datatype("unsigned long", "NcUint32"),
+        make_primitive_datatype("unsigned longlong", "NcUint64"),
+        make_primitive_datatype("unrestricted float", "NcFloat32"),
+        make_primitive_datatype("unrestricted double", "NcFloat64"),
+        make_primitive_datatype("UTF-8 string", "NcString"),
+        make_typedef_datatype("Sequence of class ID fields", "NcClassId", "NcInt32", true),
+        make_typedef_datatype("Identity handler", "NcId", "NcUint32", false),
+        make_typedef_datatype(
+            "Programmatically significant name, alphanumerics + underscore, no spaces",
+            "NcName",
+            "NcString",
+            false,
+        ),
+        make_typedef_datatype("Object id", "NcOid", "NcUint32", false),
+        make_typedef_datatype(
+            "Unique 24-bit organization id",
+            "NcOrganizationId",
+            "NcInt32",
+            false,
+        ),
+        make_typedef_datatype("Regex pattern", "NcRegex", "NcString", false),
+        make_typedef_datatype("Role path", "NcRolePath", "NcString", true),
+        make_typedef_datatype(
+            "Time interval described in nanoseconds",
+            "NcTimeInterval",
+            "NcInt64",
+            false,
+        ),
+        make_typedef_datatype("Uniform resource identifier", "NcUri", "NcString", false),
+        make_typedef_datatype("UUID", "NcUuid", "NcString", false),
+        make_typedef_datatype(
+            "Version code in semantic versioning format",
+            "NcVersionCode",
+            "NcString",
+            false,
+        ),
+        make_struct_datatype(
+            "Class element id which contains the level and index",
+            "NcElementId",
+            vec![
+                field("Level of the element", "level", "NcUint16", false, false),
+                field("Index of the element", "index", "NcUint16", false, false),
+            ],
+            None,
+        ),
+        make_struct_datatype(
+            "Property id which contains the level and index",
+            "NcPropertyId",
+            vec![],
+            Some("NcElementId"),
+        ),
+        make_struct_datatype(
+            "Method id which contains the level and index",
+            "NcMethodId",
+            vec![],
+            Some("NcElementId"),
+        ),
+        make_struct_datatype(
+            "Event id which contains the level and index",
+            "NcEventId",
+            vec![],
+            Some("NcElementId"),
+        ),
+        make_struct_datatype(
+            "Base descriptor",
+            "NcDescriptor",
+            vec![field(
+                "Optional user facing description",
+                "description",
+                "NcString",
+                true,
+                false,
+            )],
+            None,
+        ),
+        make_struct_datatype(
+            "Descriptor which is specific to a block member",
+            "NcBlockMemberDescriptor",
+            vec![
+                field(
+                    "Role of member in its containing block",
+                    "role",
+                    "NcString",
+                    false,
+                    false,
+                ),
+                field("OID of member", "oid", "NcOid", false, false),
+                field(
+                    "TRUE iff member's OID is hardwired into device",
+                    "constantOid",
+                    "NcBoolean",
+                    false,
+                    false,
+                ),
+                field("Class ID", "classId", "NcClassId", false, false),
+                field("User label", "userLabel", "NcString", true, false),
+                field("Containing block's OID", "owner", "NcOid", false, false),
+            ],
+            Some("NcDescriptor"),
+        ),
+        make_struct_datatype(
+            "Manufacturer descriptor",
+            "NcManufacturer",
+            vec![
+                field("Manufacturer's name", "name", "NcString", false, false),
+                field(
+                    "IEEE OUI or CID of manufacturer",
+                    "organizationId",
+                    "NcOrganizationId",
+                    true,
+                    false,
+                ),
+                field(
+                    "URL of the manufacturer's website",
+                    "website",
+                    "NcUri",
+                    true,
+                    false,
+                ),
+            ],
+            None,
+        ),
+        make_struct_datatype(
+            "Product descriptor",
+            "NcProduct",
+            vec![
+                field("Product name", "name", "NcString", false, false),
+                field(
+                    "Manufacturer's unique key to product - model number, SKU, etc",
+                    "key",
+                    "NcString",
+                    false,
+                    false,
+                ),
+                field(
+                    "Manufacturer's product revision level code",
+                    "revisionLevel",
+                    "NcString",
+                    false,
+                    false,
+                ),
+                field(
+                    "Brand name under which product is sold",
+                    "brandName",
+                    "NcString",
+                    true,
+                    false,
+                ),
+                field(
+                    "Unique UUID of product (not product instance)",
+                    "uuid",
+                    "NcUuid",
+                    true,
+                    false,
+                ),
+                field(
+                    "Text description of product",
+                    "description",
+                    "NcString",
+                    true,
+                    false,
+                ),
+            ],
+            None,
+        ),
+        make_struct_datatype(
+            "Device operational state",
+            "NcDeviceOperationalState",
+            vec![
+                field(
+                    "Generic operational state",
+                    "generic",
+                    "NcDeviceGenericState",
+                    false,
+                    false,
+                ),
+                field(
+                    "Specific device details",
+                    "deviceSpecificDetails",
+                    "NcString",
+                    true,
+                    false,
+                ),
+            ],
+            None,
+        ),
+        make_struct_datatype(
+            "Base touchpoint class",
+            "NcTouchpoint",
+            vec![field(
+                "Context namespace",
+                "contextNamespace",
+                "NcString",
+                false,
+                false,
+            )],
+            None,
+        ),
+        make_struct_datatype(
+            "Touchpoint class for NMOS resources",
+            "NcTouchpointNmos",
+            vec![field(
+                "Context NMOS resource",
+                "resource",
+                "NcTouchpointResourceNmos",
+                false,
+                false,
+            )],
+            Some("NcTouchpoint"),
+        ),
+        make_struct_datatype(
+            "Touchpoint resource class",
+            "NcTouchpointResource",
+            vec![field(
+                "The type of the resource",
+                "resourceType",
+                "NcString",
+                false,
+                false,
+            )],
+            None,
+        ),
+        make_struct_datatype(
+            "Touchpoint resource class for NMOS resources",
+            "NcTouchpointResourceNmos",
+            vec![field("NMOS resource UUID", "id", "NcUuid", false, false)],
+            Some("NcTouchpointResource"),
+        ),
+        make_struct_datatype(
+            "Property changed event data",
+            "NcPropertyChangedEventData",
+            vec![
+                field(
+                    "The id of the property that changed",
+                    "propertyId",
+                    "NcPropertyId",
+                    false,
+                    false,
+                ),
+                field(
+                    "Information regarding the change type",
+                    "changeType",
+                    "NcPropertyChangeType",
+                    false,
+                    false,
+                ),
+                field("Property-type specific value", "value", "", true, false),
+                field(
+                    "Index of sequence item if the property is a sequence",
+                    "sequenceItemIndex",
+                    "NcId",
+                    true,
+                    false,
+                ),
+            ],
+            None,
+        ),
+        make_struct_datatype(
+            "Base result of the invoked method",
+            "NcMethodResult",
+            vec![field(
+                "Status of method call",
+                "status",
+                "NcMethodStatus",
+                false,
+                false,
+            )],
+            None,
+        ),
+        make_struct_datatype(
+            "Error result - to be used when the method call encounters an error",
+            "NcMethodResultError",
+            vec![field(
+                "Error message",
+                "errorMessage",
+                "NcString",
+                false,
+                false,
+            )],
+            Some("NcMethodResult"),
+        ),
+        make_struct_datatype(
+            "Result when invoking the getter method associated with a property",
+            "NcMethodResultPropertyValue",
+            vec![field(
+                "Getter method value for the associated property",
+                "value",
+                "",
+                true,
+                false,
+            )],
+            Some("NcMethodResult"),
+        ),
+        make_enum_datatype(
+            "Method invokation status",
+            "NcMethodStatus",
+            vec![
+                item("Method call was successful", "Ok", 200),
+                item(
+                    "Method call was successful but targeted property is deprecated",
+                    "PropertyDeprecated",
+                    298,
+                ),
+                item(
+                    "Method call was successful but method is deprecated",
+                    "MethodDeprecated",
+                    299,
+                ),
+                item("Badly-formed command", "BadCommandFormat", 400),
+                item("Client is not authorized", "Unauthorized", 401),
+                item("Command addresses a nonexistent object", "BadOid", 404),
+                item("Attempt to change read-only state", "Readonly", 405),
+                item(
+                    "Method call is invalid in current operating context",
+                    "InvalidRequest",
+                    406,
+                ),
+                item(
+                    "There is a conflict with the current state of the device",
+                    "Conflict",
+                    409,
+                ),
+                item("Something was too big", "BufferOverflow", 413),
+                item(
+                    "Index is outside the available range",
+                    "IndexOutOfBounds",
+                    414,
+                ),
+                item(
+                    "Method parameter does not meet expectations",
+                    "ParameterError",
+                    417,
+                ),
+                item("Addressed object is locked", "Locked", 423),
+                item("Internal device error", "DeviceError", 500),
+                item(
+                    "Addressed method is not implemented by the addressed object",
+                    "MethodNotImplemented",
+                    501,
+                ),
+                item(
+                    "Addressed property is not implemented by the addressed object",
+                    "PropertyNotImplemented",
+                    502,
+                ),
+                item(
+                    "The device is not ready to handle any commands",
+                    "NotReady",
+                    503,
+                ),
+                item(
+                    "Method call did not finish within the allotted time",
+                    "Timeout",
+                    504,
+                ),
+            ],
+        ),
+        make_enum_datatype(
+            "Type of property change",
+            "NcPropertyChangeType",
+            vec![
+                item("Current value changed", "ValueChanged", 0),
+                item("Sequence item added", "SequenceItemAdded", 1),
+                item("Sequence item changed", "SequenceItemChanged", 2),
+                item("Sequence item removed", "SequenceItemRemoved", 3),
+            ],
+        ),
+        make_enum_datatype(
+            "Datatype type",
+            "NcDatatypeType",
+            vec![
+                item("Primitive datatype", "Primitive", 0),
+                item("Simple alias of another datatype", "Typedef", 1),
+                item("Data structure", "Struct", 2),
+                item("Enum datatype", "Enum", 3),
+            ],
+        ),
+        make_enum_datatype(
+            "Device generic operational state",
+            "NcDeviceGenericState",
+            vec![
+                item("Unknown", "Unknown", 0),
+                item("Normal operation", "NormalOperation", 1),
+                item("Device is initializing", "Initializing", 2),
+                item(
+                    "Device is performing a software or firmware update",
+                    "Updating",
+                    3,
+                ),
+                item(
+                    "Device is experiencing a licensing error",
+                    "LicensingError",
+                    4,
+                ),
+                item(
+                    "Device is experiencing an internal error",
+                    "InternalError",
+                    5,
+                ),
+            ],
+        ),
+        make_enum_datatype(
+            "Reset cause enum",
+            "NcResetCause",
+            vec![
+                item("Unknown", "Unknown", 0),
+                item("Power on", "PowerOn", 1),
+                item("Internal error", "InternalError", 2),
+                item("Upgrade", "Upgrade", 3),
+                item("Controller request", "ControllerRequest", 4),
+                item("Manual request from the front panel", "ManualReset", 5),
+            ],
+        ),
+        make_enum_datatype(
+            "Overall status enum data type",
+            "NcOverallStatus",
+            vec![
+                item("Inactive", "Inactive", 0),
+                item("The overall status is healthy", "Healthy", 1),
+                item(
+                    "The overall status is partially healthy",
+                    "PartiallyHealthy",
+                    2,
+                ),
+                item("The overall status is unhealthy", "Unhealthy", 3),
+            ],
+        ),
+        make_enum_datatype(
+            "Link status enum data type",
+            "NcLinkStatus",
+            vec![
+                item("All the associated network interfaces are up", "AllUp", 1),
+                item(
+                    "Some of the associated network interfaces are down",
+                    "SomeDown",
+                    2,
+                ),
+                item(
+                    "All the associated network interfaces are down",
+                    "AllDown",
+                    3,
+                ),
+            ],
+        ),
+        make_enum_datatype(
+            "Connection status enum data type",
+            "NcConnectionStatus",
+            vec![
+                item("Inactive", "Inactive", 0),
+                item("Active and healthy", "Healthy", 1),
+                item("Active and partially healthy", "PartiallyHealthy", 2),
+                item("Active and unhealthy", "Unhealthy", 3),
+            ],
+        ),
+        make_enum_datatype(
+            "Synchronization status enum data type",
+            "NcSynchronizationStatus",
+            vec![
+                item("Feature not in use", "NotUsed", 0),
+                item("Locked to a synchronization source", "Healthy", 1),
+                item(
+                    "Partially locked to a synchronization source",
+                    "PartiallyHealthy",
+                    2,
+                ),
+                item("Not locked to a synchronization source", "Unhealthy", 3),
+            ],
+        ),
+        make_enum_datatype(
+            "Stream status enum data type",
+            "NcStreamStatus",
+            vec![
+                item("Inactive", "Inactive", 0),
+                item("Active and healthy", "Healthy", 1),
+                item("Active and partially healthy", "PartiallyHealthy", 2),
+                item("Active and unhealthy", "Unhealthy", 3),
+            ],
+        ),
+        make_enum_datatype(
+            "Transmission status enum data type",
+            "NcTransmissionStatus",
+            vec![
+                item("Inactive", "Inactive", 0),
+                item("Active and healthy", "Healthy", 1),
+                item("Active and partially healthy", "PartiallyHealthy", 2),
+                item("Active and unhealthy", "Unhealthy", 3),
+            ],
+        ),
+        make_enum_datatype(
+            "Essence status enum data type",
+            "NcEssenceStatus",
+            vec![
+                item("Inactive", "Inactive", 0),
+                item("Active and healthy", "Healthy", 1),
+                item("Active and partially healthy", "PartiallyHealthy", 2),
+                item("Active and unhealthy", "Unhealthy", 3),
+            ],
+        ),
+        make_struct_datatype(
+            "Counter data type",
+            "NcCounter",
+            vec![
+                field("Counter name", "name", "NcString", false, false),
+                field("Counter value", "value", "NcUint64", false, false),
+                field(
+                    "Counter description",
+                    "description",
+                    "NcString",
+                    true,
+                    false,
+                ),
+            ],
+            None,
+        ),
+        make_struct_datatype(
+            "Method result containing counter values",
+            "NcMethodResultCounters",
+            vec![field("Counter values", "value", "NcCounter", false, true)],
+            Some("NcMethodResult"),
+        ),
+    ]
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_control_class() {
+        let registry = ClassRegistry::standard();
+
+        let descriptor = registry.control_class(class_ids::NC_BLOCK, false).unwrap();
+        assert_eq!(descriptor["name"], "NcBlock");
+        assert_eq!(descriptor["properties"].as_array().unwrap().len(), 2);
+
+        let descriptor = registry.control_class(class_ids::NC_BLOCK, true).unwrap();
+        assert_eq!(descriptor["properties"].as_array().unwrap().len(), 10);
+        assert_eq!(descriptor["methods"].as_array().unwrap().len(), 11);
+        assert_eq!(descriptor["events"].as_array().unwrap().len(), 1);
+
+        let result = registry.control_class(&[1, 9], false);
+        assert_eq!(result.unwrap_err().status, NcMethodStatus::ParameterError);
+
+        assert_eq!(class_id_at_level(&[1, 2, 0, 1], 2), Some(&[1, 2][..]));
+        assert_eq!(class_id_at_level(&[1, 2, 0, 1], 3), Some(&[1, 2, 0, 1][..]));
+        assert_eq!(
+            class_id_at_level(&[1, 2, -1234, 1, 1], 4),
+            Some(&[1, 2, -1234, 1, 1][..])
+        );
+        assert_eq!(class_id_at_level(&[1, 2, 0, 1], 4), None);
+        assert_eq!(class_id_at_level(&[1, 2], 0), None);
+    }
+
+    #[test]
+    fn test_datatype() {
+        let registry = ClassRegistry::standard();
+
+        let descriptor = registry.datatype("NcInt32", false).unwrap();
+        assert_eq!(descriptor["type"], datatype_types::PRIMITIVE);
+        let descriptor = registry.datatype("NcClassId", false).unwrap();
+        assert_eq!(descriptor["type"], datatype_types::TYPEDEF);
+        assert_eq!(descriptor["isSequence"], true);
+        let descriptor = registry.datatype("NcResetCause", false).unwrap();
+        assert_eq!(descriptor["type"], datatype_types::ENUM);
+
+        let descriptor = registry.datatype("NcBlockMemberDescriptor", false).unwrap();
+        assert_eq!(descriptor["fields"].as_array().unwrap().len(), 6);
+        let descriptor = registry.datatype("NcBlockMemberDescriptor", true).unwrap();
+        assert_eq!(descriptor["fields"].as_array().unwrap().len(), 7);
+    }
+
+    #[test]
+    fn test_register_class() {
+        let mut registry = ClassRegistry::standard();
+        let gain = |class_id: &[i32]| {
+            make_class_descriptor(
+                "Gain control",
+                class_id,
+                "GainControl",
+                None,
+                vec![make_property_descriptor(
+                    "Gain value",
+                    (3, 1),
+                    "gainValue",
+                    "NcFloat32",
+                    false,
+                    false,
+                    false,
+                )],
+                vec![],
+                vec![],
+            )
+        };
+
+        assert!(registry.register_class(gain(&[1, 2, -1234, 1])).is_ok());
+        assert!(registry.register_class(gain(&[1, 2, -1234, 1, 1])).is_ok());
+        assert!(registry.register_class(gain(&[1, 2, 0, 2])).is_ok());
+
+        // no authority key
+        assert!(registry.register_class(gain(&[1, 2, 7])).is_err());
+        // unregistered parent
+        assert!(registry.register_class(gain(&[1, 9, -1234, 1])).is_err());
+        assert!(registry.register_class(gain(&[1, 2, -1234, 5, 1])).is_err());
+        // already registered
+        assert!(registry.register_class(gain(&[1, 2, -1234, 1])).is_err());
+
+        let descriptor = registry.control_class(&[1, 2, -1234, 1, 1], true).unwrap();
+        assert_eq!(descriptor["properties"].as_array().unwrap().len(), 10);
+    }
+}
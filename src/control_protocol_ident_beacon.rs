@@ -0,0 +1,196 @@
+// The identification beacon, which lets an operator find a device, e.g. by flashing its front
+// panel lights, and which is automatically deactivated after a timeout
+// See https://specs.amwa.tv/nmos-control-feature-sets/branches/main/identification/#ncidentbeacon
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::{sleep_until, Instant};
+
+use crate::control_protocol::{
+    class_ids, property_ids, ControlProtocolState, NcMethodResult, NcMethodStatus, NcObject,
+};
+
+// The default time after which an active beacon is deactivated
+pub const DEFAULT_IDENT_BEACON_TIMEOUT: Duration = Duration::from_secs(60);
+
+// Define the hardware callback, invoked with the new state whenever the beacon is activated or
+// deactivated
+pub type IdentBeaconCallback = Arc<dyn Fn(bool) + Send + Sync>;
+
+// Construct an ident beacon, which is initially inactive
+pub fn make_ident_beacon(oid: u32, owner: u32, role: &str, user_label: Option<&str>) -> NcObject {
+    NcObject::new(
+        oid,
+        class_ids::NC_IDENT_BEACON,
+        role,
+        Some(owner),
+        user_label,
+    )
+    .with_property(property_ids::IDENT_BEACON_ACTIVE, json!(false), false)
+}
+
+// Register the setter of the active property, which only accepts a boolean, and which notifies
+// subscribers each time a controller sets it to true, even if the beacon is already active, so that
+// the timeout is restarted
+pub fn add_ident_beacon_setters(state: &mut ControlProtocolState) {
+    state.add_property_setter(
+        class_ids::NC_IDENT_BEACON,
+        property_ids::IDENT_BEACON_ACTIVE,
+        Arc::new(|state, oid, id, value| {
+            let active = value.as_bool().ok_or_else(|| {
+                NcMethodResult::error(NcMethodStatus::ParameterError, "active must be a boolean")
+            })?;
+            let was_active = state.get_property(oid, id)?.as_bool().unwrap_or(false);
+            state.set_property(oid, id, value)?;
+            if active && was_active {
+                state.notify_property(oid, id)?;
+            }
+            Ok(())
+        }),
+    );
+}
+
+// Drive the hardware from the active property of the ident beacon with the specified oid,
+// reverting it to inactive after the timeout, which is restarted each time a controller sets the
+// property to true
+// This runs until the beacon is removed from the control model
+pub async fn run_ident_beacon(
+    state: Arc<Mutex<ControlProtocolState>>,
+    oid: u32,
+    timeout: Duration,
+    callback: IdentBeaconCallback,
+) {
+    let mut notifications = state.lock().unwrap().subscribe();
+    let mut revert_at: Option<Instant> = None;
+
+    loop {
+        let revert = async move {
+            match revert_at {
+                Some(revert_at) => sleep_until(revert_at).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            event = notifications.recv() => {
+                let active = match event {
+                    Ok(event) => {
+                        if event.oid != oid || event.property_id != property_ids::IDENT_BEACON_ACTIVE {
+                            continue;
+                        }
+                        event.value.as_bool().unwrap_or(false)
+                    }
+                    // Some notifications were missed, so just resynchronize with the property
+                    Err(RecvError::Lagged(_)) => {
+                        let state = state.lock().unwrap();
+                        match state.get_property(oid, property_ids::IDENT_BEACON_ACTIVE) {
+                            Ok(active) => active.as_bool().unwrap_or(false),
+                            Err(_) => break,
+                        }
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+
+                callback(active);
+                revert_at = active.then(|| Instant::now() + timeout);
+            }
+            _ = revert => {
+                revert_at = None;
+                // The resulting notification invokes the callback
+                let result = state.lock().unwrap().set_property(
+                    oid,
+                    property_ids::IDENT_BEACON_ACTIVE,
+                    Value::Bool(false),
+                );
+                if result.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control_protocol::method_ids;
+    use crate::control_protocol_resources::{make_root_block, ROOT_BLOCK_OID};
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ident_beacon() {
+        let mut state = ControlProtocolState::new();
+        state.insert(make_root_block(None));
+        let oid = state
+            .add_member(make_ident_beacon(
+                state.next_oid(),
+                ROOT_BLOCK_OID,
+                "IdentBeacon",
+                None,
+            ))
+            .unwrap();
+        let state = Arc::new(Mutex::new(state));
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let callback: IdentBeaconCallback = {
+            let calls = calls.clone();
+            Arc::new(move |active| calls.lock().unwrap().push(active))
+        };
+        let beacon = tokio::spawn(run_ident_beacon(
+            state.clone(),
+            oid,
+            Duration::from_millis(50),
+            callback,
+        ));
+        // Let the beacon handle any notifications, and then move the paused clock on, and let the
+        // beacon handle its timeout, if that has passed
+        let advance = |millis| async move {
+            tokio::task::yield_now().await;
+            tokio::time::advance(Duration::from_millis(millis)).await;
+            tokio::task::yield_now().await;
+        };
+        advance(0).await;
+
+        let result = state.lock().unwrap().invoke(
+            oid,
+            method_ids::SET,
+            &json!({ "id": property_ids::IDENT_BEACON_ACTIVE.to_json(), "value": true }),
+        );
+        assert!(result.is_ok());
+
+        // Setting it again restarts the timeout
+        advance(30).await;
+        let result = state.lock().unwrap().invoke(
+            oid,
+            method_ids::SET,
+            &json!({ "id": property_ids::IDENT_BEACON_ACTIVE.to_json(), "value": true }),
+        );
+        assert!(result.is_ok());
+        advance(30).await;
+        assert_eq!(*calls.lock().unwrap(), vec![true, true]);
+
+        // Only a boolean is accepted
+        let result = state.lock().unwrap().invoke(
+            oid,
+            method_ids::SET,
+            &json!({ "id": property_ids::IDENT_BEACON_ACTIVE.to_json(), "value": "yes" }),
+        );
+        assert_eq!(result.status, NcMethodStatus::ParameterError);
+
+        advance(20).await;
+        assert_eq!(*calls.lock().unwrap(), vec![true, true, false]);
+        assert_eq!(
+            state
+                .lock()
+                .unwrap()
+                .get_property(oid, property_ids::IDENT_BEACON_ACTIVE)
+                .unwrap(),
+            false
+        );
+
+        beacon.abort();
+    }
+}
@@ -0,0 +1,538 @@
+// Handlers for the methods of the standard MS-05-02 classes, other than those of NcObject itself
+// See https://specs.amwa.tv/ms-05-02/releases/v1.0.0/docs/Framework.html
+
+use std::sync::Arc;
+
+use serde_json::{json, Value};
+
+use crate::control_protocol::{
+    class_ids, method_ids, property_ids, ControlProtocolState, NcMethodResult, NcMethodStatus,
+};
+use crate::control_protocol_monitors::{counter_sets, counters, reset_counters_and_messages};
+use crate::control_protocol_resources::make_member_descriptor;
+
+// Collect the member descriptors of a block, optionally including those of all nested blocks
+fn member_descriptors(state: &ControlProtocolState, block_oid: u32, recurse: bool) -> Vec<Value> {
+    let mut descriptors = Vec::new();
+    let Some(block) = state.get(block_oid) else {
+        return descriptors;
+    };
+    let members = block
+        .property(property_ids::BLOCK_MEMBERS)
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+
+    for descriptor in members {
+        let nested = descriptor
+            .get("oid")
+            .and_then(Value::as_u64)
+            .and_then(|oid| u32::try_from(oid).ok())
+            .filter(|oid| {
+                recurse
+                    && state
+                        .get(*oid)
+                        .is_some_and(|member| member.is_instance_of(class_ids::NC_BLOCK))
+            });
+        descriptors.push(descriptor);
+        if let Some(nested) = nested {
+            descriptors.extend(member_descriptors(state, nested, recurse));
+        }
+    }
+    descriptors
+}
+
+fn bool_argument(arguments: &Value, name: &str) -> Result<bool, NcMethodResult> {
+    arguments.get(name).and_then(Value::as_bool).ok_or_else(|| {
+        NcMethodResult::error(
+            NcMethodStatus::ParameterError,
+            format!("missing or invalid {} argument", name),
+        )
+    })
+}
+
+// Extract the FindMembersByRole arguments
+fn role_arguments(arguments: &Value) -> Result<(&str, bool, bool, bool), NcMethodResult> {
+    let role = arguments
+        .get("role")
+        .and_then(Value::as_str)
+        .filter(|role| !role.is_empty())
+        .ok_or_else(|| {
+            NcMethodResult::error(
+                NcMethodStatus::ParameterError,
+                "missing or empty role argument",
+            )
+        })?;
+    Ok((
+        role,
+        bool_argument(arguments, "caseSensitive")?,
+        bool_argument(arguments, "matchWholeString")?,
+        bool_argument(arguments, "recurse")?,
+    ))
+}
+
+// Register the NcBlock methods
+// See https://specs.amwa.tv/ms-05-02/releases/v1.0.0/docs/Framework.html#ncblock
+pub fn add_block_methods(state: &mut ControlProtocolState) {
+    state.add_method(
+        class_ids::NC_BLOCK,
+        method_ids::GET_MEMBER_DESCRIPTORS,
+        Arc::new(
+            |state, oid, arguments| match bool_argument(arguments, "recurse") {
+                Ok(recurse) => {
+                    NcMethodResult::ok_with(json!(member_descriptors(state, oid, recurse)))
+                }
+                Err(result) => result,
+            },
+        ),
+    );
+
+    state.add_method(
+        class_ids::NC_BLOCK,
+        method_ids::FIND_MEMBERS_BY_PATH,
+        Arc::new(|state, oid, arguments| {
+            let path: Vec<&str> = arguments
+                .get("path")
+                .and_then(Value::as_array)
+                .map(|path| path.iter().filter_map(Value::as_str).collect())
+                .unwrap_or_default();
+            if path.is_empty() {
+                return NcMethodResult::error(
+                    NcMethodStatus::ParameterError,
+                    "missing or empty path argument",
+                );
+            }
+
+            let mut found = state.get(oid);
+            for role in &path {
+                found = found.and_then(|block| {
+                    state
+                        .members(block.oid)
+                        .into_iter()
+                        .find(|member| member.role() == *role)
+                });
+            }
+
+            match found {
+                Some(member) => NcMethodResult::ok_with(json!([make_member_descriptor(member)])),
+                None => NcMethodResult::error(
+                    NcMethodStatus::ParameterError,
+                    format!("role path {} not found", path.join(".")),
+                ),
+            }
+        }),
+    );
+
+    state.add_method(
+        class_ids::NC_BLOCK,
+        method_ids::FIND_MEMBERS_BY_ROLE,
+        Arc::new(|state, oid, arguments| {
+            let (role, case_sensitive, match_whole_string, recurse) =
+                match role_arguments(arguments) {
+                    Ok(args) => args,
+                    Err(result) => return result,
+                };
+
+            let normalize = |s: &str| {
+                if case_sensitive {
+                    s.to_string()
+                } else {
+                    s.to_lowercase()
+                }
+            };
+            let role = normalize(role);
+            let found: Vec<Value> = member_descriptors(state, oid, recurse)
+                .into_iter()
+                .filter(|descriptor| {
+                    let member_role = normalize(descriptor["role"].as_str().unwrap_or_default());
+                    if match_whole_string {
+                        member_role == role
+                    } else {
+                        member_role.contains(&role)
+                    }
+                })
+                .collect();
+            NcMethodResult::ok_with(json!(found))
+        }),
+    );
+
+    state.add_method(
+        class_ids::NC_BLOCK,
+        method_ids::FIND_MEMBERS_BY_CLASS_ID,
+        Arc::new(|state, oid, arguments| {
+            let class_id: Vec<i32> = arguments
+                .get("classId")
+                .and_then(Value::as_array)
+                .map(|class_id| {
+                    class_id
+                        .iter()
+                        .filter_map(Value::as_i64)
+                        .filter_map(|i| i32::try_from(i).ok())
+                        .collect()
+                })
+                .unwrap_or_default();
+            if class_id.is_empty() {
+                return NcMethodResult::error(
+                    NcMethodStatus::ParameterError,
+                    "missing or empty classId argument",
+                );
+            }
+            let include_derived = match bool_argument(arguments, "includeDerived") {
+                Ok(include_derived) => include_derived,
+                Err(result) => return result,
+            };
+            let recurse = match bool_argument(arguments, "recurse") {
+                Ok(recurse) => recurse,
+                Err(result) => return result,
+            };
+
+            let found: Vec<Value> = member_descriptors(state, oid, recurse)
+                .into_iter()
+                .filter(|descriptor| {
+                    let member_class_id: Vec<i32> =
+                        serde_json::from_value(descriptor["classId"].clone()).unwrap_or_default();
+                    if include_derived {
+                        member_class_id.starts_with(&class_id)
+                    } else {
+                        member_class_id == class_id
+                    }
+                })
+                .collect();
+            NcMethodResult::ok_with(json!(found))
+        }),
+    );
+}
+
+// Register the NcClassManager methods
+// See https://specs.amwa.tv/ms-05-02/releases/v1.0.0/docs/Managers.html#class-manager
+pub fn add_class_manager_methods(state: &mut ControlProtocolState) {
+    state.add_method(
+        class_ids::NC_CLASS_MANAGER,
+        method_ids::GET_CONTROL_CLASS,
+        Arc::new(|state, _, arguments| {
+            let class_id: Option<Vec<i32>> = arguments
+                .get("classId")
+                .and_then(|class_id| serde_json::from_value(class_id.clone()).ok());
+            let Some(class_id) = class_id else {
+                return NcMethodResult::error(
+                    NcMethodStatus::ParameterError,
+                    "missing or invalid classId argument",
+                );
+            };
+            let include_inherited = match bool_argument(arguments, "includeInherited") {
+                Ok(include_inherited) => include_inherited,
+                Err(result) => return result,
+            };
+
+            match state
+                .class_registry()
+                .control_class(&class_id, include_inherited)
+            {
+                Ok(descriptor) => NcMethodResult::ok_with(descriptor),
+                Err(result) => result,
+            }
+        }),
+    );
+
+    state.add_method(
+        class_ids::NC_CLASS_MANAGER,
+        method_ids::GET_DATATYPE,
+        Arc::new(|state, _, arguments| {
+            let Some(name) = arguments.get("name").and_then(Value::as_str) else {
+                return NcMethodResult::error(
+                    NcMethodStatus::ParameterError,
+                    "missing or invalid name argument",
+                );
+            };
+            let include_inherited = match bool_argument(arguments, "includeInherited") {
+                Ok(include_inherited) => include_inherited,
+                Err(result) => return result,
+            };
+
+            match state.class_registry().datatype(name, include_inherited) {
+                Ok(descriptor) => NcMethodResult::ok_with(descriptor),
+                Err(result) => result,
+            }
+        }),
+    );
+}
+
+// Register the NcReceiverMonitor methods
+// See https://specs.amwa.tv/bcp-008-01/releases/v1.0.0/docs/Overview.html#ncreceivermonitor
+pub fn add_receiver_monitor_methods(state: &mut ControlProtocolState) {
+    for (method_id, counter_set) in [
+        (
+            method_ids::RECEIVER_MONITOR_GET_LOST_PACKET_COUNTERS,
+            counter_sets::LOST_PACKETS,
+        ),
+        (
+            method_ids::RECEIVER_MONITOR_GET_LATE_PACKET_COUNTERS,
+            counter_sets::LATE_PACKETS,
+        ),
+    ] {
+        state.add_method(
+            class_ids::NC_RECEIVER_MONITOR,
+            method_id,
+            Arc::new(move |state, oid, _| match state.get(oid) {
+                Some(object) => NcMethodResult::ok_with(json!(counters(object, counter_set))),
+                None => NcMethodResult::error(
+                    NcMethodStatus::BadOid,
+                    format!("no object with oid: {}", oid),
+                ),
+            }),
+        );
+    }
+
+    state.add_method(
+        class_ids::NC_RECEIVER_MONITOR,
+        method_ids::RECEIVER_MONITOR_RESET_COUNTERS_AND_MESSAGES,
+        Arc::new(
+            |state, oid, _| match reset_counters_and_messages(state, oid) {
+                Ok(()) => NcMethodResult::ok(),
+                Err(result) => result,
+            },
+        ),
+    );
+}
+
+// Register the NcSenderMonitor methods
+// See https://specs.amwa.tv/bcp-008-02/releases/v1.0.0/docs/Overview.html#ncsendermonitor
+pub fn add_sender_monitor_methods(state: &mut ControlProtocolState) {
+    state.add_method(
+        class_ids::NC_SENDER_MONITOR,
+        method_ids::SENDER_MONITOR_GET_TRANSMISSION_ERROR_COUNTERS,
+        Arc::new(|state, oid, _| match state.get(oid) {
+            Some(object) => {
+                NcMethodResult::ok_with(json!(counters(object, counter_sets::TRANSMISSION_ERRORS)))
+            }
+            None => NcMethodResult::error(
+                NcMethodStatus::BadOid,
+                format!("no object with oid: {}", oid),
+            ),
+        }),
+    );
+
+    state.add_method(
+        class_ids::NC_SENDER_MONITOR,
+        method_ids::SENDER_MONITOR_RESET_COUNTERS_AND_MESSAGES,
+        Arc::new(
+            |state, oid, _| match reset_counters_and_messages(state, oid) {
+                Ok(()) => NcMethodResult::ok(),
+                Err(result) => result,
+            },
+        ),
+    );
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control_protocol::NcObject;
+    use crate::control_protocol_classes::make_class_descriptor;
+    use crate::control_protocol_resources::{
+        make_block, make_class_manager, make_root_block, ROOT_BLOCK_OID,
+    };
+
+    const GAIN_CLASS_ID: &[i32] = &[1, 2, 0, 1];
+
+    // root
+    // +- receivers (2)
+    // |  +- gain-01 (3)
+    // +- gain-02 (4)
+    fn make_state() -> ControlProtocolState {
+        let mut state = ControlProtocolState::new();
+        state.insert(make_root_block(Some("Device")));
+        let receivers = state
+            .add_member(make_block(
+                state.next_oid(),
+                Some(ROOT_BLOCK_OID),
+                "receivers",
+                Some("Receivers"),
+            ))
+            .unwrap();
+        state
+            .add_member(NcObject::new(
+                state.next_oid(),
+                GAIN_CLASS_ID,
+                "gain-01",
+                Some(receivers),
+                None,
+            ))
+            .unwrap();
+        state
+            .add_member(NcObject::new(
+                state.next_oid(),
+                GAIN_CLASS_ID,
+                "Gain-02",
+                Some(ROOT_BLOCK_OID),
+                None,
+            ))
+            .unwrap();
+        state
+    }
+
+    fn oids(result: &NcMethodResult) -> Vec<u64> {
+        result
+            .value
+            .as_ref()
+            .and_then(Value::as_array)
+            .map(|descriptors| {
+                descriptors
+                    .iter()
+                    .filter_map(|d| d["oid"].as_u64())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_role_paths() {
+        let state = make_state();
+        assert_eq!(
+            state
+                .find_role_path(&["root", "receivers", "gain-01"])
+                .map(|o| o.oid),
+            Some(3)
+        );
+        assert_eq!(
+            state.find_role_path(&["root", "gain-01"]).map(|o| o.oid),
+            None
+        );
+        assert_eq!(state.role_path(3), vec!["root", "receivers", "gain-01"]);
+        assert_eq!(state.next_oid(), 5);
+    }
+
+    #[test]
+    fn test_add_member_conflict() {
+        let mut state = make_state();
+        let result = state.add_member(NcObject::new(
+            state.next_oid(),
+            GAIN_CLASS_ID,
+            "receivers",
+            Some(ROOT_BLOCK_OID),
+            None,
+        ));
+        assert_eq!(result.unwrap_err().status, NcMethodStatus::Conflict);
+        let result = state.add_member(NcObject::new(
+            state.next_oid(),
+            GAIN_CLASS_ID,
+            "gain-03",
+            Some(4),
+            None,
+        ));
+        assert_eq!(result.unwrap_err().status, NcMethodStatus::BadOid);
+    }
+
+    #[test]
+    fn test_get_member_descriptors() {
+        let mut state = make_state();
+        let result = state.invoke(
+            ROOT_BLOCK_OID,
+            method_ids::GET_MEMBER_DESCRIPTORS,
+            &json!({ "recurse": false }),
+        );
+        assert_eq!(oids(&result), vec![2, 4]);
+        let result = state.invoke(
+            ROOT_BLOCK_OID,
+            method_ids::GET_MEMBER_DESCRIPTORS,
+            &json!({ "recurse": true }),
+        );
+        assert_eq!(oids(&result), vec![2, 3, 4]);
+        assert_eq!(result.value.unwrap()[1]["owner"], 2);
+    }
+
+    #[test]
+    fn test_find_members_by_path() {
+        let mut state = make_state();
+        let result = state.invoke(
+            ROOT_BLOCK_OID,
+            method_ids::FIND_MEMBERS_BY_PATH,
+            &json!({ "path": ["receivers", "gain-01"] }),
+        );
+        assert_eq!(oids(&result), vec![3]);
+        let result = state.invoke(
+            ROOT_BLOCK_OID,
+            method_ids::FIND_MEMBERS_BY_PATH,
+            &json!({ "path": ["gain-01"] }),
+        );
+        assert_eq!(result.status, NcMethodStatus::ParameterError);
+    }
+
+    #[test]
+    fn test_find_members_by_role() {
+        let mut state = make_state();
+        let arguments = json!({ "role": "gain", "caseSensitive": false, "matchWholeString": false, "recurse": true });
+        let result = state.invoke(ROOT_BLOCK_OID, method_ids::FIND_MEMBERS_BY_ROLE, &arguments);
+        assert_eq!(oids(&result), vec![3, 4]);
+        let arguments = json!({ "role": "gain", "caseSensitive": true, "matchWholeString": false, "recurse": true });
+        let result = state.invoke(ROOT_BLOCK_OID, method_ids::FIND_MEMBERS_BY_ROLE, &arguments);
+        assert_eq!(oids(&result), vec![3]);
+        let arguments = json!({ "role": "gain-01", "caseSensitive": true, "matchWholeString": true, "recurse": false });
+        let result = state.invoke(ROOT_BLOCK_OID, method_ids::FIND_MEMBERS_BY_ROLE, &arguments);
+        assert_eq!(oids(&result), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn test_find_members_by_class_id() {
+        let mut state = make_state();
+        let arguments = json!({ "classId": [1, 2], "includeDerived": true, "recurse": true });
+        let result = state.invoke(
+            ROOT_BLOCK_OID,
+            method_ids::FIND_MEMBERS_BY_CLASS_ID,
+            &arguments,
+        );
+        assert_eq!(oids(&result), vec![3, 4]);
+        let arguments = json!({ "classId": [1, 1], "includeDerived": false, "recurse": true });
+        let result = state.invoke(
+            ROOT_BLOCK_OID,
+            method_ids::FIND_MEMBERS_BY_CLASS_ID,
+            &arguments,
+        );
+        assert_eq!(oids(&result), vec![2]);
+    }
+
+    #[test]
+    fn test_user_label_updates_member_descriptor() {
+        let mut state = make_state();
+        state
+            .set_property(3, property_ids::USER_LABEL, json!("Mic 1"))
+            .unwrap();
+        let members = state.get_property(2, property_ids::BLOCK_MEMBERS).unwrap();
+        assert_eq!(members[0]["userLabel"], "Mic 1");
+    }
+
+    #[test]
+    fn test_class_manager() {
+        let mut state = make_state();
+        let oid = state.next_oid();
+        let class_manager = make_class_manager(oid, state.class_registry());
+        state.add_member(class_manager).unwrap();
+
+        let arguments = json!({ "classId": [1, 3, 2], "includeInherited": false });
+        let result = state.invoke(oid, method_ids::GET_CONTROL_CLASS, &arguments);
+        assert_eq!(result.value.unwrap()["fixedRole"], "ClassManager");
+
+        let arguments = json!({ "name": "NcPropertyId", "includeInherited": true });
+        let result = state.invoke(oid, method_ids::GET_DATATYPE, &arguments);
+        assert_eq!(result.value.unwrap()["fields"].as_array().unwrap().len(), 2);
+
+        let arguments = json!({ "name": "NcUnknown", "includeInherited": true });
+        let result = state.invoke(oid, method_ids::GET_DATATYPE, &arguments);
+        assert_eq!(result.status, NcMethodStatus::ParameterError);
+
+        // Vendor-specific classes are published via the class manager
+        let mut notifications = state.subscribe();
+        let descriptor =
+            make_class_descriptor("Gain", GAIN_CLASS_ID, "Gain", None, vec![], vec![], vec![]);
+        state.register_class(descriptor).unwrap();
+        let event = notifications.try_recv().unwrap();
+        assert_eq!(
+            event.property_id,
+            property_ids::CLASS_MANAGER_CONTROL_CLASSES
+        );
+        let arguments = json!({ "classId": GAIN_CLASS_ID, "includeInherited": false });
+        let result = state.invoke(oid, method_ids::GET_CONTROL_CLASS, &arguments);
+        assert_eq!(result.value.unwrap()["name"], "Gain");
+    }
+}
@@ -0,0 +1,973 @@
+// BCP-008 status monitoring of receivers and senders
+// See https://specs.amwa.tv/bcp-008-01/releases/v1.0.0/docs/Overview.html
+// and https://specs.amwa.tv/bcp-008-02/releases/v1.0.0/docs/Overview.html
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use tokio::time::{sleep_until, Instant};
+
+use crate::control_protocol::{
+    class_ids, property_ids, ControlProtocolState, NcElementId, NcMethodResult, NcMethodStatus,
+    NcObject,
+};
+
+// Overall status of a status monitor
+// See https://specs.amwa.tv/bcp-008-01/releases/v1.0.0/docs/Overview.html#ncoverallstatus
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum NcOverallStatus {
+    Inactive = 0,
+    Healthy = 1,
+    PartiallyHealthy = 2,
+    Unhealthy = 3,
+}
+
+// Status of the network links used by a receiver
+// See https://specs.amwa.tv/bcp-008-01/releases/v1.0.0/docs/Overview.html#nclinkstatus
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum NcLinkStatus {
+    AllUp = 1,
+    SomeDown = 2,
+    AllDown = 3,
+}
+
+// Status of the connection of a receiver to its stream(s)
+// See https://specs.amwa.tv/bcp-008-01/releases/v1.0.0/docs/Overview.html#ncconnectionstatus
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum NcConnectionStatus {
+    Inactive = 0,
+    Healthy = 1,
+    PartiallyHealthy = 2,
+    Unhealthy = 3,
+}
+
+// Status of the external synchronization of a receiver
+// See https://specs.amwa.tv/bcp-008-01/releases/v1.0.0/docs/Overview.html#ncsynchronizationstatus
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum NcSynchronizationStatus {
+    NotUsed = 0,
+    Healthy = 1,
+    PartiallyHealthy = 2,
+    Unhealthy = 3,
+}
+
+// Status of the stream(s) received
+// See https://specs.amwa.tv/bcp-008-01/releases/v1.0.0/docs/Overview.html#ncstreamstatus
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum NcStreamStatus {
+    Inactive = 0,
+    Healthy = 1,
+    PartiallyHealthy = 2,
+    Unhealthy = 3,
+}
+
+// Status of the transmission of a sender
+// See https://specs.amwa.tv/bcp-008-02/releases/v1.0.0/docs/Overview.html#nctransmissionstatus
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum NcTransmissionStatus {
+    Inactive = 0,
+    Healthy = 1,
+    PartiallyHealthy = 2,
+    Unhealthy = 3,
+}
+
+// Status of the essence being sent
+// See https://specs.amwa.tv/bcp-008-02/releases/v1.0.0/docs/Overview.html#ncessencestatus
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum NcEssenceStatus {
+    Inactive = 0,
+    Healthy = 1,
+    PartiallyHealthy = 2,
+    Unhealthy = 3,
+}
+
+// The counter sets reported by the GetLostPacketCounters, GetLatePacketCounters and
+// GetTransmissionErrorCounters methods
+pub mod counter_sets {
+    pub const LOST_PACKETS: &str = "lostPackets";
+    pub const LATE_PACKETS: &str = "latePackets";
+    pub const TRANSMISSION_ERRORS: &str = "transmissionErrors";
+}
+
+// The default delay in seconds before improvements in status are reported
+// See https://specs.amwa.tv/bcp-008-01/releases/v1.0.0/docs/Overview.html#status-reporting-delay
+pub const DEFAULT_STATUS_REPORTING_DELAY: u32 = 3;
+
+// Each status domain of a monitor has a status, a message and a transition counter
+struct StatusDomain {
+    status: NcElementId,
+    message: NcElementId,
+    transition_counter: NcElementId,
+}
+
+// Receiver and sender monitors have the same shape, four status domains, the second of which
+// determines whether the monitored resource is active, and the last of which is Inactive while it
+// is not, e.g. the connection and stream statuses of a receiver
+struct StatusMonitorClass {
+    name: &'static str,
+    class_id: &'static [i32],
+    resource_type: &'static str,
+    // In the order in which they contribute the overall status message
+    domains: [StatusDomain; 4],
+    synchronization_source_id: NcElementId,
+    auto_reset_counters_and_messages: NcElementId,
+}
+
+const LINK_DOMAIN: usize = 0;
+const ACTIVITY_DOMAIN: usize = 1;
+const EXTERNAL_SYNCHRONIZATION_DOMAIN: usize = 2;
+const ESSENCE_DOMAIN: usize = 3;
+
+const RECEIVER_MONITOR: StatusMonitorClass = StatusMonitorClass {
+    name: "receiver monitor",
+    class_id: class_ids::NC_RECEIVER_MONITOR,
+    resource_type: "receiver",
+    domains: [
+        StatusDomain {
+            status: property_ids::RECEIVER_MONITOR_LINK_STATUS,
+            message: property_ids::RECEIVER_MONITOR_LINK_STATUS_MESSAGE,
+            transition_counter: property_ids::RECEIVER_MONITOR_LINK_STATUS_TRANSITION_COUNTER,
+        },
+        StatusDomain {
+            status: property_ids::RECEIVER_MONITOR_CONNECTION_STATUS,
+            message: property_ids::RECEIVER_MONITOR_CONNECTION_STATUS_MESSAGE,
+            transition_counter: property_ids::RECEIVER_MONITOR_CONNECTION_STATUS_TRANSITION_COUNTER,
+        },
+        StatusDomain {
+            status: property_ids::RECEIVER_MONITOR_EXTERNAL_SYNCHRONIZATION_STATUS,
+            message: property_ids::RECEIVER_MONITOR_EXTERNAL_SYNCHRONIZATION_STATUS_MESSAGE,
+            transition_counter:
+                property_ids::RECEIVER_MONITOR_EXTERNAL_SYNCHRONIZATION_STATUS_TRANSITION_COUNTER,
+        },
+        StatusDomain {
+            status: property_ids::RECEIVER_MONITOR_STREAM_STATUS,
+            message: property_ids::RECEIVER_MONITOR_STREAM_STATUS_MESSAGE,
+            transition_counter: property_ids::RECEIVER_MONITOR_STREAM_STATUS_TRANSITION_COUNTER,
+        },
+    ],
+    synchronization_source_id: property_ids::RECEIVER_MONITOR_SYNCHRONIZATION_SOURCE_ID,
+    auto_reset_counters_and_messages:
+        property_ids::RECEIVER_MONITOR_AUTO_RESET_COUNTERS_AND_MESSAGES,
+};
+
+const SENDER_MONITOR: StatusMonitorClass = StatusMonitorClass {
+    name: "sender monitor",
+    class_id: class_ids::NC_SENDER_MONITOR,
+    resource_type: "sender",
+    domains: [
+        StatusDomain {
+            status: property_ids::SENDER_MONITOR_LINK_STATUS,
+            message: property_ids::SENDER_MONITOR_LINK_STATUS_MESSAGE,
+            transition_counter: property_ids::SENDER_MONITOR_LINK_STATUS_TRANSITION_COUNTER,
+        },
+        StatusDomain {
+            status: property_ids::SENDER_MONITOR_TRANSMISSION_STATUS,
+            message: property_ids::SENDER_MONITOR_TRANSMISSION_STATUS_MESSAGE,
+            transition_counter: property_ids::SENDER_MONITOR_TRANSMISSION_STATUS_TRANSITION_COUNTER,
+        },
+        StatusDomain {
+            status: property_ids::SENDER_MONITOR_EXTERNAL_SYNCHRONIZATION_STATUS,
+            message: property_ids::SENDER_MONITOR_EXTERNAL_SYNCHRONIZATION_STATUS_MESSAGE,
+            transition_counter:
+                property_ids::SENDER_MONITOR_EXTERNAL_SYNCHRONIZATION_STATUS_TRANSITION_COUNTER,
+        },
+        StatusDomain {
+            status: property_ids::SENDER_MONITOR_ESSENCE_STATUS,
+            message: property_ids::SENDER_MONITOR_ESSENCE_STATUS_MESSAGE,
+            transition_counter: property_ids::SENDER_MONITOR_ESSENCE_STATUS_TRANSITION_COUNTER,
+        },
+    ],
+    synchronization_source_id: property_ids::SENDER_MONITOR_SYNCHRONIZATION_SOURCE_ID,
+    auto_reset_counters_and_messages: property_ids::SENDER_MONITOR_AUTO_RESET_COUNTERS_AND_MESSAGES,
+};
+
+// Construct a touchpoint which links a monitor to an IS-04 resource
+// See https://specs.amwa.tv/ms-05-02/releases/v1.0.0/docs/Framework.html#nctouchpointnmos
+pub fn make_touchpoint_nmos(resource_type: &str, id: &str) -> Value {
+    json!({
+        "contextNamespace": "x-nmos",
+        "resource": {
+            "resourceType": resource_type,
+            "id": id,
+        },
+    })
+}
+
+// Construct a monitor for the IS-04 resource with the specified id, initially inactive
+fn make_status_monitor(
+    class: &StatusMonitorClass,
+    oid: u32,
+    owner: u32,
+    role: &str,
+    user_label: Option<&str>,
+    resource_id: &str,
+) -> NcObject {
+    let mut object = NcObject::new(oid, class.class_id, role, Some(owner), user_label)
+        .with_property(
+            property_ids::TOUCHPOINTS,
+            json!([make_touchpoint_nmos(class.resource_type, resource_id)]),
+            true,
+        )
+        .with_property(
+            property_ids::STATUS_MONITOR_OVERALL_STATUS,
+            json!(NcOverallStatus::Inactive as u16),
+            true,
+        )
+        .with_property(
+            property_ids::STATUS_MONITOR_OVERALL_STATUS_MESSAGE,
+            Value::Null,
+            true,
+        )
+        .with_property(
+            property_ids::STATUS_MONITOR_STATUS_REPORTING_DELAY,
+            json!(DEFAULT_STATUS_REPORTING_DELAY),
+            false,
+        )
+        .with_property(class.synchronization_source_id, Value::Null, true)
+        .with_property(class.auto_reset_counters_and_messages, json!(true), false);
+
+    let initial_statuses = [
+        NcLinkStatus::AllDown as u16,
+        NcConnectionStatus::Inactive as u16,
+        NcSynchronizationStatus::NotUsed as u16,
+        NcStreamStatus::Inactive as u16,
+    ];
+    for (domain, status) in class.domains.iter().zip(initial_statuses) {
+        object = object
+            .with_property(domain.status, json!(status), true)
+            .with_property(domain.message, Value::Null, true)
+            .with_property(domain.transition_counter, json!(0), true);
+    }
+    object
+}
+
+// Construct a receiver monitor for the IS-04 receiver with the specified id
+// See https://specs.amwa.tv/bcp-008-01/releases/v1.0.0/docs/Overview.html#ncreceivermonitor
+pub fn make_receiver_monitor(
+    oid: u32,
+    owner: u32,
+    role: &str,
+    user_label: Option<&str>,
+    receiver_id: &str,
+) -> NcObject {
+    make_status_monitor(&RECEIVER_MONITOR, oid, owner, role, user_label, receiver_id)
+}
+
+// Construct a sender monitor for the IS-04 sender with the specified id
+// See https://specs.amwa.tv/bcp-008-02/releases/v1.0.0/docs/Overview.html#ncsendermonitor
+pub fn make_sender_monitor(
+    oid: u32,
+    owner: u32,
+    role: &str,
+    user_label: Option<&str>,
+    sender_id: &str,
+) -> NcObject {
+    make_status_monitor(&SENDER_MONITOR, oid, owner, role, user_label, sender_id)
+}
+
+fn property_u64(state: &ControlProtocolState, oid: u32, id: NcElementId) -> u64 {
+    state
+        .get_property(oid, id)
+        .ok()
+        .and_then(Value::as_u64)
+        .unwrap_or_default()
+}
+
+// Identify the class of a monitor
+fn status_monitor_class(
+    state: &ControlProtocolState,
+    oid: u32,
+) -> Result<&'static StatusMonitorClass, NcMethodResult> {
+    let object = state.get(oid).ok_or_else(|| {
+        NcMethodResult::error(
+            NcMethodStatus::BadOid,
+            format!("no object with oid: {}", oid),
+        )
+    })?;
+    [&RECEIVER_MONITOR, &SENDER_MONITOR]
+        .into_iter()
+        .find(|class| object.is_instance_of(class.class_id))
+        .ok_or_else(|| {
+            NcMethodResult::error(
+                NcMethodStatus::InvalidRequest,
+                format!("object {} is not a status monitor", oid),
+            )
+        })
+}
+
+fn check_status_monitor_class(
+    state: &ControlProtocolState,
+    oid: u32,
+    expected: &StatusMonitorClass,
+) -> Result<(), NcMethodResult> {
+    if status_monitor_class(state, oid)?.class_id != expected.class_id {
+        return Err(NcMethodResult::error(
+            NcMethodStatus::InvalidRequest,
+            format!("object {} is not a {}", oid, expected.name),
+        ));
+    }
+    Ok(())
+}
+
+fn status_reporting_delay(state: &ControlProtocolState, oid: u32) -> Duration {
+    Duration::from_secs(property_u64(
+        state,
+        oid,
+        property_ids::STATUS_MONITOR_STATUS_REPORTING_DELAY,
+    ))
+}
+
+// Update the status of one domain, counting transitions from a healthy status to a worse one,
+// and then the overall status
+// While the monitored resource is active, an improvement in status is only reported once the
+// status reporting delay has passed without the status changing again, and until then the worse
+// status is still reported, so that a status which keeps recovering is not reported as healthy
+// See https://specs.amwa.tv/bcp-008-01/releases/v1.0.0/docs/Overview.html#status-reporting-delay
+fn set_domain_status(
+    state: &mut ControlProtocolState,
+    oid: u32,
+    domain: usize,
+    status: u16,
+    message: Option<&str>,
+) -> Result<(), NcMethodResult> {
+    let class = status_monitor_class(state, oid)?;
+    let active = property_u64(state, oid, class.domains[ACTIVITY_DOMAIN].status)
+        != NcOverallStatus::Inactive as u64;
+    let domain = &class.domains[domain];
+
+    let previous = property_u64(state, oid, domain.status);
+    let delay = status_reporting_delay(state, oid);
+    let improved =
+        u64::from(status) >= NcOverallStatus::Healthy as u64 && u64::from(status) < previous;
+    if let Some(object) = state.get_mut(oid) {
+        let delayed = object.delayed_properties.remove(&domain.status);
+        object.delayed_properties.remove(&domain.message);
+        if active && improved && !delay.is_zero() {
+            // Reporting the same improvement again doesn't restart the delay
+            let due = match delayed {
+                Some((delayed, due)) if delayed == json!(status) => due,
+                _ => Instant::now() + delay,
+            };
+            object
+                .delayed_properties
+                .insert(domain.status, (json!(status), due));
+            object
+                .delayed_properties
+                .insert(domain.message, (json!(message), due));
+            state.delayed_properties_changed().notify_one();
+            return Ok(());
+        }
+    }
+
+    if previous == NcOverallStatus::Healthy as u64 && u64::from(status) > previous {
+        let transitions = property_u64(state, oid, domain.transition_counter) + 1;
+        state.set_property(oid, domain.transition_counter, json!(transitions))?;
+    }
+    state.set_property(oid, domain.status, json!(status))?;
+    state.set_property(oid, domain.message, json!(message))?;
+
+    update_overall_status(state, oid)
+}
+
+// The overall status is Inactive while the monitored resource is not active, and otherwise the
+// worst of the domain statuses, with the message of the first domain with that status
+// See https://specs.amwa.tv/bcp-008-01/releases/v1.0.0/docs/Overview.html#overall-status
+fn update_overall_status(state: &mut ControlProtocolState, oid: u32) -> Result<(), NcMethodResult> {
+    let class = status_monitor_class(state, oid)?;
+    let activity = &class.domains[ACTIVITY_DOMAIN];
+    let (overall_status, overall_message) =
+        if property_u64(state, oid, activity.status) == NcOverallStatus::Inactive as u64 {
+            (NcOverallStatus::Inactive as u64, Value::Null)
+        } else {
+            // NotUsed (external synchronization) and Inactive (stream or essence) do not degrade
+            // the overall status of an active resource
+            let mut worst = (NcOverallStatus::Healthy as u64, Value::Null);
+            for domain in class.domains.iter() {
+                let status = property_u64(state, oid, domain.status);
+                if status > worst.0 {
+                    let message = state.get_property(oid, domain.message)?.clone();
+                    worst = (status, message);
+                }
+            }
+            worst
+        };
+
+    state.set_property(
+        oid,
+        property_ids::STATUS_MONITOR_OVERALL_STATUS,
+        json!(overall_status),
+    )?;
+    state.set_property(
+        oid,
+        property_ids::STATUS_MONITOR_OVERALL_STATUS_MESSAGE,
+        overall_message,
+    )
+}
+
+// Report the delayed improvements in the statuses of every monitor that are due, returning when
+// the next one is due
+pub fn report_delayed_statuses(state: &mut ControlProtocolState) -> Option<Instant> {
+    let now = Instant::now();
+    let monitors: Vec<u32> = state
+        .objects()
+        .filter(|object| !object.delayed_properties.is_empty())
+        .map(|object| object.oid)
+        .collect();
+    let mut next_due: Option<Instant> = None;
+    for oid in monitors {
+        let Some(object) = state.get_mut(oid) else {
+            continue;
+        };
+        let due: Vec<(NcElementId, Value)> = object
+            .delayed_properties
+            .iter()
+            .filter(|(_, (_, due))| *due <= now)
+            .map(|(id, (value, _))| (*id, value.clone()))
+            .collect();
+        object.delayed_properties.retain(|_, (_, due)| *due > now);
+        next_due = object
+            .delayed_properties
+            .values()
+            .map(|(_, due)| *due)
+            .chain(next_due)
+            .min();
+        if due.is_empty() {
+            continue;
+        }
+        for (id, value) in due {
+            let _ = state.set_property(oid, id, value);
+        }
+        let _ = update_overall_status(state, oid);
+    }
+    next_due
+}
+
+// Report the delayed improvements in the statuses of every monitor when they are due
+// This runs until the task is aborted
+pub async fn run_status_reporting(state: Arc<Mutex<ControlProtocolState>>) {
+    let delayed_properties_changed = state.lock().unwrap().delayed_properties_changed();
+    loop {
+        let next_due = report_delayed_statuses(&mut state.lock().unwrap());
+        let due = async move {
+            match next_due {
+                Some(next_due) => sleep_until(next_due).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            _ = delayed_properties_changed.notified() => {}
+            _ = due => {}
+        }
+    }
+}
+
+// Update the link status of a receiver or sender monitor
+pub fn set_link_status(
+    state: &mut ControlProtocolState,
+    oid: u32,
+    status: NcLinkStatus,
+    message: Option<&str>,
+) -> Result<(), NcMethodResult> {
+    set_domain_status(state, oid, LINK_DOMAIN, status as u16, message)
+}
+
+pub fn set_connection_status(
+    state: &mut ControlProtocolState,
+    oid: u32,
+    status: NcConnectionStatus,
+    message: Option<&str>,
+) -> Result<(), NcMethodResult> {
+    check_status_monitor_class(state, oid, &RECEIVER_MONITOR)?;
+    set_domain_status(state, oid, ACTIVITY_DOMAIN, status as u16, message)
+}
+
+pub fn set_transmission_status(
+    state: &mut ControlProtocolState,
+    oid: u32,
+    status: NcTransmissionStatus,
+    message: Option<&str>,
+) -> Result<(), NcMethodResult> {
+    check_status_monitor_class(state, oid, &SENDER_MONITOR)?;
+    set_domain_status(state, oid, ACTIVITY_DOMAIN, status as u16, message)
+}
+
+// Update the external synchronization status of a receiver or sender monitor, and the id of the
+// current synchronization source
+pub fn set_external_synchronization_status(
+    state: &mut ControlProtocolState,
+    oid: u32,
+    status: NcSynchronizationStatus,
+    message: Option<&str>,
+    synchronization_source_id: Option<&str>,
+) -> Result<(), NcMethodResult> {
+    let class = status_monitor_class(state, oid)?;
+    state.set_property(
+        oid,
+        class.synchronization_source_id,
+        json!(synchronization_source_id),
+    )?;
+    set_domain_status(
+        state,
+        oid,
+        EXTERNAL_SYNCHRONIZATION_DOMAIN,
+        status as u16,
+        message,
+    )
+}
+
+pub fn set_stream_status(
+    state: &mut ControlProtocolState,
+    oid: u32,
+    status: NcStreamStatus,
+    message: Option<&str>,
+) -> Result<(), NcMethodResult> {
+    check_status_monitor_class(state, oid, &RECEIVER_MONITOR)?;
+    set_domain_status(state, oid, ESSENCE_DOMAIN, status as u16, message)
+}
+
+pub fn set_essence_status(
+    state: &mut ControlProtocolState,
+    oid: u32,
+    status: NcEssenceStatus,
+    message: Option<&str>,
+) -> Result<(), NcMethodResult> {
+    check_status_monitor_class(state, oid, &SENDER_MONITOR)?;
+    set_domain_status(state, oid, ESSENCE_DOMAIN, status as u16, message)
+}
+
+fn add_to_counter(
+    state: &mut ControlProtocolState,
+    oid: u32,
+    class: &StatusMonitorClass,
+    counter_set: &str,
+    interface: &str,
+    count: u64,
+) -> Result<(), NcMethodResult> {
+    check_status_monitor_class(state, oid, class)?;
+    if let Some(object) = state.get_mut(oid) {
+        *object
+            .counters
+            .entry(counter_set.to_string())
+            .or_default()
+            .entry(interface.to_string())
+            .or_default() += count;
+    }
+    Ok(())
+}
+
+// Count packets lost on a network interface of a receiver, e.g. "eth0"
+pub fn add_lost_packets(
+    state: &mut ControlProtocolState,
+    oid: u32,
+    interface: &str,
+    count: u64,
+) -> Result<(), NcMethodResult> {
+    let counter_set = counter_sets::LOST_PACKETS;
+    add_to_counter(state, oid, &RECEIVER_MONITOR, counter_set, interface, count)
+}
+
+// Count packets which arrived too late to be used on a network interface of a receiver
+pub fn add_late_packets(
+    state: &mut ControlProtocolState,
+    oid: u32,
+    interface: &str,
+    count: u64,
+) -> Result<(), NcMethodResult> {
+    let counter_set = counter_sets::LATE_PACKETS;
+    add_to_counter(state, oid, &RECEIVER_MONITOR, counter_set, interface, count)
+}
+
+// Count errors transmitting packets on a network interface of a sender
+pub fn add_transmission_errors(
+    state: &mut ControlProtocolState,
+    oid: u32,
+    interface: &str,
+    count: u64,
+) -> Result<(), NcMethodResult> {
+    let counter_set = counter_sets::TRANSMISSION_ERRORS;
+    add_to_counter(state, oid, &SENDER_MONITOR, counter_set, interface, count)
+}
+
+// Get a set of counters as a sequence of NcCounter
+// See https://specs.amwa.tv/bcp-008-01/releases/v1.0.0/docs/Overview.html#nccounter
+pub fn counters(object: &NcObject, counter_set: &str) -> Vec<Value> {
+    object
+        .counters
+        .get(counter_set)
+        .map(|counters| {
+            counters
+                .iter()
+                .map(|(name, value)| json!({ "name": name, "value": value, "description": null }))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// Reset the transition counters, the packet counters and the status messages
+pub fn reset_counters_and_messages(
+    state: &mut ControlProtocolState,
+    oid: u32,
+) -> Result<(), NcMethodResult> {
+    let class = status_monitor_class(state, oid)?;
+    for domain in class.domains.iter() {
+        state.set_property(oid, domain.transition_counter, json!(0))?;
+        state.set_property(oid, domain.message, Value::Null)?;
+    }
+    if let Some(object) = state.get_mut(oid) {
+        object.counters.clear();
+    }
+    update_overall_status(state, oid)
+}
+
+// Called when the monitored receiver or sender is activated or deactivated, to reset the counters
+// and messages if the monitor is configured to do so, and so that the monitor is inactive while
+// the resource is
+// See https://specs.amwa.tv/bcp-008-01/releases/v1.0.0/docs/Overview.html#automatic-reset
+pub fn status_monitor_activated(
+    state: &mut ControlProtocolState,
+    oid: u32,
+    active: bool,
+) -> Result<(), NcMethodResult> {
+    let class = status_monitor_class(state, oid)?;
+    let auto_reset = state
+        .get_property(oid, class.auto_reset_counters_and_messages)?
+        .as_bool()
+        .unwrap_or(true);
+    if active && auto_reset {
+        reset_counters_and_messages(state, oid)?;
+    }
+    if !active {
+        let inactive = NcOverallStatus::Inactive as u16;
+        set_domain_status(state, oid, ACTIVITY_DOMAIN, inactive, None)?;
+        set_domain_status(state, oid, ESSENCE_DOMAIN, inactive, None)?;
+    }
+    Ok(())
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control_protocol::method_ids;
+    use crate::control_protocol_resources::{make_root_block, ROOT_BLOCK_OID};
+
+    const RECEIVER_ID: &str = "db9ecfb5-54bd-4a5c-8f0a-0e3ba6ae2e4f";
+    const SENDER_ID: &str = "3b8d2f3a-8d15-4a0b-9a3e-2a9c2f1d6e70";
+
+    fn make_state() -> (ControlProtocolState, u32) {
+        let mut state = ControlProtocolState::new();
+        state.insert(make_root_block(None));
+        let oid = state
+            .add_member(make_receiver_monitor(
+                state.next_oid(),
+                ROOT_BLOCK_OID,
+                "monitor-01",
+                None,
+                RECEIVER_ID,
+            ))
+            .unwrap();
+        (state, oid)
+    }
+
+    fn property(state: &ControlProtocolState, oid: u32, id: NcElementId) -> Value {
+        state.get_property(oid, id).unwrap().clone()
+    }
+
+    #[test]
+    fn test_make_receiver_monitor() {
+        let (state, oid) = make_state();
+        let touchpoints = property(&state, oid, property_ids::TOUCHPOINTS);
+        assert_eq!(touchpoints[0]["resource"]["resourceType"], "receiver");
+        assert_eq!(touchpoints[0]["resource"]["id"], RECEIVER_ID);
+        assert_eq!(
+            property(&state, oid, property_ids::STATUS_MONITOR_OVERALL_STATUS),
+            NcOverallStatus::Inactive as u16
+        );
+    }
+
+    #[test]
+    fn test_overall_status() {
+        let (mut state, oid) = make_state();
+        set_link_status(&mut state, oid, NcLinkStatus::AllUp, None).unwrap();
+        set_stream_status(&mut state, oid, NcStreamStatus::Healthy, None).unwrap();
+        // still inactive until the connection is active
+        assert_eq!(
+            property(&state, oid, property_ids::STATUS_MONITOR_OVERALL_STATUS),
+            NcOverallStatus::Inactive as u16
+        );
+
+        set_connection_status(&mut state, oid, NcConnectionStatus::Healthy, None).unwrap();
+        assert_eq!(
+            property(&state, oid, property_ids::STATUS_MONITOR_OVERALL_STATUS),
+            NcOverallStatus::Healthy as u16
+        );
+
+        set_link_status(
+            &mut state,
+            oid,
+            NcLinkStatus::SomeDown,
+            Some("eth1 is down"),
+        )
+        .unwrap();
+        set_stream_status(
+            &mut state,
+            oid,
+            NcStreamStatus::Unhealthy,
+            Some("no packets"),
+        )
+        .unwrap();
+        assert_eq!(
+            property(&state, oid, property_ids::STATUS_MONITOR_OVERALL_STATUS),
+            NcOverallStatus::Unhealthy as u16
+        );
+        assert_eq!(
+            property(
+                &state,
+                oid,
+                property_ids::STATUS_MONITOR_OVERALL_STATUS_MESSAGE
+            ),
+            "no packets"
+        );
+        assert_eq!(
+            property(
+                &state,
+                oid,
+                RECEIVER_MONITOR.domains[LINK_DOMAIN].transition_counter
+            ),
+            1
+        );
+        assert_eq!(
+            property(
+                &state,
+                oid,
+                RECEIVER_MONITOR.domains[ESSENCE_DOMAIN].transition_counter
+            ),
+            1
+        );
+
+        // worsening further is not another transition away from healthy
+        set_link_status(&mut state, oid, NcLinkStatus::AllDown, None).unwrap();
+        assert_eq!(
+            property(
+                &state,
+                oid,
+                RECEIVER_MONITOR.domains[LINK_DOMAIN].transition_counter
+            ),
+            1
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_status_reporting_delay() {
+        let (mut state, oid) = make_state();
+        set_link_status(&mut state, oid, NcLinkStatus::AllUp, None).unwrap();
+        set_connection_status(&mut state, oid, NcConnectionStatus::Healthy, None).unwrap();
+        set_stream_status(
+            &mut state,
+            oid,
+            NcStreamStatus::Unhealthy,
+            Some("no packets"),
+        )
+        .unwrap();
+        let state = Arc::new(Mutex::new(state));
+        let reporting = tokio::spawn(run_status_reporting(state.clone()));
+        let overall_status = || {
+            property(
+                &state.lock().unwrap(),
+                oid,
+                property_ids::STATUS_MONITOR_OVERALL_STATUS,
+            )
+        };
+        let set_stream_status = |status, message| {
+            set_stream_status(&mut state.lock().unwrap(), oid, status, message).unwrap();
+        };
+        let advance = |seconds| async move {
+            tokio::time::advance(Duration::from_secs(seconds)).await;
+            tokio::task::yield_now().await;
+        };
+        assert_eq!(overall_status(), NcOverallStatus::Unhealthy as u16);
+
+        // The improvement is delayed, and then cancelled by the status getting worse again
+        set_stream_status(NcStreamStatus::Healthy, None);
+        advance(2).await;
+        assert_eq!(overall_status(), NcOverallStatus::Unhealthy as u16);
+        set_stream_status(NcStreamStatus::Unhealthy, Some("no packets"));
+        advance(2).await;
+        assert_eq!(overall_status(), NcOverallStatus::Unhealthy as u16);
+
+        // Until it has lasted for the default delay of 3 seconds
+        set_stream_status(NcStreamStatus::Healthy, None);
+        advance(2).await;
+        set_stream_status(NcStreamStatus::Healthy, None);
+        assert_eq!(overall_status(), NcOverallStatus::Unhealthy as u16);
+        advance(1).await;
+        assert_eq!(overall_status(), NcOverallStatus::Healthy as u16);
+        assert_eq!(
+            property(
+                &state.lock().unwrap(),
+                oid,
+                property_ids::RECEIVER_MONITOR_STREAM_STATUS_MESSAGE
+            ),
+            Value::Null
+        );
+
+        // Getting worse is reported immediately
+        set_stream_status(NcStreamStatus::PartiallyHealthy, Some("late packets"));
+        assert_eq!(overall_status(), NcOverallStatus::PartiallyHealthy as u16);
+
+        reporting.abort();
+    }
+
+    #[test]
+    fn test_counters_and_auto_reset() {
+        let (mut state, oid) = make_state();
+        add_lost_packets(&mut state, oid, "eth0", 3).unwrap();
+        add_lost_packets(&mut state, oid, "eth0", 2).unwrap();
+        add_late_packets(&mut state, oid, "eth1", 1).unwrap();
+
+        let result = state.invoke(
+            oid,
+            method_ids::RECEIVER_MONITOR_GET_LOST_PACKET_COUNTERS,
+            &json!({}),
+        );
+        assert!(result.is_ok());
+        assert_eq!(
+            result.value.unwrap(),
+            json!([{ "name": "eth0", "value": 5, "description": null }])
+        );
+
+        set_connection_status(&mut state, oid, NcConnectionStatus::Healthy, None).unwrap();
+        set_connection_status(&mut state, oid, NcConnectionStatus::Unhealthy, Some("lost"))
+            .unwrap();
+        assert_eq!(
+            property(
+                &state,
+                oid,
+                RECEIVER_MONITOR.domains[ACTIVITY_DOMAIN].transition_counter
+            ),
+            1
+        );
+
+        // disabling the automatic reset retains the counters across activations
+        let result = state.invoke(
+            oid,
+            method_ids::SET,
+            &json!({
+                "id": property_ids::RECEIVER_MONITOR_AUTO_RESET_COUNTERS_AND_MESSAGES.to_json(),
+                "value": false,
+            }),
+        );
+        assert!(result.is_ok());
+        status_monitor_activated(&mut state, oid, true).unwrap();
+        assert_eq!(
+            property(
+                &state,
+                oid,
+                RECEIVER_MONITOR.domains[ACTIVITY_DOMAIN].transition_counter
+            ),
+            1
+        );
+        assert_eq!(
+            counters(state.get(oid).unwrap(), counter_sets::LATE_PACKETS).len(),
+            1
+        );
+
+        state
+            .set_property(
+                oid,
+                property_ids::RECEIVER_MONITOR_AUTO_RESET_COUNTERS_AND_MESSAGES,
+                json!(true),
+            )
+            .unwrap();
+        status_monitor_activated(&mut state, oid, true).unwrap();
+        assert_eq!(
+            property(
+                &state,
+                oid,
+                RECEIVER_MONITOR.domains[ACTIVITY_DOMAIN].transition_counter
+            ),
+            0
+        );
+        assert_eq!(
+            property(
+                &state,
+                oid,
+                RECEIVER_MONITOR.domains[ACTIVITY_DOMAIN].message
+            ),
+            Value::Null
+        );
+        assert!(counters(state.get(oid).unwrap(), counter_sets::LOST_PACKETS).is_empty());
+    }
+
+    #[test]
+    fn test_sender_monitor() {
+        let mut state = ControlProtocolState::new();
+        state.insert(make_root_block(None));
+        let oid = state
+            .add_member(make_sender_monitor(
+                state.next_oid(),
+                ROOT_BLOCK_OID,
+                "monitor-02",
+                None,
+                SENDER_ID,
+            ))
+            .unwrap();
+        assert_eq!(
+            state.get(oid).unwrap().resource_type(),
+            crate::types::nmos::ResourceType::NcSenderMonitor
+        );
+        let touchpoints = property(&state, oid, property_ids::TOUCHPOINTS);
+        assert_eq!(touchpoints[0]["resource"]["resourceType"], "sender");
+        assert_eq!(touchpoints[0]["resource"]["id"], SENDER_ID);
+
+        // receiver-specific statuses and counters are rejected
+        let result = set_connection_status(&mut state, oid, NcConnectionStatus::Healthy, None);
+        assert_eq!(result.unwrap_err().status, NcMethodStatus::InvalidRequest);
+        assert!(add_lost_packets(&mut state, oid, "eth0", 1).is_err());
+
+        set_link_status(&mut state, oid, NcLinkStatus::AllUp, None).unwrap();
+        set_transmission_status(&mut state, oid, NcTransmissionStatus::Healthy, None).unwrap();
+        set_essence_status(
+            &mut state,
+            oid,
+            NcEssenceStatus::PartiallyHealthy,
+            Some("no input signal"),
+        )
+        .unwrap();
+        assert_eq!(
+            property(&state, oid, property_ids::STATUS_MONITOR_OVERALL_STATUS),
+            NcOverallStatus::PartiallyHealthy as u16
+        );
+        assert_eq!(
+            property(
+                &state,
+                oid,
+                property_ids::SENDER_MONITOR_ESSENCE_STATUS_MESSAGE
+            ),
+            "no input signal"
+        );
+
+        add_transmission_errors(&mut state, oid, "eth0", 7).unwrap();
+        let result = state.invoke(
+            oid,
+            method_ids::SENDER_MONITOR_GET_TRANSMISSION_ERROR_COUNTERS,
+            &json!({}),
+        );
+        assert_eq!(result.value.unwrap()[0]["value"], 7);
+
+        let result = state.invoke(
+            oid,
+            method_ids::SENDER_MONITOR_RESET_COUNTERS_AND_MESSAGES,
+            &json!({}),
+        );
+        assert!(result.is_ok());
+        assert!(counters(state.get(oid).unwrap(), counter_sets::TRANSMISSION_ERRORS).is_empty());
+
+        status_monitor_activated(&mut state, oid, false).unwrap();
+        assert_eq!(
+            property(&state, oid, property_ids::STATUS_MONITOR_OVERALL_STATUS),
+            NcOverallStatus::Inactive as u16
+        );
+    }
+}
@@ -0,0 +1,355 @@
+// Construct the standard objects of an MS-05-02 control model
+// See https://specs.amwa.tv/ms-05-02/releases/v1.0.0/docs/Blocks.html
+
+use serde_json::{json, Value};
+
+use crate::control_protocol::{class_ids, property_ids, NcObject};
+use crate::control_protocol_classes::ClassRegistry;
+
+// The root block always has oid 1 and role "root"
+// See https://specs.amwa.tv/ms-05-02/releases/v1.0.0/docs/Blocks.html#root-block
+pub const ROOT_BLOCK_OID: u32 = 1;
+pub const ROOT_BLOCK_ROLE: &str = "root";
+
+// Construct a block, which is initially enabled and has no members
+// See https://specs.amwa.tv/ms-05-02/releases/v1.0.0/docs/Framework.html#ncblock
+pub fn make_block(oid: u32, owner: Option<u32>, role: &str, user_label: Option<&str>) -> NcObject {
+    NcObject::new(oid, class_ids::NC_BLOCK, role, owner, user_label)
+        .with_property(property_ids::BLOCK_ENABLED, json!(true), true)
+        .with_property(property_ids::BLOCK_MEMBERS, json!([]), true)
+}
+
+// Construct the root block
+pub fn make_root_block(user_label: Option<&str>) -> NcObject {
+    make_block(ROOT_BLOCK_OID, None, ROOT_BLOCK_ROLE, user_label)
+}
+
+// Construct the block member descriptor for an object
+// See https://specs.amwa.tv/ms-05-02/releases/v1.0.0/docs/Framework.html#ncblockmemberdescriptor
+pub fn make_member_descriptor(object: &NcObject) -> Value {
+    json!({
+        "description": object.user_label().unwrap_or_else(|| object.role()),
+        "role": object.role(),
+        "oid": object.oid,
+        "constantOid": true,
+        "classId": object.class_id,
+        "userLabel": object.user_label(),
+        "owner": object.owner(),
+    })
+}
+
+// Construct the class manager, which has the fixed role "ClassManager" in the root block
+// See https://specs.amwa.tv/ms-05-02/releases/v1.0.0/docs/Managers.html#class-manager
+pub fn make_class_manager(oid: u32, classes: &ClassRegistry) -> NcObject {
+    NcObject::new(
+        oid,
+        class_ids::NC_CLASS_MANAGER,
+        "ClassManager",
+        Some(ROOT_BLOCK_OID),
+        Some("Class manager"),
+    )
+    .with_property(
+        property_ids::CLASS_MANAGER_CONTROL_CLASSES,
+        json!(classes.control_classes()),
+        true,
+    )
+    .with_property(
+        property_ids::CLASS_MANAGER_DATATYPES,
+        json!(classes.datatypes()),
+        true,
+    )
+}
+
+// The version of MS-05-02 implemented by the device manager
+pub const NC_VERSION: &str = "v1.0.0";
+
+// BCP-002-02 Asset Distinguishing Information tags, used to derive the device manager identity
+// See https://specs.amwa.tv/bcp-002-02/releases/v1.0.0/docs/Asset_Distinguishing_Information.html
+pub mod asset_tags {
+    pub const MANUFACTURER: &str = "urn:x-nmos:tag:asset:manufacturer/v1.0";
+    pub const PRODUCT: &str = "urn:x-nmos:tag:asset:product/v1.0";
+    pub const INSTANCE_ID: &str = "urn:x-nmos:tag:asset:instance-id/v1.0";
+    pub const FUNCTION: &str = "urn:x-nmos:tag:asset:function/v1.0";
+}
+
+// Device generic operational state
+// See https://specs.amwa.tv/ms-05-02/releases/v1.0.0/docs/Framework.html#ncdevicegenericstate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NcDeviceGenericState {
+    Unknown = 0,
+    NormalOperation = 1,
+    Initializing = 2,
+    Updating = 3,
+    LicensingError = 4,
+    InternalError = 5,
+}
+
+// Reason for the most recent reset
+// See https://specs.amwa.tv/ms-05-02/releases/v1.0.0/docs/Framework.html#ncresetcause
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NcResetCause {
+    Unknown = 0,
+    PowerOn = 1,
+    InternalError = 2,
+    Upgrade = 3,
+    ControllerRequest = 4,
+    ManualReset = 5,
+}
+
+// Construct the device operational state
+pub fn make_operational_state(
+    generic: NcDeviceGenericState,
+    device_specific_details: Option<&str>,
+) -> Value {
+    json!({
+        "generic": generic as u16,
+        "deviceSpecificDetails": device_specific_details,
+    })
+}
+
+// Get the first value of a tag of an IS-04 resource
+fn first_tag<'a>(resource: &'a Value, tag: &str) -> Option<&'a str> {
+    resource["tags"][tag]
+        .as_array()
+        .and_then(|values| values.first())
+        .and_then(Value::as_str)
+}
+
+// Construct the device manager, which has the fixed role "DeviceManager" in the root block,
+// deriving its identity from the IS-04 Node and Device, so that the IS-04 and IS-12 views of the
+// device are consistent
+// See https://specs.amwa.tv/ms-05-02/releases/v1.0.0/docs/Managers.html#device-manager
+pub fn make_device_manager(oid: u32, node: &Value, device: &Value) -> NcObject {
+    let tag = |tag| first_tag(device, tag).or_else(|| first_tag(node, tag));
+
+    let manufacturer = json!({
+        "name": tag(asset_tags::MANUFACTURER).unwrap_or_default(),
+        "organizationId": null,
+        "website": null,
+    });
+    let product_name = tag(asset_tags::PRODUCT).unwrap_or_default();
+    let product = json!({
+        "name": product_name,
+        "key": product_name,
+        "revisionLevel": "",
+        "brandName": null,
+        "uuid": null,
+        "description": device["description"].as_str(),
+    });
+    let device_name = device["label"]
+        .as_str()
+        .or_else(|| node["label"].as_str())
+        .filter(|label| !label.is_empty());
+
+    NcObject::new(
+        oid,
+        class_ids::NC_DEVICE_MANAGER,
+        "DeviceManager",
+        Some(ROOT_BLOCK_OID),
+        Some("Device manager"),
+    )
+    .with_property(
+        property_ids::DEVICE_MANAGER_NC_VERSION,
+        json!(NC_VERSION),
+        true,
+    )
+    .with_property(
+        property_ids::DEVICE_MANAGER_MANUFACTURER,
+        manufacturer,
+        true,
+    )
+    .with_property(property_ids::DEVICE_MANAGER_PRODUCT, product, true)
+    .with_property(
+        property_ids::DEVICE_MANAGER_SERIAL_NUMBER,
+        json!(tag(asset_tags::INSTANCE_ID).unwrap_or_default()),
+        true,
+    )
+    .with_property(
+        property_ids::DEVICE_MANAGER_USER_INVENTORY_CODE,
+        Value::Null,
+        false,
+    )
+    .with_property(
+        property_ids::DEVICE_MANAGER_DEVICE_NAME,
+        json!(device_name),
+        false,
+    )
+    .with_property(
+        property_ids::DEVICE_MANAGER_DEVICE_ROLE,
+        json!(tag(asset_tags::FUNCTION)),
+        false,
+    )
+    .with_property(
+        property_ids::DEVICE_MANAGER_OPERATIONAL_STATE,
+        make_operational_state(NcDeviceGenericState::NormalOperation, None),
+        true,
+    )
+    .with_property(
+        property_ids::DEVICE_MANAGER_RESET_CAUSE,
+        json!(NcResetCause::PowerOn as u16),
+        true,
+    )
+    .with_property(property_ids::DEVICE_MANAGER_MESSAGE, Value::Null, true)
+}
+
+// Update the IS-04 Device from the controller-settable device manager properties, returning
+// whether it was modified, in which case the caller should bump its version
+pub fn update_device_from_device_manager(device_manager: &NcObject, device: &mut Value) -> bool {
+    let mut modified = false;
+
+    if let Some(device_name) = device_manager
+        .property(property_ids::DEVICE_MANAGER_DEVICE_NAME)
+        .and_then(Value::as_str)
+    {
+        if device["label"] != device_name {
+            device["label"] = json!(device_name);
+            modified = true;
+        }
+    }
+
+    let device_role = device_manager
+        .property(property_ids::DEVICE_MANAGER_DEVICE_ROLE)
+        .and_then(Value::as_str);
+    if first_tag(device, asset_tags::FUNCTION) != device_role {
+        if !device["tags"].is_object() {
+            device["tags"] = json!({});
+        }
+        match device_role {
+            Some(device_role) => device["tags"][asset_tags::FUNCTION] = json!([device_role]),
+            None => {
+                if let Some(tags) = device["tags"].as_object_mut() {
+                    tags.remove(asset_tags::FUNCTION);
+                }
+            }
+        }
+        modified = true;
+    }
+
+    modified
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control_protocol::{method_ids, ControlProtocolState, NcElementId, NcMethodStatus};
+
+    fn make_device() -> Value {
+        json!({
+            "id": "58f6b536-ca4c-43fd-880a-9df2501fc125",
+            "label": "Stagebox 1",
+            "description": "Stagebox",
+            "tags": {
+                asset_tags::MANUFACTURER: ["Example Ltd"],
+                asset_tags::PRODUCT: ["SB-40"],
+                asset_tags::INSTANCE_ID: ["SN-0042"],
+            },
+        })
+    }
+
+    fn make_state() -> ControlProtocolState {
+        let mut state = ControlProtocolState::new();
+        state.insert(make_root_block(None));
+        let node = json!({ "label": "node", "tags": { asset_tags::FUNCTION: ["Stage left"] } });
+        state
+            .add_member(make_device_manager(state.next_oid(), &node, &make_device()))
+            .unwrap();
+        state
+    }
+
+    #[test]
+    fn test_make_device_manager() {
+        let state = make_state();
+        let device_manager = state.find_role_path(&["root", "DeviceManager"]).unwrap();
+        let property = |id| device_manager.property(id).unwrap();
+
+        assert_eq!(property(property_ids::DEVICE_MANAGER_NC_VERSION), "v1.0.0");
+        assert_eq!(
+            property(property_ids::DEVICE_MANAGER_MANUFACTURER)["name"],
+            "Example Ltd"
+        );
+        assert_eq!(
+            property(property_ids::DEVICE_MANAGER_PRODUCT)["name"],
+            "SB-40"
+        );
+        assert_eq!(
+            property(property_ids::DEVICE_MANAGER_SERIAL_NUMBER),
+            "SN-0042"
+        );
+        assert_eq!(
+            property(property_ids::DEVICE_MANAGER_DEVICE_NAME),
+            "Stagebox 1"
+        );
+        assert_eq!(
+            property(property_ids::DEVICE_MANAGER_DEVICE_ROLE),
+            "Stage left"
+        );
+        assert_eq!(
+            property(property_ids::DEVICE_MANAGER_OPERATIONAL_STATE)["generic"],
+            1
+        );
+
+        // Only the fields of NcBlockMemberDescriptor, and those of NcDescriptor
+        let descriptor = make_member_descriptor(device_manager);
+        let mut fields: Vec<&str> = descriptor
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        fields.sort_unstable();
+        assert_eq!(
+            fields,
+            [
+                "classId",
+                "constantOid",
+                "description",
+                "oid",
+                "owner",
+                "role",
+                "userLabel"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_set_device_manager_properties() {
+        let mut state = make_state();
+        let oid = state
+            .find_role_path(&["root", "DeviceManager"])
+            .unwrap()
+            .oid;
+        let mut notifications = state.subscribe();
+
+        let set = |id: NcElementId, value| json!({ "id": id.to_json(), "value": value });
+        let result = state.invoke(
+            oid,
+            method_ids::SET,
+            &set(
+                property_ids::DEVICE_MANAGER_DEVICE_NAME,
+                json!("Stagebox 2"),
+            ),
+        );
+        assert!(result.is_ok());
+        assert_eq!(notifications.try_recv().unwrap().value, "Stagebox 2");
+
+        let result = state.invoke(
+            oid,
+            method_ids::SET,
+            &set(property_ids::DEVICE_MANAGER_SERIAL_NUMBER, json!("SN-0043")),
+        );
+        assert_eq!(result.status, NcMethodStatus::Readonly);
+
+        let mut device = make_device();
+        assert!(update_device_from_device_manager(
+            state.get(oid).unwrap(),
+            &mut device
+        ));
+        assert_eq!(device["label"], "Stagebox 2");
+        assert_eq!(device["tags"][asset_tags::FUNCTION], json!(["Stage left"]));
+        assert!(!update_device_from_device_manager(
+            state.get(oid).unwrap(),
+            &mut device
+        ));
+    }
+}
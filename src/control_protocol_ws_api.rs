@@ -0,0 +1,302 @@
+// The IS-12 NMOS Control Protocol WebSocket endpoint
+// See https://specs.amwa.tv/is-12/releases/v1.0.0/docs/Transport_and_message_encoding.html
+// and https://specs.amwa.tv/is-12/releases/v1.0.0/docs/Protocol_messaging.html
+
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use slog::{info, warn, Logger};
+use tokio::sync::broadcast::error::RecvError;
+use warp::http::Method;
+use warp::reply::Response;
+use warp::ws::{Message, WebSocket};
+use warp::{Filter, Rejection, Reply};
+
+use crate::api_methods::{with_allowed_methods, RouteMethods};
+use crate::control_protocol::{ControlProtocolState, NcElementId, NcMethodResult, NcMethodStatus};
+use crate::header_policy::{make_header_policy, with_header_policy};
+use crate::metrics::{with_request_metrics, Metrics};
+use crate::server_timing::with_server_timing;
+use crate::settings::Settings;
+
+// IS-12 message types
+// See https://specs.amwa.tv/is-12/releases/v1.0.0/docs/Protocol_messaging.html#message-types
+pub mod message_types {
+    pub const COMMAND: u8 = 0;
+    pub const COMMAND_RESPONSE: u8 = 1;
+    pub const NOTIFICATION: u8 = 2;
+    pub const SUBSCRIPTION: u8 = 3;
+    pub const SUBSCRIPTION_RESPONSE: u8 = 4;
+    pub const ERROR: u8 = 5;
+}
+
+// The device control type to advertise in the IS-04 Device "controls"
+pub const CONTROL_TYPE: &str = "urn:x-nmos:control:ncp/v1.0";
+
+// Construct the IS-04 Device "controls" entry for the IS-12 endpoint, e.g. "ws://host:port/x-nmos/ncp/v1.0"
+pub fn make_control_protocol_control(href: &str) -> Value {
+    json!({
+        "href": href,
+        "type": CONTROL_TYPE,
+    })
+}
+
+fn make_error_message(status: NcMethodStatus, error_message: &str) -> Value {
+    json!({
+        "messageType": message_types::ERROR,
+        "status": status as u16,
+        "errorMessage": error_message,
+    })
+}
+
+// Handle a Command message, invoking each command in turn
+fn handle_commands(state: &Mutex<ControlProtocolState>, commands: &[Value]) -> Value {
+    let mut state = state.lock().unwrap();
+    let responses: Vec<Value> = commands
+        .iter()
+        .map(|command| {
+            let handle = command.get("handle").cloned().unwrap_or(Value::Null);
+            let oid = command
+                .get("oid")
+                .and_then(Value::as_u64)
+                .and_then(|oid| u32::try_from(oid).ok());
+            let method_id = command.get("methodId").and_then(NcElementId::from_json);
+            let result = match (oid, method_id) {
+                (Some(oid), Some(method_id)) => {
+                    let arguments = command
+                        .get("arguments")
+                        .cloned()
+                        .unwrap_or_else(|| json!({}));
+                    state.invoke(oid, method_id, &arguments)
+                }
+                _ => NcMethodResult::error(
+                    NcMethodStatus::BadCommandFormat,
+                    "command must have an oid and methodId",
+                ),
+            };
+            json!({ "handle": handle, "result": result.to_json() })
+        })
+        .collect();
+
+    json!({
+        "messageType": message_types::COMMAND_RESPONSE,
+        "responses": responses,
+    })
+}
+
+// Handle a message received from a controller, updating the connection's subscriptions, and
+// returning the message to send in reply
+pub fn handle_message(
+    state: &Mutex<ControlProtocolState>,
+    subscriptions: &mut BTreeSet<u32>,
+    message: &str,
+) -> Value {
+    let message: Value = match serde_json::from_str(message) {
+        Ok(message) => message,
+        Err(e) => return make_error_message(NcMethodStatus::BadCommandFormat, &e.to_string()),
+    };
+
+    let message_type = message.get("messageType").and_then(Value::as_u64);
+    match message_type.and_then(|message_type| u8::try_from(message_type).ok()) {
+        Some(message_types::COMMAND) => match message.get("commands").and_then(Value::as_array) {
+            Some(commands) => handle_commands(state, commands),
+            None => make_error_message(NcMethodStatus::BadCommandFormat, "missing commands"),
+        },
+        Some(message_types::SUBSCRIPTION) => {
+            match message.get("subscriptions").and_then(Value::as_array) {
+                Some(oids) => {
+                    // Each subscription message replaces the previous subscriptions
+                    // See https://specs.amwa.tv/is-12/releases/v1.0.0/docs/Protocol_messaging.html#subscription-message-type
+                    let state = state.lock().unwrap();
+                    *subscriptions = oids
+                        .iter()
+                        .filter_map(Value::as_u64)
+                        .filter_map(|oid| u32::try_from(oid).ok())
+                        .filter(|oid| state.get(*oid).is_some())
+                        .collect();
+                    json!({
+                        "messageType": message_types::SUBSCRIPTION_RESPONSE,
+                        "subscriptions": subscriptions.iter().collect::<Vec<_>>(),
+                    })
+                }
+                None => {
+                    make_error_message(NcMethodStatus::BadCommandFormat, "missing subscriptions")
+                }
+            }
+        }
+        _ => make_error_message(NcMethodStatus::BadCommandFormat, "unexpected messageType"),
+    }
+}
+
+// Serve one controller connection, replying to its messages and forwarding the property changed
+// notifications for the objects to which it is subscribed
+async fn handle_connection(
+    websocket: WebSocket,
+    state: Arc<Mutex<ControlProtocolState>>,
+    gate: Logger,
+) {
+    let (mut sender, mut receiver) = websocket.split();
+    let mut notifications = state.lock().unwrap().subscribe();
+    let mut subscriptions = BTreeSet::new();
+
+    info!(gate, "Control protocol connection opened");
+
+    loop {
+        let reply = tokio::select! {
+            message = receiver.next() => match message {
+                Some(Ok(message)) if message.is_text() => {
+                    handle_message(&state, &mut subscriptions, message.to_str().unwrap_or_default())
+                }
+                Some(Ok(message)) if message.is_close() => break,
+                Some(Ok(_)) => continue,
+                Some(Err(e)) => {
+                    warn!(gate, "Control protocol connection error: {}", e);
+                    break;
+                }
+                None => break,
+            },
+            notification = notifications.recv() => match notification {
+                Ok(event) if subscriptions.contains(&event.oid) => json!({
+                    "messageType": message_types::NOTIFICATION,
+                    "notifications": [event.to_json()],
+                }),
+                Ok(_) => continue,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(gate, "Control protocol connection missed {} notifications", skipped);
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            },
+        };
+
+        if let Err(e) = sender.send(Message::text(reply.to_string())).await {
+            warn!(gate, "Control protocol connection error: {}", e);
+            break;
+        }
+    }
+
+    info!(gate, "Control protocol connection closed");
+}
+
+const CONTROL_PROTOCOL_API_METHODS: &[RouteMethods] = &[("/x-nmos/ncp/v1.0", &[Method::GET])];
+
+// Make the route for the IS-12 WebSocket endpoint, at /x-nmos/ncp/v1.0
+// The timing and metrics of a request cover the WebSocket handshake, not the connection
+pub fn make_control_protocol_ws_api(
+    state: Arc<Mutex<ControlProtocolState>>,
+    settings: &Settings,
+    metrics: Option<Arc<Metrics>>,
+    gate: Logger,
+) -> impl Filter<Extract = (Response,), Error = Rejection> + Clone {
+    let connection_gate = gate.clone();
+    let api =
+        warp::path!("x-nmos" / "ncp" / "v1.0")
+            .and(warp::ws())
+            .map(move |ws: warp::ws::Ws| {
+                let state = state.clone();
+                let gate = connection_gate.clone();
+                ws.on_upgrade(move |websocket| handle_connection(websocket, state, gate))
+                    .into_response()
+            });
+    let api = with_header_policy(
+        with_allowed_methods(api, CONTROL_PROTOCOL_API_METHODS),
+        make_header_policy(settings, "ncp"),
+    );
+    with_server_timing(with_request_metrics(api, "ncp", metrics), "ncp", gate)
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control_protocol::{class_ids, NcObject};
+
+    fn make_state() -> Mutex<ControlProtocolState> {
+        let mut state = ControlProtocolState::new();
+        state.insert(NcObject::new(
+            1,
+            class_ids::NC_BLOCK,
+            "root",
+            None,
+            Some("Root"),
+        ));
+        Mutex::new(state)
+    }
+
+    #[test]
+    fn test_command() {
+        let state = make_state();
+        let mut subscriptions = BTreeSet::new();
+        let message = json!({
+            "messageType": 0,
+            "commands": [
+                { "handle": 1, "oid": 1, "methodId": { "level": 1, "index": 1 }, "arguments": { "id": { "level": 1, "index": 5 } } },
+                { "handle": 2, "oid": 7, "methodId": { "level": 1, "index": 1 }, "arguments": { "id": { "level": 1, "index": 5 } } },
+            ]
+        });
+        let reply = handle_message(&state, &mut subscriptions, &message.to_string());
+        assert_eq!(
+            reply,
+            json!({
+                "messageType": 1,
+                "responses": [
+                    { "handle": 1, "result": { "status": 200, "value": "root" } },
+                    { "handle": 2, "result": { "status": 404, "errorMessage": "no object with oid: 7" } },
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_subscription() {
+        let state = make_state();
+        let mut subscriptions = BTreeSet::new();
+        let message = json!({ "messageType": 3, "subscriptions": [1, 7] });
+        let reply = handle_message(&state, &mut subscriptions, &message.to_string());
+        assert_eq!(reply, json!({ "messageType": 4, "subscriptions": [1] }));
+        assert!(subscriptions.contains(&1));
+    }
+
+    #[tokio::test]
+    async fn test_ws_api_headers() {
+        let gate = Logger::root(slog::Discard, slog::o!());
+        let metrics = Arc::new(Metrics::new().unwrap());
+        let api = make_control_protocol_ws_api(
+            Arc::new(make_state()),
+            &Settings::default(),
+            Some(metrics.clone()),
+            gate,
+        );
+
+        let response = warp::test::request()
+            .path("/x-nmos/ncp/v1.0")
+            .header("connection", "upgrade")
+            .header("upgrade", "websocket")
+            .header("sec-websocket-version", "13")
+            .header("sec-websocket-key", "dGhlIHNhbXBsZSBub25jZQ==")
+            .reply(&api)
+            .await;
+        assert_eq!(response.status(), 101);
+        assert!(response.headers().contains_key("server-timing"));
+        assert_eq!(response.headers()["x-content-type-options"], "nosniff");
+
+        let response = warp::test::request()
+            .method("OPTIONS")
+            .path("/x-nmos/ncp/v1.0")
+            .reply(&api)
+            .await;
+        assert_eq!(response.headers()["allow"], "GET, HEAD, OPTIONS");
+        assert_eq!(metrics.requests.with_label_values(&["ncp", "101"]).get(), 1);
+    }
+
+    #[test]
+    fn test_bad_message() {
+        let state = make_state();
+        let mut subscriptions = BTreeSet::new();
+        let reply = handle_message(&state, &mut subscriptions, "not json");
+        assert_eq!(reply["messageType"], 5);
+        assert_eq!(reply["status"], 400);
+    }
+}
@@ -0,0 +1,566 @@
+// Serve static files from a filesystem root, e.g. for the admin UI
+//
+// Requests can only reach regular files inside the root: ".." and hidden segments, encoded
+// separators and symlinks that lead outside the root are all rejected. Responses can be validated
+// with ETag/If-None-Match or Last-Modified/If-Modified-Since, and precompressed ".br" or ".gz"
+// variants are served to clients that accept them.
+//
+// The same files can instead be embedded in the binary and served from memory, with the same
+// checks, e.g. so the admin UI doesn't need to be deployed alongside the binary.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use percent_encoding::percent_decode_str;
+use slog::{debug, Logger};
+use warp::filters::BoxedFilter;
+use warp::http::header::{self, HeaderMap, HeaderValue};
+use warp::http::{Method, Response, StatusCode};
+use warp::hyper::Body;
+use warp::Filter;
+
+// Determine the Content-Type of a file from its path relative to the root, or None if the file
+// must not be served
+pub type ContentTypeHandler = Arc<dyn Fn(&str) -> Option<&'static str> + Send + Sync>;
+
+// Only serve files with the specified extensions, e.g. "js", with the corresponding content types
+pub fn make_relative_path_content_type_handler(
+    valid_extensions: HashMap<&'static str, &'static str>,
+) -> ContentTypeHandler {
+    Arc::new(move |relative_path| {
+        let (_, extension) = relative_path.rsplit_once('.')?;
+        valid_extensions.get(extension).copied()
+    })
+}
+
+// Define how paths that don't match a file are handled
+#[derive(Debug, Clone, Default)]
+pub struct FilesystemRouteOptions {
+    // The file served for paths without an extension that don't match a file, e.g. "index.html"
+    // for the client-side routes of a single-page application
+    pub fallback: Option<String>,
+}
+
+// The precompressed variants that may be served, most preferred first
+const ENCODINGS: &[(&str, &str)] = &[("br", "br"), ("gzip", "gz")];
+
+// A file to be served
+#[derive(Debug)]
+pub struct StaticFile {
+    pub content_type: &'static str,
+    pub content_encoding: Option<&'static str>,
+    // Embedded files have no modification time
+    pub last_modified: Option<SystemTime>,
+    pub etag: String,
+    pub contents: Vec<u8>,
+}
+
+// Reject paths that could escape the root or that identify hidden files, returning the decoded
+// relative path
+fn decode_relative_path(tail: &str) -> Option<String> {
+    let lowercase = tail.to_ascii_lowercase();
+    if ["%2f", "%5c", "%00", "\\"]
+        .iter()
+        .any(|encoded| lowercase.contains(encoded))
+    {
+        return None;
+    }
+    let decoded = percent_decode_str(tail).decode_utf8().ok()?;
+    let segments = decoded.split('/').collect::<Vec<_>>();
+    let valid = segments.iter().enumerate().all(|(index, segment)| {
+        // Only the last segment may be empty, i.e. a trailing slash
+        (!segment.is_empty() || index + 1 == segments.len()) && !segment.starts_with('.')
+    });
+    valid.then(|| decoded.into_owned())
+}
+
+// Resolve a relative path to a regular file that is still inside the root once any symlinks have
+// been followed
+async fn resolve_file(root: &Path, relative_path: &str) -> Option<(PathBuf, std::fs::Metadata)> {
+    let path = tokio::fs::canonicalize(root.join(relative_path))
+        .await
+        .ok()?;
+    if !path.starts_with(root) {
+        return None;
+    }
+    let metadata = tokio::fs::metadata(&path).await.ok()?;
+    metadata.is_file().then_some((path, metadata))
+}
+
+// Determine which content codings the client accepts
+fn accepted_encodings(headers: &HeaderMap) -> Vec<String> {
+    let accept_encoding = headers
+        .get(header::ACCEPT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    accept_encoding
+        .split(',')
+        .filter_map(|coding| {
+            let mut params = coding.split(';').map(str::trim);
+            let coding = params.next()?.to_ascii_lowercase();
+            let q = params
+                .find_map(|param| param.strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            (q > 0.0).then_some(coding)
+        })
+        .collect()
+}
+
+fn make_etag(metadata: &std::fs::Metadata, encoding: Option<&str>) -> String {
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default();
+    match encoding {
+        Some(encoding) => format!(
+            "\"{:x}-{:x}-{}\"",
+            metadata.len(),
+            modified.as_nanos(),
+            encoding
+        ),
+        None => format!("\"{:x}-{:x}\"", metadata.len(), modified.as_nanos()),
+    }
+}
+
+// Determine the relative paths that may be served for the request path, in order, i.e. the file
+// itself and then the fallback
+fn relative_paths(tail: &str, options: &FilesystemRouteOptions) -> Option<Vec<String>> {
+    let mut relative_path = decode_relative_path(tail)?;
+    // Only paths that don't look like files are client-side routes
+    let is_route = !relative_path
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .contains('.');
+    if relative_path.is_empty() || relative_path.ends_with('/') {
+        relative_path.push_str("index.html");
+    }
+    let mut relative_paths = vec![relative_path];
+    match &options.fallback {
+        Some(fallback) if is_route => relative_paths.push(fallback.clone()),
+        _ => {}
+    }
+    Some(relative_paths)
+}
+
+// Find the file for the request path, or its fallback, preferring a precompressed variant
+async fn find_file(
+    root: &Path,
+    tail: &str,
+    handler: &ContentTypeHandler,
+    options: &FilesystemRouteOptions,
+    headers: &HeaderMap,
+) -> Option<StaticFile> {
+    let mut found = None;
+    for relative_path in relative_paths(tail, options)? {
+        if let Some((path, metadata)) = resolve_file(root, &relative_path).await {
+            found = Some((relative_path, path, metadata));
+            break;
+        }
+    }
+    let (relative_path, path, metadata) = found?;
+    let content_type = handler(&relative_path)?;
+
+    let accepted = accepted_encodings(headers);
+    for (encoding, extension) in ENCODINGS {
+        if !accepted.iter().any(|accepted| accepted == encoding) {
+            continue;
+        }
+        let variant = format!("{}.{}", relative_path, extension);
+        if let Some((variant_path, variant_metadata)) = resolve_file(root, &variant).await {
+            if let Ok(contents) = tokio::fs::read(&variant_path).await {
+                return Some(StaticFile {
+                    content_type,
+                    content_encoding: Some(encoding),
+                    last_modified: metadata.modified().ok(),
+                    etag: make_etag(&variant_metadata, Some(encoding)),
+                    contents,
+                });
+            }
+        }
+    }
+
+    let contents = tokio::fs::read(&path).await.ok()?;
+    Some(StaticFile {
+        content_type,
+        content_encoding: None,
+        last_modified: metadata.modified().ok(),
+        etag: make_etag(&metadata, None),
+        contents,
+    })
+}
+
+// Determine whether the client's cached copy is still current
+// See https://www.rfc-editor.org/rfc/rfc9110#section-13.2.2
+fn is_not_modified(headers: &HeaderMap, file: &StaticFile) -> bool {
+    if let Some(if_none_match) = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+    {
+        return if_none_match
+            .split(',')
+            .map(str::trim)
+            .any(|etag| etag == "*" || etag.strip_prefix("W/").unwrap_or(etag) == file.etag);
+    }
+    headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|since| httpdate::parse_http_date(since).ok())
+        .and_then(|since| since.duration_since(UNIX_EPOCH).ok())
+        .zip(
+            file.last_modified
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok()),
+        )
+        .is_some_and(|(since, modified)| modified.as_secs() <= since.as_secs())
+}
+
+fn make_file_response(method: &Method, headers: &HeaderMap, file: StaticFile) -> Response<Body> {
+    let not_modified = is_not_modified(headers, &file);
+    let mut response = Response::builder()
+        .header(header::ETAG, &file.etag)
+        .header(header::CACHE_CONTROL, "no-cache")
+        .header(header::VARY, "Accept-Encoding");
+    if let Some(last_modified) = file.last_modified {
+        response = response.header(
+            header::LAST_MODIFIED,
+            httpdate::fmt_http_date(last_modified),
+        );
+    }
+    if not_modified {
+        return response
+            .status(StatusCode::NOT_MODIFIED)
+            .body(Body::empty())
+            .unwrap();
+    }
+    response = response
+        .header(header::CONTENT_TYPE, file.content_type)
+        .header(header::CONTENT_LENGTH, file.contents.len());
+    if let Some(content_encoding) = file.content_encoding {
+        response = response.header(header::CONTENT_ENCODING, content_encoding);
+    }
+    let body = if method == Method::HEAD {
+        Body::empty()
+    } else {
+        Body::from(file.contents)
+    };
+    response.status(StatusCode::OK).body(body).unwrap()
+}
+
+// Make the route for the files under the filesystem root, for GET and HEAD requests
+pub fn make_filesystem_route(
+    filesystem_root: &str,
+    handler: ContentTypeHandler,
+    options: FilesystemRouteOptions,
+    gate: Logger,
+) -> BoxedFilter<(Response<Body>,)> {
+    // Symlinks are resolved before checking that a file is inside the root, so the root must be
+    // resolved too
+    let root = Arc::new(
+        std::fs::canonicalize(filesystem_root).unwrap_or_else(|_| PathBuf::from(filesystem_root)),
+    );
+    let options = Arc::new(options);
+
+    warp::get()
+        .or(warp::head())
+        .unify()
+        .and(warp::method())
+        .and(warp::path::tail())
+        .and(warp::header::headers_cloned())
+        .and_then(
+            move |method: Method, tail: warp::path::Tail, headers: HeaderMap| {
+                let root = root.clone();
+                let handler = handler.clone();
+                let options = options.clone();
+                let gate = gate.clone();
+                async move {
+                    match find_file(&root, tail.as_str(), &handler, &options, &headers).await {
+                        Some(file) => Ok(make_file_response(&method, &headers, file)),
+                        None => {
+                            debug!(gate, "No file to serve for: {}", tail.as_str());
+                            Err(warp::reject::not_found())
+                        }
+                    }
+                }
+            },
+        )
+        .boxed()
+}
+
+// The contents of the embedded files, by path relative to the root, e.g. "js/app.js"
+pub type EmbeddedFiles = HashMap<String, &'static [u8]>;
+
+// An embedded file, with its ETag computed once from its contents
+struct EmbeddedFile {
+    contents: &'static [u8],
+    etag: String,
+}
+
+fn make_embedded_etag(contents: &[u8]) -> String {
+    let mut hasher = DefaultHasher::new();
+    contents.hash(&mut hasher);
+    format!("\"{:x}-{:x}\"", contents.len(), hasher.finish())
+}
+
+// Find the embedded file for the request path, or its fallback, preferring a precompressed variant
+fn find_embedded_file(
+    files: &HashMap<String, EmbeddedFile>,
+    tail: &str,
+    handler: &ContentTypeHandler,
+    options: &FilesystemRouteOptions,
+    headers: &HeaderMap,
+) -> Option<StaticFile> {
+    let (relative_path, file) =
+        relative_paths(tail, options)?
+            .into_iter()
+            .find_map(|relative_path| {
+                let file = files.get(&relative_path)?;
+                Some((relative_path, file))
+            })?;
+    let content_type = handler(&relative_path)?;
+
+    let accepted = accepted_encodings(headers);
+    let variant = ENCODINGS.iter().find_map(|(encoding, extension)| {
+        if !accepted.iter().any(|accepted| accepted == encoding) {
+            return None;
+        }
+        let variant = files.get(&format!("{}.{}", relative_path, extension))?;
+        Some((*encoding, variant))
+    });
+    let (content_encoding, file) = match variant {
+        Some((encoding, variant)) => (Some(encoding), variant),
+        None => (None, file),
+    };
+    Some(StaticFile {
+        content_type,
+        content_encoding,
+        last_modified: None,
+        etag: file.etag.clone(),
+        contents: file.contents.to_vec(),
+    })
+}
+
+// Make the route for the embedded files, for GET and HEAD requests, which behaves just like the
+// route for the same files under a filesystem root
+pub fn make_embedded_route(
+    files: EmbeddedFiles,
+    handler: ContentTypeHandler,
+    options: FilesystemRouteOptions,
+    gate: Logger,
+) -> BoxedFilter<(Response<Body>,)> {
+    let files = Arc::new(
+        files
+            .into_iter()
+            .map(|(relative_path, contents)| {
+                let etag = make_embedded_etag(contents);
+                (relative_path, EmbeddedFile { contents, etag })
+            })
+            .collect::<HashMap<_, _>>(),
+    );
+    let options = Arc::new(options);
+
+    warp::get()
+        .or(warp::head())
+        .unify()
+        .and(warp::method())
+        .and(warp::path::tail())
+        .and(warp::header::headers_cloned())
+        .and_then(
+            move |method: Method, tail: warp::path::Tail, headers: HeaderMap| {
+                let file = find_embedded_file(&files, tail.as_str(), &handler, &options, &headers);
+                let gate = gate.clone();
+                async move {
+                    match file {
+                        Some(file) => Ok(make_file_response(&method, &headers, file)),
+                        None => {
+                            debug!(gate, "No embedded file to serve for: {}", tail.as_str());
+                            Err(warp::reject::not_found())
+                        }
+                    }
+                }
+            },
+        )
+        .boxed()
+}
+
+// Add a header to every response, e.g. the Content-Security-Policy
+pub fn with_header(
+    route: BoxedFilter<(Response<Body>,)>,
+    name: header::HeaderName,
+    value: HeaderValue,
+) -> BoxedFilter<(Response<Body>,)> {
+    route
+        .map(move |mut response: Response<Body>| {
+            response.headers_mut().insert(name.clone(), value.clone());
+            response
+        })
+        .boxed()
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_root() -> (PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("nmos-filesystem-{}", std::process::id()));
+        let root = dir.join("root");
+        std::fs::create_dir_all(root.join("js")).unwrap();
+        std::fs::write(root.join("index.html"), "<html></html>").unwrap();
+        std::fs::write(root.join("js/app.js"), "app();").unwrap();
+        std::fs::write(root.join("js/app.js.gz"), "gzipped").unwrap();
+        std::fs::write(root.join("js/app.js.br"), "brotli").unwrap();
+        std::fs::write(root.join(".secret.js"), "hidden").unwrap();
+        std::fs::write(dir.join("outside.js"), "outside").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(dir.join("outside.js"), root.join("escape.js")).unwrap();
+        (dir, root)
+    }
+
+    #[tokio::test]
+    async fn test_filesystem_route() {
+        let (dir, root) = make_root();
+        let handler = make_relative_path_content_type_handler(
+            [("html", "text/html"), ("js", "application/javascript")]
+                .into_iter()
+                .collect(),
+        );
+        let options = FilesystemRouteOptions {
+            fallback: Some("index.html".to_string()),
+        };
+        let gate = Logger::root(slog::Discard, slog::o!());
+        let route = make_filesystem_route(root.to_str().unwrap(), handler, options, gate);
+
+        let response = warp::test::request().path("/js/app.js").reply(&route).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.body().as_ref(), b"app();");
+        assert_eq!(response.headers()["content-type"], "application/javascript");
+        let etag = response.headers()["etag"].clone();
+        let last_modified = response.headers()["last-modified"].clone();
+
+        let response = warp::test::request()
+            .path("/js/app.js")
+            .header("if-none-match", etag)
+            .reply(&route)
+            .await;
+        assert_eq!(response.status(), 304);
+        assert!(response.body().is_empty());
+        let response = warp::test::request()
+            .path("/js/app.js")
+            .header("if-modified-since", last_modified)
+            .reply(&route)
+            .await;
+        assert_eq!(response.status(), 304);
+
+        // Precompressed variants, with a different ETag
+        let response = warp::test::request()
+            .path("/js/app.js")
+            .header("accept-encoding", "gzip, br;q=0")
+            .reply(&route)
+            .await;
+        assert_eq!(response.headers()["content-encoding"], "gzip");
+        assert_eq!(response.body().as_ref(), b"gzipped");
+        let response = warp::test::request()
+            .path("/js/app.js")
+            .header("accept-encoding", "gzip, br")
+            .reply(&route)
+            .await;
+        assert_eq!(response.headers()["content-encoding"], "br");
+        assert!(response.headers()["etag"]
+            .to_str()
+            .unwrap()
+            .ends_with("-br\""));
+
+        // HEAD has the same headers without the body
+        let response = warp::test::request()
+            .method("HEAD")
+            .path("/js/app.js")
+            .reply(&route)
+            .await;
+        assert_eq!(response.headers()["content-length"], "6");
+        assert!(response.body().is_empty());
+
+        // Client-side routes fall back to the index, but missing files don't
+        let response = warp::test::request()
+            .path("/nodes/1234")
+            .reply(&route)
+            .await;
+        assert_eq!(response.body().as_ref(), b"<html></html>");
+        let response = warp::test::request()
+            .path("/js/missing.js")
+            .reply(&route)
+            .await;
+        assert_eq!(response.status(), 404);
+
+        // Nothing outside the root, or hidden, can be reached
+        for path in [
+            "/js/../../outside.js",
+            "/js/%2e%2e/%2e%2e/outside.js",
+            "/js%2f..%2f..%2foutside.js",
+            "/js%5capp.js",
+            "/.secret.js",
+            "/escape.js",
+        ] {
+            let response = warp::test::request().path(path).reply(&route).await;
+            assert_eq!(response.status(), 404, "{}", path);
+        }
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_embedded_route() {
+        let handler = make_relative_path_content_type_handler(
+            [("html", "text/html"), ("js", "application/javascript")]
+                .into_iter()
+                .collect(),
+        );
+        let options = FilesystemRouteOptions {
+            fallback: Some("index.html".to_string()),
+        };
+        let files: EmbeddedFiles = [
+            ("index.html", b"<html></html>".as_slice()),
+            ("js/app.js", b"app();".as_slice()),
+            ("js/app.js.gz", b"gzipped".as_slice()),
+        ]
+        .into_iter()
+        .map(|(relative_path, contents)| (relative_path.to_string(), contents))
+        .collect();
+        let gate = Logger::root(slog::Discard, slog::o!());
+        let route = make_embedded_route(files, handler, options, gate);
+
+        let response = warp::test::request().path("/js/app.js").reply(&route).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.body().as_ref(), b"app();");
+        assert_eq!(response.headers()["content-type"], "application/javascript");
+        assert!(response.headers().get("last-modified").is_none());
+        let response = warp::test::request()
+            .path("/js/app.js")
+            .header("if-none-match", response.headers()["etag"].clone())
+            .reply(&route)
+            .await;
+        assert_eq!(response.status(), 304);
+
+        let response = warp::test::request()
+            .path("/js/app.js")
+            .header("accept-encoding", "gzip, br")
+            .reply(&route)
+            .await;
+        assert_eq!(response.headers()["content-encoding"], "gzip");
+        assert_eq!(response.body().as_ref(), b"gzipped");
+
+        let response = warp::test::request().path("/").reply(&route).await;
+        assert_eq!(response.body().as_ref(), b"<html></html>");
+        let response = warp::test::request().path("/nodes").reply(&route).await;
+        assert_eq!(response.body().as_ref(), b"<html></html>");
+        for path in ["/js/missing.js", "/js/../index.html", "/js/app.js.gz"] {
+            let response = warp::test::request().path(path).reply(&route).await;
+            assert_eq!(response.status(), 404, "{}", path);
+        }
+    }
+}
@@ -0,0 +1,376 @@
+// The CORS and security response headers of the NMOS APIs, which are configured in the settings,
+// with optional overrides per API, so that every API responds consistently
+// See https://specs.amwa.tv/is-04/releases/v1.3.2/docs/APIs_-_Server_Side_Implementation_Notes.html#cross-origin-resource-sharing-cors
+// and https://fetch.spec.whatwg.org/#http-cors-protocol
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use serde::Deserialize;
+use warp::http::header::{self, HeaderMap, HeaderName, HeaderValue};
+use warp::http::Method;
+use warp::hyper::Body;
+use warp::reply::Response;
+use warp::{Filter, Rejection};
+
+use crate::settings::Settings;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct HeaderPolicy {
+    // The origins allowed to make cross-origin requests, e.g. "https://controller.example.com",
+    // or "*" for any origin
+    pub allowed_origins: Vec<String>,
+    // Whether cross-origin requests may include credentials, e.g. cookies, which are then only
+    // allowed from the listed origins, never from any origin with "*"
+    pub allow_credentials: bool,
+    // The methods allowed by preflight responses, unless the route says which methods it allows
+    pub allowed_methods: Vec<String>,
+    // The request headers allowed by preflight responses, or empty to allow those requested
+    pub allowed_headers: Vec<String>,
+    // The response headers that scripts may read, e.g. to page through Query API results
+    pub exposed_headers: Vec<String>,
+    // How long preflight responses may be cached, in seconds
+    pub max_age: u64,
+    // Other headers added to every response, unless the route has already set them
+    pub security_headers: BTreeMap<String, String>,
+}
+
+impl Default for HeaderPolicy {
+    fn default() -> Self {
+        let strings = |values: &[&str]| values.iter().map(|value| value.to_string()).collect();
+        HeaderPolicy {
+            allowed_origins: strings(&["*"]),
+            allow_credentials: false,
+            allowed_methods: strings(&["GET", "HEAD", "POST", "PUT", "PATCH", "DELETE", "OPTIONS"]),
+            allowed_headers: Vec::new(),
+            exposed_headers: strings(&[
+                "Link",
+                "Location",
+                "Server-Timing",
+                "X-Paging-Limit",
+                "X-Paging-Since",
+                "X-Paging-Until",
+            ]),
+            max_age: 86400,
+            security_headers: [
+                ("Cache-Control", "no-cache"),
+                ("Referrer-Policy", "strict-origin-when-cross-origin"),
+                ("X-Content-Type-Options", "nosniff"),
+                ("X-Frame-Options", "SAMEORIGIN"),
+            ]
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect(),
+        }
+    }
+}
+
+// Define the fields of the header policy that are different for one API, e.g. "query"
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct HeaderPolicyOverride {
+    pub allowed_origins: Option<Vec<String>>,
+    pub allow_credentials: Option<bool>,
+    pub allowed_methods: Option<Vec<String>>,
+    pub allowed_headers: Option<Vec<String>>,
+    pub exposed_headers: Option<Vec<String>>,
+    pub max_age: Option<u64>,
+    // Merged with the security headers of the policy, where an empty value removes the header
+    pub security_headers: Option<BTreeMap<String, String>>,
+}
+
+pub type HeaderPolicyOverrides = HashMap<String, HeaderPolicyOverride>;
+
+impl HeaderPolicy {
+    pub fn with_override(mut self, policy: &HeaderPolicyOverride) -> Self {
+        if let Some(allowed_origins) = &policy.allowed_origins {
+            self.allowed_origins = allowed_origins.clone();
+        }
+        if let Some(allow_credentials) = policy.allow_credentials {
+            self.allow_credentials = allow_credentials;
+        }
+        if let Some(allowed_methods) = &policy.allowed_methods {
+            self.allowed_methods = allowed_methods.clone();
+        }
+        if let Some(allowed_headers) = &policy.allowed_headers {
+            self.allowed_headers = allowed_headers.clone();
+        }
+        if let Some(exposed_headers) = &policy.exposed_headers {
+            self.exposed_headers = exposed_headers.clone();
+        }
+        if let Some(max_age) = policy.max_age {
+            self.max_age = max_age;
+        }
+        for (name, value) in policy.security_headers.iter().flatten() {
+            if value.is_empty() {
+                self.security_headers.remove(name);
+            } else {
+                self.security_headers.insert(name.clone(), value.clone());
+            }
+        }
+        self
+    }
+
+    // Determine the Access-Control-Allow-Origin for the request's Origin, if it is allowed
+    fn allowed_origin(&self, origin: Option<&HeaderValue>) -> Option<HeaderValue> {
+        let any = self.allowed_origins.iter().any(|allowed| allowed == "*");
+        let Some(origin) = origin else {
+            // Not a cross-origin request, but "*" is harmless if any origin is allowed anyway
+            return (any && !self.allow_credentials).then(|| HeaderValue::from_static("*"));
+        };
+        let listed = origin.to_str().is_ok_and(|origin| {
+            self.allowed_origins
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(origin))
+        });
+        // Reflecting any Origin with credentials would let every site make authenticated requests
+        if listed {
+            Some(origin.clone())
+        } else if any && !self.allow_credentials {
+            Some(HeaderValue::from_static("*"))
+        } else {
+            None
+        }
+    }
+
+    // Check for a misconfiguration, i.e. "*" with credentials, for which only the listed origins
+    // are allowed, so that credentials are never allowed from any origin
+    pub fn check(&self) -> Result<(), String> {
+        if self.allow_credentials && self.allowed_origins.iter().any(|allowed| allowed == "*") {
+            return Err(
+                "allowed_origins \"*\" can't be used with allow_credentials, list the origins instead"
+                    .to_string(),
+            );
+        }
+        Ok(())
+    }
+
+    // Add the CORS and security headers to the response
+    pub fn apply(&self, method: &Method, request_headers: &HeaderMap, response: &mut Response) {
+        let preflight = method == Method::OPTIONS
+            && request_headers.contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+        let allowed_origin = self.allowed_origin(request_headers.get(header::ORIGIN));
+        let headers = response.headers_mut();
+
+        for (name, value) in &self.security_headers {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                headers.entry(name).or_insert(value);
+            }
+        }
+
+        let Some(allowed_origin) = allowed_origin else {
+            return;
+        };
+        if allowed_origin != "*" {
+            headers.append(header::VARY, HeaderValue::from_static("Origin"));
+        }
+        headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, allowed_origin);
+        if self.allow_credentials {
+            headers.insert(
+                header::ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                HeaderValue::from_static("true"),
+            );
+        }
+
+        if preflight {
+            // The route's own Allow header is more accurate than the configured methods
+            let allowed_methods = match headers.get(header::ALLOW) {
+                Some(allow) => Some(allow.clone()),
+                None => join(&self.allowed_methods),
+            };
+            if let Some(allowed_methods) = allowed_methods {
+                headers.insert(header::ACCESS_CONTROL_ALLOW_METHODS, allowed_methods);
+            }
+            let allowed_headers = if self.allowed_headers.is_empty() {
+                request_headers
+                    .get(header::ACCESS_CONTROL_REQUEST_HEADERS)
+                    .cloned()
+            } else {
+                join(&self.allowed_headers)
+            };
+            if let Some(allowed_headers) = allowed_headers {
+                headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, allowed_headers);
+            }
+            headers.insert(
+                header::ACCESS_CONTROL_MAX_AGE,
+                HeaderValue::from(self.max_age),
+            );
+        } else if let Some(exposed_headers) = join(&self.exposed_headers) {
+            headers.insert(header::ACCESS_CONTROL_EXPOSE_HEADERS, exposed_headers);
+        }
+    }
+}
+
+fn join(values: &[String]) -> Option<HeaderValue> {
+    if values.is_empty() {
+        return None;
+    }
+    HeaderValue::from_str(&values.join(", ")).ok()
+}
+
+// Check the header policy of every API in the settings, when they are loaded
+pub fn check_header_policies(settings: &Settings) -> Result<(), String> {
+    settings.header_policy.check()?;
+    for api in settings.api_header_policies.keys() {
+        make_header_policy(settings, api)
+            .check()
+            .map_err(|e| format!("{} API: {}", api, e))?;
+    }
+    Ok(())
+}
+
+// Make the header policy for the specified API, e.g. "node" or "admin"
+pub fn make_header_policy(settings: &Settings, api: &str) -> HeaderPolicy {
+    match settings.api_header_policies.get(api) {
+        Some(policy) => settings.header_policy.clone().with_override(policy),
+        None => settings.header_policy.clone(),
+    }
+}
+
+// Apply the header policy to every response of the API, and respond to CORS preflight requests
+// that the API doesn't answer itself, e.g. with the Allow header of crate::api_methods
+pub fn with_header_policy<F>(
+    api: F,
+    policy: HeaderPolicy,
+) -> impl Filter<Extract = (Response,), Error = Rejection> + Clone
+where
+    F: Filter<Extract = (Response,), Error = Rejection> + Clone + Send + Sync + 'static,
+{
+    let policy = Arc::new(policy);
+    // Any other request is rejected as not found, so that the API's own rejection is reported,
+    // rather than e.g. a missing header
+    let preflight = warp::method().and(warp::header::headers_cloned()).and_then(
+        |method: Method, request_headers: HeaderMap| async move {
+            if method == Method::OPTIONS
+                && request_headers.contains_key(header::ACCESS_CONTROL_REQUEST_METHOD)
+            {
+                Ok(Response::new(Body::empty()))
+            } else {
+                Err(warp::reject::not_found())
+            }
+        },
+    );
+
+    warp::method()
+        .and(warp::header::headers_cloned())
+        .and(api.or(preflight).unify())
+        .map(
+            move |method: Method, request_headers: HeaderMap, mut response: Response| {
+                policy.apply(&method, &request_headers, &mut response);
+                response
+            },
+        )
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use warp::Reply;
+
+    #[tokio::test]
+    async fn test_header_policy() {
+        let api =
+            warp::path!("x-nmos" / "query").map(|| warp::reply::json(&["v1.3/"]).into_response());
+        let route = with_header_policy(api, HeaderPolicy::default());
+
+        let response = warp::test::request()
+            .path("/x-nmos/query")
+            .header("origin", "https://controller.example.com")
+            .reply(&route)
+            .await;
+        assert_eq!(response.headers()["access-control-allow-origin"], "*");
+        assert!(response.headers()["access-control-expose-headers"]
+            .to_str()
+            .unwrap()
+            .contains("X-Paging-Limit"));
+        assert_eq!(response.headers()["x-content-type-options"], "nosniff");
+
+        let response = warp::test::request()
+            .method("OPTIONS")
+            .path("/x-nmos/query")
+            .header("origin", "https://controller.example.com")
+            .header("access-control-request-method", "POST")
+            .header("access-control-request-headers", "Content-Type")
+            .reply(&route)
+            .await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            response.headers()["access-control-allow-headers"],
+            "Content-Type"
+        );
+        assert_eq!(response.headers()["access-control-max-age"], "86400");
+
+        // Only the listed origins, with credentials, for this API
+        let settings = Settings {
+            api_header_policies: [(
+                "query".to_string(),
+                HeaderPolicyOverride {
+                    allowed_origins: Some(vec!["https://controller.example.com".to_string()]),
+                    allow_credentials: Some(true),
+                    security_headers: Some(
+                        [("X-Frame-Options".to_string(), String::new())]
+                            .into_iter()
+                            .collect(),
+                    ),
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let route = with_header_policy(api, make_header_policy(&settings, "query"));
+        let response = warp::test::request()
+            .path("/x-nmos/query")
+            .header("origin", "https://controller.example.com")
+            .reply(&route)
+            .await;
+        assert_eq!(
+            response.headers()["access-control-allow-origin"],
+            "https://controller.example.com"
+        );
+        assert_eq!(
+            response.headers()["access-control-allow-credentials"],
+            "true"
+        );
+        assert_eq!(response.headers()["vary"], "Origin");
+        assert!(response.headers().get("x-frame-options").is_none());
+        let response = warp::test::request()
+            .path("/x-nmos/query")
+            .header("origin", "https://elsewhere.example.com")
+            .reply(&route)
+            .await;
+        assert!(response
+            .headers()
+            .get("access-control-allow-origin")
+            .is_none());
+        assert!(check_header_policies(&settings).is_ok());
+        let response = warp::test::request()
+            .path("/x-nmos/node")
+            .reply(&route)
+            .await;
+        assert_eq!(response.status(), 404);
+
+        // Any origin with credentials is a misconfiguration, and no unlisted origin is reflected
+        let policy = HeaderPolicy {
+            allow_credentials: true,
+            ..Default::default()
+        };
+        assert!(policy.check().is_err());
+        let route = with_header_policy(api, policy);
+        let response = warp::test::request()
+            .path("/x-nmos/query")
+            .header("origin", "https://elsewhere.example.com")
+            .reply(&route)
+            .await;
+        assert!(response
+            .headers()
+            .get("access-control-allow-origin")
+            .is_none());
+    }
+}
@@ -0,0 +1,128 @@
+// Resource ids, which are either random, or repeatable, i.e. the same after every restart of the
+// Node, so that controllers can continue to use the ids they have saved, e.g. for routes
+// Repeatable ids are name-based (version 5) UUIDs, of a seed and the role of the resource
+// See https://specs.amwa.tv/is-04/releases/v1.3.2/docs/Data_Model_-_Identifier_Mapping.html
+// and https://www.rfc-editor.org/rfc/rfc9562#section-5.5
+
+use uuid::Uuid;
+
+use crate::settings::Settings;
+use crate::types::nmos::{ResourceType, ALL_TYPES};
+
+// The role path of the Node itself, of which there is only one
+pub const NODE_ROLE_PATH: &str = "/x-nmos/node/self";
+
+// Make a random id
+pub fn make_id() -> String {
+    Uuid::new_v4().to_string()
+}
+
+// Make the seed of the repeatable ids, which is either a UUID, or any other name for the Node that
+// doesn't change, e.g. its host name
+pub fn make_seed(seed: &str) -> Uuid {
+    Uuid::parse_str(seed).unwrap_or_else(|_| Uuid::new_v5(&Uuid::NAMESPACE_DNS, seed.as_bytes()))
+}
+
+// Make the repeatable id of the resource with the specified role, e.g. "/x-nmos/node/sender/0"
+pub fn make_repeatable_id(seed: &Uuid, role_path: &str) -> String {
+    Uuid::new_v5(seed, role_path.as_bytes()).to_string()
+}
+
+// Make the role path of a resource of the Node, by its type and a name or index that is unique
+// among the resources of that type, e.g. "/x-nmos/node/flow/camera1"
+// The Node's role path is always NODE_ROLE_PATH
+pub fn make_role_path(resource_type: ResourceType, name: &str) -> String {
+    match resource_type {
+        ResourceType::Node => NODE_ROLE_PATH.to_string(),
+        _ => format!("/x-nmos/node/{}/{}", ALL_TYPES[&resource_type], name),
+    }
+}
+
+// Make the ids of the Node's resources, which are repeatable if it is configured with a seed, and
+// otherwise change on every restart
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdGenerator {
+    seed: Option<Uuid>,
+}
+
+impl IdGenerator {
+    pub fn new(seed: Option<&str>) -> Self {
+        IdGenerator {
+            seed: seed.map(make_seed),
+        }
+    }
+
+    // Make the ids with the seed in the settings, if any
+    pub fn from_settings(settings: &Settings) -> Self {
+        IdGenerator::new(settings.seed_id.as_deref())
+    }
+
+    pub fn is_repeatable(&self) -> bool {
+        self.seed.is_some()
+    }
+
+    pub fn make_id(&self, role_path: &str) -> String {
+        match &self.seed {
+            Some(seed) => make_repeatable_id(seed, role_path),
+            None => make_id(),
+        }
+    }
+
+    pub fn node_id(&self) -> String {
+        self.make_id(NODE_ROLE_PATH)
+    }
+
+    pub fn resource_id(&self, resource_type: ResourceType, name: &str) -> String {
+        self.make_id(&make_role_path(resource_type, name))
+    }
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repeatable_ids() {
+        let seed = "b0a2b8a3-6b4e-4b7e-9c1e-2f5a8e3d4c6f";
+        let ids = IdGenerator::new(Some(seed));
+        let node_id = ids.node_id();
+        assert_eq!(node_id, IdGenerator::new(Some(seed)).node_id());
+        assert_eq!(Uuid::parse_str(&node_id).unwrap().get_version_num(), 5);
+
+        let sender_id = ids.resource_id(ResourceType::Sender, "0");
+        assert_eq!(
+            sender_id,
+            make_repeatable_id(&make_seed(seed), "/x-nmos/node/sender/0")
+        );
+        assert_ne!(sender_id, ids.resource_id(ResourceType::Receiver, "0"));
+        assert_ne!(sender_id, ids.resource_id(ResourceType::Sender, "1"));
+
+        // A different seed makes different ids, and a name can be the seed too
+        assert_ne!(node_id, IdGenerator::new(Some("host1")).node_id());
+        assert_eq!(
+            IdGenerator::new(Some("host1")).node_id(),
+            IdGenerator::new(Some("host1")).node_id()
+        );
+
+        let ids = IdGenerator::new(None);
+        assert!(!ids.is_repeatable());
+        assert_ne!(ids.node_id(), ids.node_id());
+    }
+
+    #[test]
+    fn test_from_settings() {
+        let settings: Settings = serde_json::from_str(r#"{"seed_id": "host1"}"#).unwrap();
+        let ids = IdGenerator::from_settings(&settings);
+        let restarted = IdGenerator::from_settings(&settings);
+        assert_eq!(ids.node_id(), restarted.node_id());
+        assert_eq!(ids.node_id(), ids.resource_id(ResourceType::Node, "self"));
+        assert_eq!(
+            ids.resource_id(ResourceType::Device, "0"),
+            restarted.resource_id(ResourceType::Device, "0")
+        );
+        assert_ne!(ids.node_id(), ids.resource_id(ResourceType::Device, "0"));
+
+        assert!(!IdGenerator::from_settings(&Settings::default()).is_repeatable());
+    }
+}
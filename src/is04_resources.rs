@@ -0,0 +1,1182 @@
+// Typed IS-04 resources, which serialize to and from the JSON of the Node, Registration and Query
+// APIs, and builders for them that require the properties every resource of that type must have
+// Properties that aren't modelled, e.g. vendor extensions or those from a later version of the
+// specification, are kept in the `extra` map of each resource, so that they round-trip unchanged
+// See https://specs.amwa.tv/is-04/releases/v1.3.2/APIs/schemas/
+
+use std::collections::BTreeMap;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::api_version::ApiVersion;
+use crate::tai::{make_version, Tai};
+use crate::types::nmos::ResourceType;
+
+pub type Tags = BTreeMap<String, Vec<String>>;
+pub type Extra = Map<String, Value>;
+
+// Formats
+// See https://specs.amwa.tv/nmos-parameter-registers/branches/main/formats/
+pub const FORMAT_VIDEO: &str = "urn:x-nmos:format:video";
+pub const FORMAT_AUDIO: &str = "urn:x-nmos:format:audio";
+pub const FORMAT_DATA: &str = "urn:x-nmos:format:data";
+pub const FORMAT_MUX: &str = "urn:x-nmos:format:mux";
+
+// Device types
+// See https://specs.amwa.tv/nmos-parameter-registers/branches/main/device-types/
+pub const DEVICE_TYPE_GENERIC: &str = "urn:x-nmos:device:generic";
+pub const DEVICE_TYPE_PIPELINE: &str = "urn:x-nmos:device:pipeline";
+
+// Transports
+// See https://specs.amwa.tv/nmos-parameter-registers/branches/main/transports/
+pub const TRANSPORT_RTP: &str = "urn:x-nmos:transport:rtp";
+pub const TRANSPORT_RTP_MCAST: &str = "urn:x-nmos:transport:rtp.mcast";
+pub const TRANSPORT_WEBSOCKET: &str = "urn:x-nmos:transport:websocket";
+pub const TRANSPORT_MQTT: &str = "urn:x-nmos:transport:mqtt";
+
+const V1_1: ApiVersion = ApiVersion::new(1, 1);
+const V1_2: ApiVersion = ApiVersion::new(1, 2);
+const V1_3: ApiVersion = ApiVersion::new(1, 3);
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+// An IS-04 resource, which can be represented in the JSON of each version of the specification
+pub trait Resource: Serialize + DeserializeOwned {
+    const TYPE: ResourceType;
+    // The top-level properties that were added after v1.0, and the version that added them
+    const PROPERTIES: &'static [(&'static str, ApiVersion)];
+
+    // Represent the resource in the JSON of the specified version, without the properties that
+    // were added in later versions
+    fn to_json(&self, version: &ApiVersion) -> Result<Value, serde_json::Error> {
+        let mut value = serde_json::to_value(self)?;
+        if let Some(object) = value.as_object_mut() {
+            for (name, added) in Self::PROPERTIES {
+                if added > version {
+                    object.remove(*name);
+                }
+            }
+        }
+        Ok(value)
+    }
+
+    fn from_json(value: Value) -> Result<Self, serde_json::Error> {
+        serde_json::from_value(value)
+    }
+}
+
+// The properties that all resources except subscriptions have
+// See https://specs.amwa.tv/is-04/releases/v1.3.2/APIs/schemas/with-refs/resource_core.html
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResourceCore {
+    pub id: String,
+    pub version: String,
+    pub label: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub tags: Tags,
+}
+
+impl ResourceCore {
+    pub fn new(id: &str) -> Self {
+        ResourceCore {
+            id: id.to_string(),
+            version: make_version(),
+            label: String::new(),
+            description: String::new(),
+            tags: Tags::new(),
+        }
+    }
+}
+
+// Build the resource core on its own, e.g. for resource types that aren't modelled here
+pub struct ResourceCoreBuilder {
+    core: ResourceCore,
+}
+
+impl ResourceCoreBuilder {
+    pub fn new(id: &str) -> Self {
+        ResourceCoreBuilder {
+            core: ResourceCore::new(id),
+        }
+    }
+
+    // Override the version, which is otherwise made when the builder is created
+    pub fn version(mut self, version: Tai) -> Self {
+        self.core.version = version.to_string();
+        self
+    }
+
+    pub fn tags(mut self, tags: Tags) -> Self {
+        self.core.tags = tags;
+        self
+    }
+
+    pub fn build(self) -> ResourceCore {
+        self.core
+    }
+}
+
+impl ResourceBuilder for ResourceCoreBuilder {
+    fn core_mut(&mut self) -> &mut ResourceCore {
+        &mut self.core
+    }
+}
+
+// The setters of the resource core, which all the builders except that of subscriptions have
+pub trait ResourceBuilder: Sized {
+    fn core_mut(&mut self) -> &mut ResourceCore;
+
+    fn label(mut self, label: &str) -> Self {
+        self.core_mut().label = label.to_string();
+        self
+    }
+
+    fn description(mut self, description: &str) -> Self {
+        self.core_mut().description = description.to_string();
+        self
+    }
+
+    fn tag(mut self, name: &str, values: &[&str]) -> Self {
+        let values = values.iter().map(|value| value.to_string()).collect();
+        self.core_mut().tags.insert(name.to_string(), values);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rational {
+    pub numerator: u64,
+    #[serde(default = "Rational::default_denominator")]
+    pub denominator: u64,
+}
+
+impl Rational {
+    pub fn new(numerator: u64, denominator: u64) -> Self {
+        Rational {
+            numerator,
+            denominator,
+        }
+    }
+
+    fn default_denominator() -> u64 {
+        1
+    }
+}
+
+// Node
+// See https://specs.amwa.tv/is-04/releases/v1.3.2/APIs/schemas/with-refs/node.html
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Endpoint {
+    pub host: String,
+    pub port: u16,
+    pub protocol: String,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub authorization: bool,
+}
+
+impl Endpoint {
+    pub fn new(host: &str, port: u16, protocol: &str) -> Self {
+        Endpoint {
+            host: host.to_string(),
+            port,
+            protocol: protocol.to_string(),
+            authorization: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NodeApi {
+    pub versions: Vec<ApiVersion>,
+    pub endpoints: Vec<Endpoint>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Service {
+    pub href: String,
+    #[serde(rename = "type")]
+    pub service_type: String,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub authorization: bool,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+impl Service {
+    pub fn new(href: &str, service_type: &str) -> Self {
+        Service {
+            href: href.to_string(),
+            service_type: service_type.to_string(),
+            authorization: false,
+            extra: Extra::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Clock {
+    pub name: String,
+    // "internal" or "ptp", with the other properties of PTP clocks, e.g. "gmid", in extra
+    pub ref_type: String,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+impl Clock {
+    pub fn internal(name: &str) -> Self {
+        Clock {
+            name: name.to_string(),
+            ref_type: "internal".to_string(),
+            extra: Extra::new(),
+        }
+    }
+
+    pub fn ptp(name: &str, gmid: &str, traceable: bool, locked: bool) -> Self {
+        let extra = [
+            ("traceable", Value::from(traceable)),
+            ("version", Value::from("IEEE1588-2008")),
+            ("gmid", Value::from(gmid)),
+            ("locked", Value::from(locked)),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value))
+        .collect();
+        Clock {
+            name: name.to_string(),
+            ref_type: "ptp".to_string(),
+            extra,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Interface {
+    pub name: String,
+    // Null if the chassis ID of the interface is unknown
+    pub chassis_id: Option<String>,
+    pub port_id: String,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+impl Interface {
+    pub fn new(name: &str, chassis_id: Option<&str>, port_id: &str) -> Self {
+        Interface {
+            name: name.to_string(),
+            chassis_id: chassis_id.map(str::to_string),
+            port_id: port_id.to_string(),
+            extra: Extra::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Node {
+    #[serde(flatten)]
+    pub core: ResourceCore,
+    pub href: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    #[serde(default)]
+    pub api: NodeApi,
+    #[serde(default)]
+    pub caps: Extra,
+    #[serde(default)]
+    pub services: Vec<Service>,
+    #[serde(default)]
+    pub clocks: Vec<Clock>,
+    #[serde(default)]
+    pub interfaces: Vec<Interface>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+impl Resource for Node {
+    const TYPE: ResourceType = ResourceType::Node;
+    const PROPERTIES: &'static [(&'static str, ApiVersion)] = &[
+        ("description", V1_1),
+        ("tags", V1_1),
+        ("api", V1_1),
+        ("clocks", V1_1),
+        ("interfaces", V1_2),
+    ];
+}
+
+pub struct NodeBuilder {
+    node: Node,
+}
+
+impl NodeBuilder {
+    pub fn new(id: &str, href: &str, hostname: &str) -> Self {
+        NodeBuilder {
+            node: Node {
+                core: ResourceCore::new(id),
+                href: href.to_string(),
+                hostname: Some(hostname.to_string()),
+                api: NodeApi::default(),
+                caps: Extra::new(),
+                services: Vec::new(),
+                clocks: Vec::new(),
+                interfaces: Vec::new(),
+                extra: Extra::new(),
+            },
+        }
+    }
+
+    pub fn api_version(mut self, version: ApiVersion) -> Self {
+        self.node.api.versions.push(version);
+        self
+    }
+
+    pub fn endpoint(mut self, endpoint: Endpoint) -> Self {
+        self.node.api.endpoints.push(endpoint);
+        self
+    }
+
+    pub fn service(mut self, service: Service) -> Self {
+        self.node.services.push(service);
+        self
+    }
+
+    pub fn clock(mut self, clock: Clock) -> Self {
+        self.node.clocks.push(clock);
+        self
+    }
+
+    pub fn interface(mut self, interface: Interface) -> Self {
+        self.node.interfaces.push(interface);
+        self
+    }
+
+    pub fn build(self) -> Node {
+        self.node
+    }
+}
+
+impl ResourceBuilder for NodeBuilder {
+    fn core_mut(&mut self) -> &mut ResourceCore {
+        &mut self.node.core
+    }
+}
+
+// Device
+// See https://specs.amwa.tv/is-04/releases/v1.3.2/APIs/schemas/with-refs/device.html
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Control {
+    pub href: String,
+    #[serde(rename = "type")]
+    pub control_type: String,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub authorization: bool,
+}
+
+impl Control {
+    pub fn new(href: &str, control_type: &str) -> Self {
+        Control {
+            href: href.to_string(),
+            control_type: control_type.to_string(),
+            authorization: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Device {
+    #[serde(flatten)]
+    pub core: ResourceCore,
+    #[serde(rename = "type")]
+    pub device_type: String,
+    pub node_id: String,
+    // Deprecated, but still required, in favour of the device_id of the senders and receivers
+    #[serde(default)]
+    pub senders: Vec<String>,
+    #[serde(default)]
+    pub receivers: Vec<String>,
+    #[serde(default)]
+    pub controls: Vec<Control>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+impl Resource for Device {
+    const TYPE: ResourceType = ResourceType::Device;
+    const PROPERTIES: &'static [(&'static str, ApiVersion)] =
+        &[("description", V1_1), ("tags", V1_1), ("controls", V1_1)];
+}
+
+pub struct DeviceBuilder {
+    device: Device,
+}
+
+impl DeviceBuilder {
+    pub fn new(id: &str, node_id: &str, device_type: &str) -> Self {
+        DeviceBuilder {
+            device: Device {
+                core: ResourceCore::new(id),
+                device_type: device_type.to_string(),
+                node_id: node_id.to_string(),
+                senders: Vec::new(),
+                receivers: Vec::new(),
+                controls: Vec::new(),
+                extra: Extra::new(),
+            },
+        }
+    }
+
+    pub fn sender(mut self, sender_id: &str) -> Self {
+        self.device.senders.push(sender_id.to_string());
+        self
+    }
+
+    pub fn receiver(mut self, receiver_id: &str) -> Self {
+        self.device.receivers.push(receiver_id.to_string());
+        self
+    }
+
+    pub fn control(mut self, control: Control) -> Self {
+        self.device.controls.push(control);
+        self
+    }
+
+    pub fn build(self) -> Device {
+        self.device
+    }
+}
+
+impl ResourceBuilder for DeviceBuilder {
+    fn core_mut(&mut self) -> &mut ResourceCore {
+        &mut self.device.core
+    }
+}
+
+// Source
+// See https://specs.amwa.tv/is-04/releases/v1.3.2/APIs/schemas/with-refs/source.html
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Channel {
+    pub label: String,
+    // e.g. "L" or "R"
+    // See https://specs.amwa.tv/nmos-parameter-registers/branches/main/channel-symbols/
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+}
+
+impl Channel {
+    pub fn new(label: &str, symbol: Option<&str>) -> Self {
+        Channel {
+            label: label.to_string(),
+            symbol: symbol.map(str::to_string),
+        }
+    }
+}
+
+// The format and the properties of each kind of source, i.e. generic (video or data), audio, data
+// with an event type, or mux, are in the one structure, so that none are lost in deserialization
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Source {
+    #[serde(flatten)]
+    pub core: ResourceCore,
+    #[serde(default)]
+    pub caps: Extra,
+    pub device_id: String,
+    #[serde(default)]
+    pub parents: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grain_rate: Option<Rational>,
+    // Null if the source isn't associated with one of the Node's clocks
+    #[serde(default)]
+    pub clock_name: Option<String>,
+    pub format: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channels: Option<Vec<Channel>>,
+    // See https://specs.amwa.tv/is-07/releases/v1.0.1/docs/3.0._Event_types.html
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_type: Option<String>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+impl Resource for Source {
+    const TYPE: ResourceType = ResourceType::Source;
+    const PROPERTIES: &'static [(&'static str, ApiVersion)] = &[
+        ("grain_rate", V1_1),
+        ("clock_name", V1_1),
+        ("channels", V1_1),
+        ("event_type", V1_3),
+    ];
+}
+
+pub struct SourceBuilder {
+    source: Source,
+}
+
+impl SourceBuilder {
+    fn new(id: &str, device_id: &str, format: &str) -> Self {
+        SourceBuilder {
+            source: Source {
+                core: ResourceCore::new(id),
+                caps: Extra::new(),
+                device_id: device_id.to_string(),
+                parents: Vec::new(),
+                grain_rate: None,
+                clock_name: None,
+                format: format.to_string(),
+                channels: None,
+                event_type: None,
+                extra: Extra::new(),
+            },
+        }
+    }
+
+    // A generic source, whose format is either video or data
+    pub fn generic(id: &str, device_id: &str, format: &str) -> Self {
+        SourceBuilder::new(id, device_id, format)
+    }
+
+    pub fn audio(id: &str, device_id: &str, channels: Vec<Channel>) -> Self {
+        let mut builder = SourceBuilder::new(id, device_id, FORMAT_AUDIO);
+        builder.source.channels = Some(channels);
+        builder
+    }
+
+    pub fn data(id: &str, device_id: &str, event_type: &str) -> Self {
+        let mut builder = SourceBuilder::new(id, device_id, FORMAT_DATA);
+        builder.source.event_type = Some(event_type.to_string());
+        builder
+    }
+
+    pub fn mux(id: &str, device_id: &str) -> Self {
+        SourceBuilder::new(id, device_id, FORMAT_MUX)
+    }
+
+    pub fn parent(mut self, source_id: &str) -> Self {
+        self.source.parents.push(source_id.to_string());
+        self
+    }
+
+    pub fn grain_rate(mut self, grain_rate: Rational) -> Self {
+        self.source.grain_rate = Some(grain_rate);
+        self
+    }
+
+    pub fn clock_name(mut self, clock_name: &str) -> Self {
+        self.source.clock_name = Some(clock_name.to_string());
+        self
+    }
+
+    pub fn build(self) -> Source {
+        self.source
+    }
+}
+
+impl ResourceBuilder for SourceBuilder {
+    fn core_mut(&mut self) -> &mut ResourceCore {
+        &mut self.source.core
+    }
+}
+
+// Flow
+// See https://specs.amwa.tv/is-04/releases/v1.3.2/APIs/schemas/with-refs/flow.html
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Component {
+    // e.g. "Y", "Cb" or "Cr"
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u32,
+}
+
+impl Component {
+    pub fn new(name: &str, width: u32, height: u32, bit_depth: u32) -> Self {
+        Component {
+            name: name.to_string(),
+            width,
+            height,
+            bit_depth,
+        }
+    }
+}
+
+// The Data Identification Words of the ancillary data in a flow
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub struct DidSdid {
+    // e.g. "0x41"
+    pub did: String,
+    pub sdid: String,
+}
+
+// The format, media type and the properties of each kind of flow, i.e. video (raw or coded), audio
+// (raw or coded), data, SDI ancillary data or mux, are in the one structure, like those of sources
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Flow {
+    #[serde(flatten)]
+    pub core: ResourceCore,
+    pub source_id: String,
+    pub device_id: String,
+    #[serde(default)]
+    pub parents: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grain_rate: Option<Rational>,
+    pub format: String,
+    // e.g. "video/raw" or "audio/L24"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+    // Video
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frame_width: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frame_height: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interlace_mode: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub colorspace: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transfer_characteristic: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub components: Option<Vec<Component>>,
+    // Audio
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sample_rate: Option<Rational>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bit_depth: Option<u32>,
+    // Data
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_type: Option<String>,
+    #[serde(rename = "DID_SDID", default, skip_serializing_if = "Option::is_none")]
+    pub did_sdid: Option<Vec<DidSdid>>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+impl Resource for Flow {
+    const TYPE: ResourceType = ResourceType::Flow;
+    const PROPERTIES: &'static [(&'static str, ApiVersion)] = &[
+        ("grain_rate", V1_1),
+        ("device_id", V1_1),
+        ("media_type", V1_1),
+        ("frame_width", V1_1),
+        ("frame_height", V1_1),
+        ("interlace_mode", V1_1),
+        ("colorspace", V1_1),
+        ("transfer_characteristic", V1_1),
+        ("components", V1_1),
+        ("sample_rate", V1_1),
+        ("bit_depth", V1_1),
+        ("DID_SDID", V1_1),
+        ("event_type", V1_3),
+    ];
+}
+
+pub struct FlowBuilder {
+    flow: Flow,
+}
+
+impl FlowBuilder {
+    fn new(id: &str, source_id: &str, device_id: &str, format: &str, media_type: &str) -> Self {
+        FlowBuilder {
+            flow: Flow {
+                core: ResourceCore::new(id),
+                source_id: source_id.to_string(),
+                device_id: device_id.to_string(),
+                parents: Vec::new(),
+                grain_rate: None,
+                format: format.to_string(),
+                media_type: Some(media_type.to_string()),
+                frame_width: None,
+                frame_height: None,
+                interlace_mode: None,
+                colorspace: None,
+                transfer_characteristic: None,
+                components: None,
+                sample_rate: None,
+                bit_depth: None,
+                event_type: None,
+                did_sdid: None,
+                extra: Extra::new(),
+            },
+        }
+    }
+
+    // A coded video flow, e.g. "video/H264"
+    pub fn coded_video(
+        id: &str,
+        source_id: &str,
+        device_id: &str,
+        media_type: &str,
+        frame_width: u32,
+        frame_height: u32,
+        colorspace: &str,
+    ) -> Self {
+        let mut builder = FlowBuilder::new(id, source_id, device_id, FORMAT_VIDEO, media_type);
+        builder.flow.frame_width = Some(frame_width);
+        builder.flow.frame_height = Some(frame_height);
+        builder.flow.colorspace = Some(colorspace.to_string());
+        builder
+    }
+
+    pub fn raw_video(
+        id: &str,
+        source_id: &str,
+        device_id: &str,
+        frame_width: u32,
+        frame_height: u32,
+        colorspace: &str,
+        components: Vec<Component>,
+    ) -> Self {
+        let mut builder = FlowBuilder::coded_video(
+            id,
+            source_id,
+            device_id,
+            "video/raw",
+            frame_width,
+            frame_height,
+            colorspace,
+        );
+        builder.flow.components = Some(components);
+        builder
+    }
+
+    // A coded audio flow, e.g. "audio/mpeg4-generic"
+    pub fn coded_audio(
+        id: &str,
+        source_id: &str,
+        device_id: &str,
+        media_type: &str,
+        sample_rate: Rational,
+    ) -> Self {
+        let mut builder = FlowBuilder::new(id, source_id, device_id, FORMAT_AUDIO, media_type);
+        builder.flow.sample_rate = Some(sample_rate);
+        builder
+    }
+
+    // A raw audio flow, whose media type is "audio/L<bit depth>", e.g. "audio/L24"
+    pub fn raw_audio(
+        id: &str,
+        source_id: &str,
+        device_id: &str,
+        sample_rate: Rational,
+        bit_depth: u32,
+    ) -> Self {
+        let media_type = format!("audio/L{}", bit_depth);
+        let mut builder =
+            FlowBuilder::coded_audio(id, source_id, device_id, &media_type, sample_rate);
+        builder.flow.bit_depth = Some(bit_depth);
+        builder
+    }
+
+    // A data flow, e.g. "application/json" for IS-07 events
+    pub fn data(id: &str, source_id: &str, device_id: &str, media_type: &str) -> Self {
+        FlowBuilder::new(id, source_id, device_id, FORMAT_DATA, media_type)
+    }
+
+    // An SDI ancillary data flow
+    pub fn sdianc_data(id: &str, source_id: &str, device_id: &str) -> Self {
+        FlowBuilder::new(id, source_id, device_id, FORMAT_DATA, "video/smpte291")
+    }
+
+    // A mux flow, e.g. "video/SMPTE2022-6"
+    pub fn mux(id: &str, source_id: &str, device_id: &str, media_type: &str) -> Self {
+        FlowBuilder::new(id, source_id, device_id, FORMAT_MUX, media_type)
+    }
+
+    pub fn parent(mut self, flow_id: &str) -> Self {
+        self.flow.parents.push(flow_id.to_string());
+        self
+    }
+
+    pub fn grain_rate(mut self, grain_rate: Rational) -> Self {
+        self.flow.grain_rate = Some(grain_rate);
+        self
+    }
+
+    // e.g. "progressive", "interlaced_tff", "interlaced_bff" or "interlaced_psf"
+    pub fn interlace_mode(mut self, interlace_mode: &str) -> Self {
+        self.flow.interlace_mode = Some(interlace_mode.to_string());
+        self
+    }
+
+    // e.g. "SDR", "HLG" or "PQ"
+    pub fn transfer_characteristic(mut self, transfer_characteristic: &str) -> Self {
+        self.flow.transfer_characteristic = Some(transfer_characteristic.to_string());
+        self
+    }
+
+    pub fn event_type(mut self, event_type: &str) -> Self {
+        self.flow.event_type = Some(event_type.to_string());
+        self
+    }
+
+    pub fn did_sdid(mut self, did: &str, sdid: &str) -> Self {
+        self.flow
+            .did_sdid
+            .get_or_insert_with(Vec::new)
+            .push(DidSdid {
+                did: did.to_string(),
+                sdid: sdid.to_string(),
+            });
+        self
+    }
+
+    pub fn build(self) -> Flow {
+        self.flow
+    }
+}
+
+impl ResourceBuilder for FlowBuilder {
+    fn core_mut(&mut self) -> &mut ResourceCore {
+        &mut self.flow.core
+    }
+}
+
+// Sender
+// See https://specs.amwa.tv/is-04/releases/v1.3.2/APIs/schemas/with-refs/sender.html
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SenderSubscription {
+    // Null unless the sender is sending to a unicast receiver
+    pub receiver_id: Option<String>,
+    pub active: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sender {
+    #[serde(flatten)]
+    pub core: ResourceCore,
+    // Null if the sender isn't currently configured with a flow
+    pub flow_id: Option<String>,
+    pub transport: String,
+    pub device_id: String,
+    // Null if the transport file isn't currently available
+    pub manifest_href: Option<String>,
+    #[serde(default)]
+    pub interface_bindings: Vec<String>,
+    #[serde(default)]
+    pub subscription: SenderSubscription,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+impl Resource for Sender {
+    const TYPE: ResourceType = ResourceType::Sender;
+    const PROPERTIES: &'static [(&'static str, ApiVersion)] =
+        &[("interface_bindings", V1_2), ("subscription", V1_2)];
+}
+
+pub struct SenderBuilder {
+    sender: Sender,
+}
+
+impl SenderBuilder {
+    pub fn new(id: &str, device_id: &str, flow_id: Option<&str>, transport: &str) -> Self {
+        SenderBuilder {
+            sender: Sender {
+                core: ResourceCore::new(id),
+                flow_id: flow_id.map(str::to_string),
+                transport: transport.to_string(),
+                device_id: device_id.to_string(),
+                manifest_href: None,
+                interface_bindings: Vec::new(),
+                subscription: SenderSubscription::default(),
+                extra: Extra::new(),
+            },
+        }
+    }
+
+    pub fn manifest_href(mut self, manifest_href: &str) -> Self {
+        self.sender.manifest_href = Some(manifest_href.to_string());
+        self
+    }
+
+    // The name of one of the Node's interfaces, from which the sender is sending
+    pub fn interface_binding(mut self, interface: &str) -> Self {
+        self.sender.interface_bindings.push(interface.to_string());
+        self
+    }
+
+    pub fn build(self) -> Sender {
+        self.sender
+    }
+}
+
+impl ResourceBuilder for SenderBuilder {
+    fn core_mut(&mut self) -> &mut ResourceCore {
+        &mut self.sender.core
+    }
+}
+
+// Receiver
+// See https://specs.amwa.tv/is-04/releases/v1.3.2/APIs/schemas/with-refs/receiver.html
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReceiverSubscription {
+    // Null unless the receiver is receiving from a sender
+    pub sender_id: Option<String>,
+    #[serde(default)]
+    pub active: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Receiver {
+    #[serde(flatten)]
+    pub core: ResourceCore,
+    pub device_id: String,
+    pub transport: String,
+    pub format: String,
+    // e.g. "media_types", and the constraint sets of BCP-004-01 Receiver Capabilities
+    #[serde(default)]
+    pub caps: Extra,
+    #[serde(default)]
+    pub interface_bindings: Vec<String>,
+    pub subscription: ReceiverSubscription,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+impl Resource for Receiver {
+    const TYPE: ResourceType = ResourceType::Receiver;
+    const PROPERTIES: &'static [(&'static str, ApiVersion)] = &[("interface_bindings", V1_2)];
+}
+
+pub struct ReceiverBuilder {
+    receiver: Receiver,
+}
+
+impl ReceiverBuilder {
+    pub fn new(id: &str, device_id: &str, format: &str, transport: &str) -> Self {
+        ReceiverBuilder {
+            receiver: Receiver {
+                core: ResourceCore::new(id),
+                device_id: device_id.to_string(),
+                transport: transport.to_string(),
+                format: format.to_string(),
+                caps: Extra::new(),
+                interface_bindings: Vec::new(),
+                subscription: ReceiverSubscription::default(),
+                extra: Extra::new(),
+            },
+        }
+    }
+
+    // A media type that the receiver accepts, e.g. "video/raw"
+    pub fn media_type(mut self, media_type: &str) -> Self {
+        let media_types = self
+            .receiver
+            .caps
+            .entry("media_types")
+            .or_insert_with(|| Value::Array(Vec::new()));
+        if let Value::Array(media_types) = media_types {
+            media_types.push(Value::from(media_type));
+        }
+        self
+    }
+
+    // The name of one of the Node's interfaces, on which the receiver is receiving
+    pub fn interface_binding(mut self, interface: &str) -> Self {
+        self.receiver.interface_bindings.push(interface.to_string());
+        self
+    }
+
+    pub fn build(self) -> Receiver {
+        self.receiver
+    }
+}
+
+impl ResourceBuilder for ReceiverBuilder {
+    fn core_mut(&mut self) -> &mut ResourceCore {
+        &mut self.receiver.core
+    }
+}
+
+// Subscription, to changes in the resources of the Query API
+// See https://specs.amwa.tv/is-04/releases/v1.3.2/APIs/schemas/with-refs/queryapi-subscription-response.html
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Subscription {
+    pub id: String,
+    pub ws_href: String,
+    pub max_update_rate_ms: u64,
+    pub persist: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub secure: bool,
+    // e.g. "/nodes", or "" for all resource types
+    pub resource_path: String,
+    #[serde(default)]
+    pub params: Extra,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub authorization: bool,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+impl Resource for Subscription {
+    const TYPE: ResourceType = ResourceType::Subscription;
+    const PROPERTIES: &'static [(&'static str, ApiVersion)] =
+        &[("secure", V1_1), ("authorization", V1_3)];
+}
+
+pub struct SubscriptionBuilder {
+    subscription: Subscription,
+}
+
+impl SubscriptionBuilder {
+    pub fn new(id: &str, ws_href: &str, resource_path: &str) -> Self {
+        SubscriptionBuilder {
+            subscription: Subscription {
+                id: id.to_string(),
+                ws_href: ws_href.to_string(),
+                max_update_rate_ms: 100,
+                persist: false,
+                secure: ws_href.starts_with("wss:"),
+                resource_path: resource_path.to_string(),
+                params: Extra::new(),
+                authorization: false,
+                extra: Extra::new(),
+            },
+        }
+    }
+
+    pub fn max_update_rate_ms(mut self, max_update_rate_ms: u64) -> Self {
+        self.subscription.max_update_rate_ms = max_update_rate_ms;
+        self
+    }
+
+    pub fn persist(mut self, persist: bool) -> Self {
+        self.subscription.persist = persist;
+        self
+    }
+
+    pub fn authorization(mut self, authorization: bool) -> Self {
+        self.subscription.authorization = authorization;
+        self
+    }
+
+    // A query parameter that resources must match, e.g. ("label", "camera")
+    pub fn param(mut self, name: &str, value: &str) -> Self {
+        self.subscription
+            .params
+            .insert(name.to_string(), Value::from(value));
+        self
+    }
+
+    pub fn build(self) -> Subscription {
+        self.subscription
+    }
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_node_round_trip() {
+        let json = json!({
+            "id": "3b8be755-08ff-452b-b217-c9151eb21193",
+            "version": "1441973902:879053935",
+            "label": "host1",
+            "description": "",
+            "tags": {},
+            "href": "http://172.29.80.65:12345/",
+            "hostname": "host1",
+            "api": {
+                "versions": ["v1.2", "v1.3"],
+                "endpoints": [{"host": "172.29.80.65", "port": 12345, "protocol": "http"}]
+            },
+            "caps": {},
+            "services": [{"href": "http://172.29.80.65:12345/x-manufacturer/pipelinemanager/", "type": "urn:x-manufacturer:service:pipelinemanager", "x-manufacturer-priority": 1}],
+            "clocks": [{"name": "clk0", "ref_type": "internal"}],
+            "interfaces": [{"name": "eth0", "chassis_id": null, "port_id": "74-26-96-db-87-31"}],
+            "x-manufacturer-extension": {"enabled": true}
+        });
+        let node = Node::from_json(json.clone()).unwrap();
+        assert_eq!(node.api.versions[1], ApiVersion::new(1, 3));
+        assert_eq!(node.services[0].extra["x-manufacturer-priority"], 1);
+        assert_eq!(node.extra["x-manufacturer-extension"]["enabled"], true);
+        assert_eq!(node.to_json(&ApiVersion::new(1, 3)).unwrap(), json);
+
+        // Properties added in later versions of the specification are omitted
+        let v1_0 = node.to_json(&ApiVersion::new(1, 0)).unwrap();
+        for name in ["description", "tags", "api", "clocks", "interfaces"] {
+            assert!(v1_0.get(name).is_none(), "{}", name);
+        }
+        assert_eq!(v1_0["x-manufacturer-extension"]["enabled"], true);
+
+        // Required properties are enforced when deserializing too
+        assert!(Node::from_json(json!({"id": "3b8be755-08ff-452b-b217-c9151eb21193"})).is_err());
+    }
+
+    #[test]
+    fn test_builders() {
+        let flow = FlowBuilder::raw_video(
+            "c3ae52ea-f2c8-4b0a-a4e2-1e8a1b4c1a2b",
+            "d6b4c9a5-3f5e-4d5f-9e6d-4c2f0a5b7e8c",
+            "58f6b536-ca4c-43fd-880a-9df2501fc125",
+            1920,
+            1080,
+            "BT709",
+            vec![
+                Component::new("Y", 1920, 1080, 10),
+                Component::new("Cb", 960, 1080, 10),
+                Component::new("Cr", 960, 1080, 10),
+            ],
+        )
+        .grain_rate(Rational::new(25, 1))
+        .interlace_mode("interlaced_tff")
+        .label("camera")
+        .build();
+        let json = flow.to_json(&ApiVersion::new(1, 3)).unwrap();
+        assert_eq!(json["format"], FORMAT_VIDEO);
+        assert_eq!(json["media_type"], "video/raw");
+        assert_eq!(json["components"][1]["width"], 960);
+        assert_eq!(
+            json["grain_rate"],
+            json!({"numerator": 25, "denominator": 1})
+        );
+        assert_eq!(json["label"], "camera");
+        assert!(json.get("bit_depth").is_none());
+        assert_eq!(Flow::from_json(json).unwrap(), flow);
+
+        let flow = FlowBuilder::raw_audio("a", "b", "c", Rational::new(48000, 1), 24).build();
+        assert_eq!(flow.media_type.as_deref(), Some("audio/L24"));
+
+        let source = SourceBuilder::audio(
+            "d6b4c9a5-3f5e-4d5f-9e6d-4c2f0a5b7e8c",
+            "58f6b536-ca4c-43fd-880a-9df2501fc125",
+            vec![
+                Channel::new("Left", Some("L")),
+                Channel::new("Right", Some("R")),
+            ],
+        )
+        .clock_name("clk0")
+        .build();
+        let json = source.to_json(&ApiVersion::new(1, 3)).unwrap();
+        assert_eq!(json["channels"][1]["symbol"], "R");
+        assert_eq!(json["parents"], json!([]));
+
+        let sender = SenderBuilder::new("s", "d", None, TRANSPORT_RTP_MCAST).build();
+        let json = sender.to_json(&ApiVersion::new(1, 3)).unwrap();
+        assert_eq!(json["flow_id"], Value::Null);
+        assert_eq!(json["manifest_href"], Value::Null);
+        assert_eq!(
+            json["subscription"],
+            json!({"receiver_id": null, "active": false})
+        );
+
+        let receiver = ReceiverBuilder::new("r", "d", FORMAT_VIDEO, TRANSPORT_RTP)
+            .media_type("video/raw")
+            .build();
+        let json = receiver.to_json(&ApiVersion::new(1, 3)).unwrap();
+        assert_eq!(json["caps"]["media_types"], json!(["video/raw"]));
+    }
+}
@@ -0,0 +1,657 @@
+// Validation of API requests and responses against the JSON schemas of IS-04 and IS-05, which are
+// compiled into the binary from the "schemas" directory (see schemas/fetch.sh)
+// See https://specs.amwa.tv/is-04/releases/v1.3.2/APIs/schemas/
+// and https://specs.amwa.tv/is-05/releases/v1.1.2/APIs/schemas/
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use include_dir::{include_dir, Dir, DirEntry};
+use jsonschema::{Draft, Retrieve, Uri, Validator};
+use serde_json::Value;
+use slog::{error, warn, Logger};
+use warp::http::{header, StatusCode};
+use warp::path::FullPath;
+use warp::reply::Response;
+use warp::{Filter, Rejection, Reply};
+
+use crate::api_utils::make_error_response_body;
+use crate::api_version::ApiVersion;
+
+static BUNDLED_SCHEMAS: Dir = include_dir!("$CARGO_MANIFEST_DIR/schemas");
+
+pub const REGISTRATION_REQUEST_SCHEMA: &str = "registrationapi-resource-post-request.json";
+
+// The schemas of the staged parameters of a sender or receiver, whose names in IS-05 v1.0 have a
+// "v1.0-" prefix
+pub fn sender_staged_schema(version: &ApiVersion) -> &'static str {
+    if *version == ApiVersion::new(1, 0) {
+        "v1.0-sender-stage-schema.json"
+    } else {
+        "sender-stage-schema.json"
+    }
+}
+
+pub fn receiver_staged_schema(version: &ApiVersion) -> &'static str {
+    if *version == ApiVersion::new(1, 0) {
+        "v1.0-receiver-stage-schema.json"
+    } else {
+        "receiver-stage-schema.json"
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Specification {
+    Is04,
+    Is05,
+}
+
+impl Specification {
+    // The directory of the specification's schemas, and its repository, e.g. "is-04"
+    pub fn name(&self) -> &'static str {
+        match self {
+            Specification::Is04 => "is-04",
+            Specification::Is05 => "is-05",
+        }
+    }
+
+    // The schemas of the specified version that may be selected, which must all be bundled
+    pub fn schemas(&self, version: &ApiVersion) -> Vec<&'static str> {
+        match self {
+            Specification::Is04 => vec![
+                REGISTRATION_REQUEST_SCHEMA,
+                "node.json",
+                "devices.json",
+                "device.json",
+                "sources.json",
+                "source.json",
+                "flows.json",
+                "flow.json",
+                "senders.json",
+                "sender.json",
+                "receivers.json",
+                "receiver.json",
+            ],
+            Specification::Is05 => vec![
+                sender_staged_schema(version),
+                receiver_staged_schema(version),
+            ],
+        }
+    }
+}
+
+// The schema of the specified version, which selects the schema for a request or response, e.g.
+// that of the Node API for "/x-nmos/node/v1.3/self"
+pub type SchemaSelector = fn(&str) -> Option<(ApiVersion, &'static str)>;
+
+// Make the URI by which a schema is identified, and its relative references are resolved, e.g.
+// "file:///is-04/v1.3/node.json"
+pub fn make_schema_uri(spec: Specification, version: &ApiVersion, name: &str) -> String {
+    format!("file:///{}/{}/{}", spec.name(), version, name)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaError {
+    // The schema isn't bundled
+    Missing(String),
+    // The schema, or one that it references, isn't valid
+    Schema(String),
+    // The JSON doesn't match the schema, with the path and message of each error
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaError::Missing(uri) => write!(f, "schema {} is not bundled", uri),
+            SchemaError::Schema(message) => write!(f, "invalid schema: {}", message),
+            SchemaError::Invalid(errors) => write!(f, "{}", errors.join("; ")),
+        }
+    }
+}
+
+// Resolve references between the schemas, without fetching anything
+struct Documents(Arc<HashMap<String, Value>>);
+
+impl Retrieve for Documents {
+    fn retrieve(&self, uri: &Uri<&str>) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        self.0.get(uri.as_str()).cloned().ok_or_else(|| {
+            SchemaError::Missing(uri.as_str().to_string())
+                .to_string()
+                .into()
+        })
+    }
+}
+
+pub struct Schemas {
+    documents: Arc<HashMap<String, Value>>,
+    // The schemas that have been compiled so far, since most are only used by one API
+    validators: Mutex<HashMap<String, Arc<Validator>>>,
+    // The versions of requests and responses that weren't validated, because their schemas
+    // aren't bundled, each of which is reported the first time
+    unvalidated: Mutex<BTreeSet<(Specification, ApiVersion)>>,
+}
+
+impl Schemas {
+    // Load the bundled schemas, and compile those of every supported version, so that a missing or
+    // invalid schema is found at startup rather than by a request
+    pub fn bundled() -> Result<Self, SchemaError> {
+        fn collect(
+            dir: &'static Dir,
+            documents: &mut Vec<(String, Value)>,
+        ) -> Result<(), SchemaError> {
+            for entry in dir.entries() {
+                match entry {
+                    DirEntry::Dir(dir) => collect(dir, documents)?,
+                    DirEntry::File(file) => {
+                        let path = file.path().to_string_lossy().replace('\\', "/");
+                        if !path.ends_with(".json") {
+                            continue;
+                        }
+                        let document = serde_json::from_slice(file.contents())
+                            .map_err(|e| SchemaError::Schema(format!("{}: {}", path, e)))?;
+                        documents.push((path, document));
+                    }
+                }
+            }
+            Ok(())
+        }
+        let mut documents = Vec::new();
+        collect(&BUNDLED_SCHEMAS, &mut documents)?;
+        let schemas = Schemas::new(documents);
+        for spec in [Specification::Is04, Specification::Is05] {
+            for version in schemas.versions(spec) {
+                for name in spec.schemas(&version) {
+                    schemas.validator(&make_schema_uri(spec, &version, name))?;
+                }
+            }
+        }
+        Ok(schemas)
+    }
+
+    // Load the schemas, by their path in the schemas directory, e.g. "is-04/v1.3/node.json"
+    pub fn new(documents: impl IntoIterator<Item = (String, Value)>) -> Self {
+        let documents = documents
+            .into_iter()
+            .map(|(path, mut document)| {
+                let uri = format!("file:///{}", path);
+                // The schemas are draft-04, in which "id" sets the base URI for their references
+                if let Some(document) = document.as_object_mut() {
+                    document.insert("id".to_string(), Value::from(uri.clone()));
+                }
+                (uri, document)
+            })
+            .collect();
+        Schemas {
+            documents: Arc::new(documents),
+            validators: Mutex::new(HashMap::new()),
+            unvalidated: Mutex::new(BTreeSet::new()),
+        }
+    }
+
+    // The versions of the specification whose schemas are loaded, e.g. from "is-04/v1.3/"
+    // Requests and responses of other versions aren't validated
+    pub fn versions(&self, spec: Specification) -> BTreeSet<ApiVersion> {
+        let prefix = format!("file:///{}/", spec.name());
+        self.documents
+            .keys()
+            .filter_map(|uri| uri.strip_prefix(&prefix)?.split_once('/'))
+            .filter_map(|(version, _)| ApiVersion::parse(version))
+            .collect()
+    }
+
+    // The versions of the specification whose requests or responses weren't validated so far
+    pub fn unvalidated_versions(&self, spec: Specification) -> Vec<ApiVersion> {
+        let unvalidated = self.unvalidated.lock().unwrap();
+        unvalidated
+            .iter()
+            .filter(|(unvalidated_spec, _)| *unvalidated_spec == spec)
+            .map(|(_, version)| *version)
+            .collect()
+    }
+
+    // Select the schema of a request or response, if its version is loaded, and otherwise report
+    // that requests and responses of that version aren't validated
+    fn select(
+        &self,
+        spec: Specification,
+        select: SchemaSelector,
+        path: &str,
+        gate: &Logger,
+    ) -> Option<(ApiVersion, &'static str)> {
+        let (version, name) = select(path)?;
+        if self.versions(spec).contains(&version) {
+            return Some((version, name));
+        }
+        if self.unvalidated.lock().unwrap().insert((spec, version)) {
+            warn!(
+                gate,
+                "No {} {} schemas are bundled, so its requests and responses aren't validated",
+                spec.name(),
+                version
+            );
+        }
+        None
+    }
+
+    fn validator(&self, uri: &str) -> Result<Arc<Validator>, SchemaError> {
+        let mut validators = self.validators.lock().unwrap();
+        if let Some(validator) = validators.get(uri) {
+            return Ok(validator.clone());
+        }
+        let schema = self
+            .documents
+            .get(uri)
+            .ok_or_else(|| SchemaError::Missing(uri.to_string()))?;
+        let validator = jsonschema::options()
+            .with_draft(Draft::Draft4)
+            .with_retriever(Documents(self.documents.clone()))
+            .build(schema)
+            .map_err(|e| SchemaError::Schema(e.to_string()))?;
+        let validator = Arc::new(validator);
+        validators.insert(uri.to_string(), validator.clone());
+        Ok(validator)
+    }
+
+    // Validate the JSON against the named schema of the specified version, e.g. "node.json"
+    pub fn validate(
+        &self,
+        spec: Specification,
+        version: &ApiVersion,
+        name: &str,
+        instance: &Value,
+    ) -> Result<(), SchemaError> {
+        let validator = self.validator(&make_schema_uri(spec, version, name))?;
+        let errors: Vec<String> = validator
+            .iter_errors(instance)
+            .map(|e| {
+                let path = e.instance_path.to_string();
+                let path = if path.is_empty() { "/" } else { &path };
+                format!("{}: {}", path, e)
+            })
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(SchemaError::Invalid(errors))
+        }
+    }
+}
+
+// Split the path of an API, e.g. "/x-nmos/node/v1.3/self", into its version and the rest
+fn split_api_path<'a>(path: &'a str, api: &str) -> Option<(ApiVersion, Vec<&'a str>)> {
+    let mut segments = path.split('/').filter(|segment| !segment.is_empty());
+    if segments.next() != Some("x-nmos") || segments.next() != Some(api) {
+        return None;
+    }
+    let version = ApiVersion::parse(segments.next()?)?;
+    Some((version, segments.collect()))
+}
+
+// Select the schema of a Registration API request, i.e. to register a resource
+pub fn registration_request_schema(path: &str) -> Option<(ApiVersion, &'static str)> {
+    match split_api_path(path, "registration")? {
+        (version, rest) if rest == ["resource"] => Some((version, REGISTRATION_REQUEST_SCHEMA)),
+        _ => None,
+    }
+}
+
+// Select the schema of a Node API response
+pub fn node_api_response_schema(path: &str) -> Option<(ApiVersion, &'static str)> {
+    let (version, rest) = split_api_path(path, "node")?;
+    let name = match rest.as_slice() {
+        ["self"] => "node.json",
+        ["devices"] => "devices.json",
+        ["devices", _] => "device.json",
+        ["sources"] => "sources.json",
+        ["sources", _] => "source.json",
+        ["flows"] => "flows.json",
+        ["flows", _] => "flow.json",
+        ["senders"] => "senders.json",
+        ["senders", _] => "sender.json",
+        ["receivers"] => "receivers.json",
+        ["receivers", _] => "receiver.json",
+        _ => return None,
+    };
+    Some((version, name))
+}
+
+// Select the schema of a Connection API request, i.e. to change the staged parameters
+pub fn connection_patch_schema(path: &str) -> Option<(ApiVersion, &'static str)> {
+    let (version, rest) = split_api_path(path, "connection")?;
+    match rest.as_slice() {
+        ["single", "senders", _, "staged"] => Some((version, sender_staged_schema(&version))),
+        ["single", "receivers", _, "staged"] => Some((version, receiver_staged_schema(&version))),
+        _ => None,
+    }
+}
+
+// A request whose body doesn't match the schema, or couldn't be validated
+#[derive(Debug)]
+pub struct InvalidBody(pub SchemaError);
+
+impl warp::reject::Reject for InvalidBody {}
+
+// Make the error response, with the schema errors in its debug field
+pub fn make_schema_error_response(code: StatusCode, error: &SchemaError) -> Response {
+    let body = make_error_response_body(code, "", Some(&error.to_string()));
+    warp::reply::with_status(warp::reply::json(&body), code).into_response()
+}
+
+// Extract the JSON body of the request, if it matches the selected schema, or reject the request
+// with InvalidBody
+pub fn validated_json_body(
+    schemas: Arc<Schemas>,
+    spec: Specification,
+    select: SchemaSelector,
+    gate: Logger,
+) -> impl Filter<Extract = (Value,), Error = Rejection> + Clone {
+    warp::path::full()
+        .and(warp::body::json())
+        .and_then(move |path: FullPath, body: Value| {
+            let schemas = schemas.clone();
+            let gate = gate.clone();
+            async move {
+                let Some((version, name)) = schemas.select(spec, select, path.as_str(), &gate)
+                else {
+                    return Ok(body);
+                };
+                match schemas.validate(spec, &version, name, &body) {
+                    Ok(()) => Ok(body),
+                    Err(e @ SchemaError::Invalid(_)) => Err(warp::reject::custom(InvalidBody(e))),
+                    Err(e) => {
+                        error!(gate, "Unable to validate request"; "path" => path.as_str(), "error" => %e);
+                        Err(warp::reject::custom(InvalidBody(e)))
+                    }
+                }
+            }
+        })
+}
+
+// Respond to a request rejected with InvalidBody with 400 Bad Request, or with 500 Internal Server
+// Error if the fault is the server's own, i.e. its schema is missing or invalid
+pub async fn recover_invalid_body(rejection: Rejection) -> Result<Response, Rejection> {
+    match rejection.find::<InvalidBody>() {
+        Some(InvalidBody(e @ SchemaError::Invalid(_))) => {
+            Ok(make_schema_error_response(StatusCode::BAD_REQUEST, e))
+        }
+        Some(InvalidBody(e)) => Ok(make_schema_error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            e,
+        )),
+        None => Err(rejection),
+    }
+}
+
+// Validate the successful JSON responses of the API against the selected schema, replacing those
+// that don't match with 500 Internal Server Error, since the fault is the server's own
+// Responses of another Content-Type, e.g. the HTML rendering of the API, aren't validated
+pub fn with_validated_responses<F>(
+    api: F,
+    schemas: Arc<Schemas>,
+    spec: Specification,
+    select: SchemaSelector,
+    gate: Logger,
+) -> impl Filter<Extract = (Response,), Error = Rejection> + Clone
+where
+    F: Filter<Extract = (Response,), Error = Rejection> + Clone + Send + Sync + 'static,
+{
+    warp::path::full()
+        .and(api)
+        .and_then(move |path: FullPath, response: Response| {
+            let schemas = schemas.clone();
+            let gate = gate.clone();
+            async move {
+                let Some((version, name)) = schemas.select(spec, select, path.as_str(), &gate)
+                else {
+                    return Ok::<_, Rejection>(response);
+                };
+                let is_json = response
+                    .headers()
+                    .get(header::CONTENT_TYPE)
+                    .and_then(|value| value.to_str().ok())
+                    .is_some_and(|value| value.starts_with("application/json"));
+                if !response.status().is_success() || !is_json {
+                    return Ok(response);
+                }
+                let (parts, body) = response.into_parts();
+                let body = warp::hyper::body::to_bytes(body).await.unwrap_or_default();
+                let result = match serde_json::from_slice(&body) {
+                    Ok(instance) => schemas.validate(spec, &version, name, &instance),
+                    Err(e) => Err(SchemaError::Invalid(vec![format!("/: {}", e)])),
+                };
+                if let Err(e) = result {
+                    error!(gate, "Invalid response"; "path" => path.as_str(), "errors" => %e);
+                    return Ok(make_schema_error_response(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        &e,
+                    ));
+                }
+                Ok(Response::from_parts(parts, body.into()))
+            }
+        })
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // Just enough of the IS-04 schemas to check the references between them are resolved
+    fn make_schemas() -> Schemas {
+        Schemas::new([
+            (
+                "is-04/v1.3/resource_core.json".to_string(),
+                json!({
+                    "type": "object",
+                    "required": ["id", "version", "label"],
+                    "properties": {"id": {"type": "string", "pattern": "^[0-9a-f]{8}-"}}
+                }),
+            ),
+            (
+                "is-04/v1.3/node.json".to_string(),
+                json!({
+                    "$schema": "http://json-schema.org/draft-04/schema#",
+                    "allOf": [
+                        {"$ref": "resource_core.json"},
+                        {
+                            "type": "object",
+                            "required": ["services", "interfaces"],
+                            "properties": {
+                                "services": {"type": "array", "items": {"required": ["href", "type"]}},
+                                "interfaces": {"type": "array", "items": {"required": ["chassis_id", "port_id", "name"]}}
+                            }
+                        }
+                    ]
+                }),
+            ),
+            (
+                format!("is-04/v1.3/{}", REGISTRATION_REQUEST_SCHEMA),
+                json!({
+                    "type": "object",
+                    "required": ["type", "data"],
+                    "properties": {"data": {"$ref": "node.json"}}
+                }),
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_validate() {
+        let schemas = make_schemas();
+        let v1_3 = ApiVersion::new(1, 3);
+        let node: Value = serde_json::from_str(include_str!("../node.json")).unwrap();
+        assert_eq!(
+            schemas.validate(Specification::Is04, &v1_3, "node.json", &node),
+            Ok(())
+        );
+
+        let node = json!({
+            "id": "test",
+            "version": "1441973902:879053935",
+            "label": "",
+            "services": [{"service": "urn:x-nmos:service:query", "version": "v1.1"}],
+            "interfaces": [{"if_name": "eth0"}]
+        });
+        let Err(SchemaError::Invalid(errors)) =
+            schemas.validate(Specification::Is04, &v1_3, "node.json", &node)
+        else {
+            panic!("node should be invalid");
+        };
+        for path in ["/id: ", "/services/0: ", "/interfaces/0: "] {
+            assert!(errors.iter().any(|e| e.starts_with(path)), "{:?}", errors);
+        }
+
+        assert!(matches!(
+            schemas.validate(
+                Specification::Is04,
+                &ApiVersion::new(1, 0),
+                "node.json",
+                &node
+            ),
+            Err(SchemaError::Missing(_))
+        ));
+        assert_eq!(
+            node_api_response_schema("/x-nmos/node/v1.2/devices/abc/"),
+            Some((ApiVersion::new(1, 2), "device.json"))
+        );
+        assert_eq!(
+            connection_patch_schema("/x-nmos/connection/v1.1/single/receivers/abc/staged"),
+            Some((ApiVersion::new(1, 1), "receiver-stage-schema.json"))
+        );
+        assert_eq!(
+            connection_patch_schema("/x-nmos/connection/v1.0/single/senders/abc/staged"),
+            Some((ApiVersion::new(1, 0), "v1.0-sender-stage-schema.json"))
+        );
+    }
+
+    #[test]
+    fn test_bundled_schemas() {
+        let schemas = Schemas::bundled().unwrap();
+        let v1_3 = ApiVersion::new(1, 3);
+        let node: Value = serde_json::from_str(include_str!("../node.json")).unwrap();
+        let registration = json!({"type": "node", "data": node});
+        assert_eq!(
+            schemas.validate(
+                Specification::Is04,
+                &v1_3,
+                REGISTRATION_REQUEST_SCHEMA,
+                &registration
+            ),
+            Ok(())
+        );
+
+        let registration = json!({"type": "device", "data": node});
+        assert!(matches!(
+            schemas.validate(
+                Specification::Is04,
+                &v1_3,
+                REGISTRATION_REQUEST_SCHEMA,
+                &registration
+            ),
+            Err(SchemaError::Invalid(_))
+        ));
+
+        let staged = json!({
+            "master_enable": true,
+            "activation": {"mode": "activate_immediate", "requested_time": null},
+            "transport_params": [{"destination_ip": "auto", "destination_port": 5004}]
+        });
+        for version in schemas.versions(Specification::Is05) {
+            assert_eq!(
+                schemas.validate(
+                    Specification::Is05,
+                    &version,
+                    sender_staged_schema(&version),
+                    &staged
+                ),
+                Ok(())
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_validated_json_body() {
+        let gate = Logger::root(slog::Discard, slog::o!());
+        let route = warp::post()
+            .and(warp::path!("x-nmos" / "registration" / String / "resource"))
+            .and(validated_json_body(
+                Arc::new(make_schemas()),
+                Specification::Is04,
+                registration_request_schema,
+                gate,
+            ))
+            .map(|_, _| warp::reply::with_status("", StatusCode::CREATED).into_response())
+            .recover(recover_invalid_body);
+
+        let node: Value = serde_json::from_str(include_str!("../node.json")).unwrap();
+        let response = warp::test::request()
+            .method("POST")
+            .path("/x-nmos/registration/v1.3/resource")
+            .json(&json!({"type": "node", "data": node}))
+            .reply(&route)
+            .await;
+        assert_eq!(response.status(), 201);
+
+        let response = warp::test::request()
+            .method("POST")
+            .path("/x-nmos/registration/v1.3/resource")
+            .json(&json!({"type": "node", "data": {"services": []}}))
+            .reply(&route)
+            .await;
+        assert_eq!(response.status(), 400);
+        let body: Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["code"], 400);
+        assert_eq!(body["error"], "Bad Request");
+        assert!(body["debug"].as_str().unwrap().contains("/data: "));
+    }
+
+    #[tokio::test]
+    async fn test_with_validated_responses() {
+        let gate = Logger::root(slog::Discard, slog::o!());
+        let api = warp::path!("x-nmos" / "node" / String / String).map(
+            |version: String, resource: String| {
+                let node: Value = serde_json::from_str(include_str!("../node.json")).unwrap();
+                match (version.as_str(), resource.as_str()) {
+                    ("v1.3", "self") => warp::reply::json(&node).into_response(),
+                    ("v1.3", "devices") => warp::reply::html("<html></html>").into_response(),
+                    _ => warp::reply::json(&json!({"id": "test"})).into_response(),
+                }
+            },
+        );
+        let schemas = Arc::new(Schemas::bundled().unwrap());
+        let route = with_validated_responses(
+            api,
+            schemas.clone(),
+            Specification::Is04,
+            node_api_response_schema,
+            gate,
+        );
+
+        let response = warp::test::request()
+            .path("/x-nmos/node/v1.3/self")
+            .reply(&route)
+            .await;
+        assert_eq!(response.status(), 200);
+
+        let response = warp::test::request()
+            .path("/x-nmos/node/v1.3/senders")
+            .reply(&route)
+            .await;
+        assert_eq!(response.status(), 500);
+
+        // Responses that aren't JSON, and versions without bundled schemas, aren't validated, and
+        // the latter are reported
+        assert!(schemas.unvalidated_versions(Specification::Is04).is_empty());
+        for path in ["/x-nmos/node/v1.3/devices", "/x-nmos/node/v1.2/self"] {
+            let response = warp::test::request().path(path).reply(&route).await;
+            assert_eq!(response.status(), 200, "{}", path);
+        }
+        assert_eq!(
+            schemas.unvalidated_versions(Specification::Is04),
+            [ApiVersion::new(1, 2)]
+        );
+    }
+}
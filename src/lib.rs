@@ -0,0 +1,38 @@
+// an implementation of IS-04 for NMOS in rust
+//
+
+pub mod admin_ui;
+pub mod api_listener;
+pub mod api_methods;
+pub mod api_downgrade;
+pub mod api_html;
+pub mod api_utils;
+pub mod api_version;
+pub mod authorization_server;
+pub mod capabilities;
+pub mod certificate;
+pub mod certificate_handlers;
+pub mod certificate_health;
+pub mod certificate_revocation;
+pub mod configuration_api;
+pub mod control_protocol;
+pub mod control_protocol_bulk_properties;
+pub mod control_protocol_classes;
+pub mod control_protocol_ident_beacon;
+pub mod control_protocol_methods;
+pub mod control_protocol_monitors;
+pub mod control_protocol_resources;
+pub mod control_protocol_ws_api;
+pub mod filesystem_route;
+pub mod header_policy;
+pub mod id;
+pub mod is04_resources;
+pub mod json_schema;
+pub mod metrics;
+pub mod resource;
+pub mod server_timing;
+pub mod settings;
+pub mod system_api;
+pub mod system_client;
+pub mod tai;
+pub mod types;
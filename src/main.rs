@@ -1,11 +1,6 @@
 // an implementation of IS-04 for NMOS in rust
 //
 
-pub mod admin_ui;
-pub mod api_downgrade;
-pub mod api_utils;
-pub mod capabilities;
-
 use hyper::rt::{Future, Stream};
 use hyper::Client;
 use std::fs::File;
@@ -0,0 +1,143 @@
+// Metrics of the APIs, in the Prometheus text exposition format
+// See https://prometheus.io/docs/instrumenting/exposition_formats/#text-based-format
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, Opts, Registry, TextEncoder,
+};
+use warp::http::header::CONTENT_TYPE;
+use warp::reply::Response;
+use warp::{Filter, Rejection, Reply};
+
+// Define the metrics, each of which is updated by the part of the implementation that it measures
+pub struct Metrics {
+    registry: Registry,
+    // Requests per API, e.g. "query", and response status
+    pub requests: IntCounterVec,
+    pub request_duration: HistogramVec,
+    pub tls_handshake_failures: IntCounter,
+}
+
+impl Metrics {
+    pub fn new() -> Result<Self, prometheus::Error> {
+        let registry = Registry::new_custom(Some("nmos".to_string()), None)?;
+        let requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "The number of API requests"),
+            &["api", "status"],
+        )?;
+        let request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "The time taken to process API requests",
+            ),
+            &["api"],
+        )?;
+        let tls_handshake_failures = IntCounter::new(
+            "tls_handshake_failures_total",
+            "The number of failed TLS handshakes with API clients",
+        )?;
+
+        registry.register(Box::new(requests.clone()))?;
+        registry.register(Box::new(request_duration.clone()))?;
+        registry.register(Box::new(tls_handshake_failures.clone()))?;
+
+        Ok(Metrics {
+            registry,
+            requests,
+            request_duration,
+            tls_handshake_failures,
+        })
+    }
+
+    // Render the metrics in the text exposition format
+    pub fn render(&self) -> Result<String, prometheus::Error> {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        String::from_utf8(buffer).map_err(|e| prometheus::Error::Msg(e.to_string()))
+    }
+}
+
+// Count the requests to the API, e.g. "query", by response status, and measure their duration, if
+// metrics are specified
+pub fn with_request_metrics<F>(
+    api: F,
+    api_name: &'static str,
+    metrics: Option<Arc<Metrics>>,
+) -> impl Filter<Extract = (Response,), Error = Rejection> + Clone
+where
+    F: Filter<Extract = (Response,), Error = Rejection> + Clone + Send + Sync + 'static,
+{
+    warp::any()
+        .map(Instant::now)
+        .and(api)
+        .map(move |received: Instant, response: Response| {
+            if let Some(metrics) = &metrics {
+                metrics
+                    .requests
+                    .with_label_values(&[api_name, response.status().as_str()])
+                    .inc();
+                metrics
+                    .request_duration
+                    .with_label_values(&[api_name])
+                    .observe(received.elapsed().as_secs_f64());
+            }
+            response
+        })
+}
+
+// Make the route for the metrics endpoint, at /metrics
+pub fn make_metrics_api(
+    metrics: Arc<Metrics>,
+) -> impl Filter<Extract = (Response,), Error = Rejection> + Clone {
+    warp::path!("metrics")
+        .and(warp::get())
+        .map(move || match metrics.render() {
+            Ok(body) => {
+                warp::reply::with_header(body, CONTENT_TYPE, TextEncoder::new().format_type())
+                    .into_response()
+            }
+            Err(e) => warp::reply::with_status(
+                e.to_string(),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            )
+            .into_response(),
+        })
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_metrics() {
+        let metrics = Arc::new(Metrics::new().unwrap());
+        let api =
+            warp::path!("x-nmos" / "query").map(|| warp::reply::json(&["v1.3/"]).into_response());
+        let api = with_request_metrics(api, "query", Some(metrics.clone()));
+        warp::test::request()
+            .path("/x-nmos/query")
+            .reply(&api)
+            .await;
+
+        let response = warp::test::request()
+            .path("/metrics")
+            .reply(&make_metrics_api(metrics))
+            .await;
+        assert_eq!(response.status(), 200);
+        assert!(response.headers()["content-type"]
+            .to_str()
+            .unwrap()
+            .starts_with("text/plain"));
+        let body = String::from_utf8(response.body().to_vec()).unwrap();
+        for line in [
+            "nmos_http_requests_total{api=\"query\",status=\"200\"} 1",
+            "nmos_http_request_duration_seconds_count{api=\"query\"} 1",
+            "nmos_tls_handshake_failures_total 0",
+        ] {
+            assert!(body.lines().any(|l| l == line), "{}", line);
+        }
+    }
+}
@@ -1,39 +1,29 @@
-use serde_json::{json, Value};
-use std::collections::HashMap;
-
 // Define the namespace for NMOS
-mod nmos {
+pub mod nmos {
     pub mod details {
-        // Define a function to create resource core JSON value
-        pub fn make_resource_core(
-            id: &str,
-            label: &str,
-            description: &str,
-            tags: HashMap<String, Value>,
-        ) -> Value {
-            json!({
-                "id": id,
-                "version": nmos::make_version(),
-                "label": label,
-                "description": description,
-                "tags": tags,
-            })
+        use serde_json::Value;
+
+        use crate::is04_resources::{ResourceBuilder, ResourceCoreBuilder, Tags};
+
+        // Define a function to create resource core JSON value, with a new version
+        pub fn make_resource_core(id: &str, label: &str, description: &str, tags: Tags) -> Value {
+            let core = ResourceCoreBuilder::new(id)
+                .label(label)
+                .description(description)
+                .tags(tags)
+                .build();
+            serde_json::to_value(core).expect("the resource core is valid JSON")
         }
 
         // Define a function to create resource core JSON value from settings
-        pub fn make_resource_core_from_settings(id: &str, settings: &nmos::Settings) -> Value {
+        pub fn make_resource_core_from_settings(id: &str, settings: &super::Settings) -> Value {
             let label = settings.get_label();
             let description = settings.get_description();
 
-            make_resource_core(id, &label, &description, HashMap::new())
+            make_resource_core(id, &label, &description, Tags::new())
         }
     }
 
-    // Define a function to make version JSON value
-    fn make_version() -> Value {
-        json!({"major": 1, "minor": 2})
-    }
-
     // Define a structure for settings
     pub struct Settings {
         label: String,
@@ -50,11 +40,11 @@ mod nmos {
         }
 
         pub fn get_label(&self) -> String {
-            &self.label
+            self.label.clone()
         }
 
         pub fn get_description(&self) -> String {
-            &self.description
+            self.description.clone()
         }
     }
 }
@@ -63,32 +53,50 @@ mod nmos {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::is04_resources::Tags;
+    use crate::tai::Tai;
+    use serde_json::{json, Value};
+
+    // Check the version of a resource, which must be a "<seconds>:<nanoseconds>" TAI timestamp,
+    // and remove it, so that the rest of the resource can be compared
+    fn take_version(resource: &mut Value) -> Tai {
+        let version = resource["version"].take();
+        Tai::parse(version.as_str().unwrap()).unwrap()
+    }
 
     #[test]
     fn test_make_resource_core() {
         let id = "resource_id";
         let label = "resource_label";
         let description = "resource_description";
-        let tags = HashMap::new();
-        let result = nmos::details::make_resource_core(id, label, description, tags);
+        let tags: Tags = [("location".to_string(), vec!["studio".to_string()])]
+            .into_iter()
+            .collect();
+        let mut result = nmos::details::make_resource_core(id, label, description, tags);
+        let version = take_version(&mut result);
         let expected = json!({
             "id": "resource_id",
-            "version": {"major": 1, "minor": 2},
+            "version": null,
             "label": "resource_label",
             "description": "resource_description",
-            "tags": {}
+            "tags": {"location": ["studio"]}
         });
         assert_eq!(result, expected);
+
+        // Every change has a later version
+        let mut result = nmos::details::make_resource_core(id, label, description, Tags::new());
+        assert!(take_version(&mut result) > version);
     }
 
     #[test]
     fn test_make_resource_core_from_settings() {
         let id = "resource_id";
         let settings = nmos::Settings::new("label_from_settings", "description_from_settings");
-        let result = nmos::details::make_resource_core_from_settings(id, &settings);
+        let mut result = nmos::details::make_resource_core_from_settings(id, &settings);
+        take_version(&mut result);
         let expected = json!({
             "id": "resource_id",
-            "version": {"major": 1, "minor": 2},
+            "version": null,
             "label": "label_from_settings",
             "description": "description_from_settings",
             "tags": {}
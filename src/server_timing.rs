@@ -0,0 +1,133 @@
+// Measure how long each API request takes to process, reporting it to the client in the
+// Server-Timing header and logging it with the request
+// See https://www.w3.org/TR/server-timing/
+
+use std::time::Instant;
+
+use slog::{info, Logger};
+use warp::http::header::{HeaderName, HeaderValue};
+use warp::http::Method;
+use warp::path::FullPath;
+use warp::reply::Response;
+use warp::{Filter, Rejection};
+
+// The name of the Server-Timing metric for the processing of the request
+pub const PROCESSING_METRIC: &str = "proc";
+
+pub const SERVER_TIMING: HeaderName = HeaderName::from_static("server-timing");
+pub const TIMING_ALLOW_ORIGIN: HeaderName = HeaderName::from_static("timing-allow-origin");
+
+// Make the Server-Timing header value for the processing duration
+pub fn make_server_timing(duration_ms: f64) -> HeaderValue {
+    HeaderValue::from_str(&format!("{};dur={:.3}", PROCESSING_METRIC, duration_ms))
+        .expect("a metric name and number are a valid header value")
+}
+
+// Stamp the arrival time of each request to the API, e.g. "query", and when the response is ready,
+// add the Server-Timing header and log a structured record of the request
+// Requests that the API rejects are left to be logged where the rejection is handled, since
+// another API may yet match them
+pub fn with_server_timing<F>(
+    api: F,
+    api_name: &'static str,
+    gate: Logger,
+) -> impl Filter<Extract = (Response,), Error = Rejection> + Clone
+where
+    F: Filter<Extract = (Response,), Error = Rejection> + Clone + Send + Sync + 'static,
+{
+    warp::any()
+        .map(Instant::now)
+        .and(warp::method())
+        .and(warp::path::full())
+        .and(api)
+        .map(
+            move |received: Instant, method: Method, path: FullPath, mut response: Response| {
+                let duration_ms = received.elapsed().as_secs_f64() * 1000.0;
+                response
+                    .headers_mut()
+                    .append(SERVER_TIMING, make_server_timing(duration_ms));
+                // Allow browsers to read the timing of cross-origin requests too
+                response
+                    .headers_mut()
+                    .entry(TIMING_ALLOW_ORIGIN)
+                    .or_insert(HeaderValue::from_static("*"));
+                info!(gate, "Sending response";
+                    "api" => api_name,
+                    "method" => %method,
+                    "path" => path.as_str(),
+                    "status" => response.status().as_u16(),
+                    "duration_ms" => duration_ms,
+                );
+                response
+            },
+        )
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use slog::KV;
+    use std::sync::{Arc, Mutex};
+    use warp::Reply;
+
+    // Collect the key-value pairs of the log records
+    struct Records(Arc<Mutex<Vec<String>>>);
+
+    impl slog::Drain for Records {
+        type Ok = ();
+        type Err = slog::Never;
+
+        fn log(
+            &self,
+            record: &slog::Record,
+            values: &slog::OwnedKVList,
+        ) -> Result<Self::Ok, Self::Err> {
+            struct Serializer(String);
+            impl slog::Serializer for Serializer {
+                fn emit_arguments(
+                    &mut self,
+                    key: slog::Key,
+                    value: &std::fmt::Arguments,
+                ) -> slog::Result {
+                    self.0.push_str(&format!("{}={} ", key, value));
+                    Ok(())
+                }
+            }
+            let mut serializer = Serializer(String::new());
+            record.kv().serialize(record, &mut serializer).unwrap();
+            values.serialize(record, &mut serializer).unwrap();
+            self.0.lock().unwrap().push(serializer.0);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_server_timing() {
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let gate = Logger::root(Records(records.clone()), slog::o!());
+        let api =
+            warp::path!("x-nmos" / "query").map(|| warp::reply::json(&["v1.3/"]).into_response());
+        let api = with_server_timing(api, "query", gate);
+
+        let response = warp::test::request()
+            .path("/x-nmos/query")
+            .reply(&api)
+            .await;
+        let server_timing = response.headers()["server-timing"].to_str().unwrap();
+        let duration = server_timing.strip_prefix("proc;dur=").unwrap();
+        assert!(duration.parse::<f64>().unwrap() >= 0.0);
+
+        let records = records.lock().unwrap();
+        assert_eq!(records.len(), 1);
+        for field in [
+            "api=query",
+            "method=GET",
+            "path=/x-nmos/query",
+            "status=200",
+            "duration_ms=",
+        ] {
+            assert!(records[0].contains(field), "{}", records[0]);
+        }
+    }
+}
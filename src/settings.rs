@@ -0,0 +1,190 @@
+// The settings of the API listeners and their certificates, which use the same names as the
+// fields of the JSON configuration file, so that they can be deserialized directly from it
+// See https://specs.amwa.tv/bcp-003-01/releases/v1.0.0/docs/1.0._Secure_Communication.html
+
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::admin_ui::AdminUiRoot;
+use crate::api_listener::{CipherPolicy, ClientAuthentication};
+use crate::certificate_health::DEFAULT_EXPIRY_WARNING_DAYS;
+use crate::header_policy::{HeaderPolicy, HeaderPolicyOverrides};
+use crate::tai::DEFAULT_TAI_OFFSET;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    // The address on which the API listeners are bound, empty for all interfaces
+    pub host_address: String,
+    // Whether the API listeners serve HTTPS rather than plain HTTP
+    pub server_secure: bool,
+    // The TLS protocol versions and cipher suites offered by the API listeners
+    pub cipher_policy: CipherPolicy,
+    // Whether clients of the API listeners must present a certificate issued by the CA, e.g. so
+    // that a Registration API only accepts registrations from known Nodes
+    pub client_authentication: ClientAuthentication,
+    // How often to reload the server certificates, in seconds, or 0 to only reload them on SIGHUP
+    pub certificate_reload_interval: u64,
+    // The PEM file containing the CA certificate(s) used to verify peers
+    pub ca_certificate_file: Option<String>,
+    // The server certificates, e.g. one RSA and one ECDSA
+    pub server_certificates: Vec<ServerCertificateSettings>,
+    // The server certificates as in earlier versions of the settings, in which the private key
+    // and certificate chain files are paired by index
+    pub private_key_files: Vec<String>,
+    pub certificate_chain_files: Vec<String>,
+    pub dh_param_file: Option<String>,
+    // The PEM files containing CRLs against which client-side TLS checks server certificates
+    pub crl_files: Vec<String>,
+    // The URL of a local OCSP responder with which client-side TLS checks server certificates
+    pub ocsp_responder: Option<String>,
+    // How many days before expiry certificates are reported as expiring
+    pub certificate_expiry_warning_days: i64,
+    // How often to check the certificate health, in seconds, or 0 to only check it at startup
+    pub certificate_health_interval: u64,
+    // The sources the admin UI may connect to, for the Content-Security-Policy, e.g. "'self'" and
+    // the URLs of the Query API and Connection API if they are on other ports
+    pub admin_ui_connect_src: Vec<String>,
+    // Whether the admin UI is served from the filesystem root or from the files embedded in the
+    // binary, i.e. "filesystem" or "embedded"
+    pub admin_ui_root: AdminUiRoot,
+    // The CORS and security response headers of every API, which should be checked with
+    // crate::header_policy::check_header_policies when the settings are loaded
+    pub header_policy: HeaderPolicy,
+    // The differences from the header policy for specific APIs, by name, e.g. "query" or "admin"
+    pub api_header_policies: HeaderPolicyOverrides,
+    // The difference between TAI and UTC, in seconds, for resource versions and activation times,
+    // which is applied by Settings::apply
+    pub tai_offset: i64,
+    // The seed of the ids of the Node's resources, either a UUID or another name for the Node that
+    // doesn't change, e.g. its host name, so that they are the same after every restart, or unset
+    // for new ids every time
+    // See crate::id::IdGenerator::from_settings
+    pub seed_id: Option<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            host_address: String::new(),
+            server_secure: false,
+            cipher_policy: CipherPolicy::default(),
+            client_authentication: ClientAuthentication::default(),
+            certificate_reload_interval: 3600,
+            ca_certificate_file: None,
+            server_certificates: Vec::new(),
+            private_key_files: Vec::new(),
+            certificate_chain_files: Vec::new(),
+            dh_param_file: None,
+            crl_files: Vec::new(),
+            ocsp_responder: None,
+            certificate_expiry_warning_days: DEFAULT_EXPIRY_WARNING_DAYS,
+            certificate_health_interval: 24 * 60 * 60,
+            admin_ui_connect_src: vec!["'self'".to_string()],
+            admin_ui_root: AdminUiRoot::default(),
+            header_policy: HeaderPolicy::default(),
+            api_header_policies: HeaderPolicyOverrides::new(),
+            tai_offset: DEFAULT_TAI_OFFSET,
+            seed_id: None,
+        }
+    }
+}
+
+impl Settings {
+    // Check the settings and apply those that are process-wide, rather than those of a specific
+    // API, when the settings are loaded
+    // Nothing is applied if the settings are invalid, e.g. if a header policy is misconfigured
+    pub fn apply(&self) -> Result<(), String> {
+        crate::header_policy::check_header_policies(self)?;
+        crate::tai::set_tai_offset(self.tai_offset);
+        Ok(())
+    }
+
+    // The interval at which to reload the server certificates, or None to only reload them on
+    // SIGHUP, for crate::api_listener::run_certificate_reloader
+    pub fn certificate_reload_period(&self) -> Option<Duration> {
+        match self.certificate_reload_interval {
+            0 => None,
+            seconds => Some(Duration::from_secs(seconds)),
+        }
+    }
+
+    // The interval at which to check the certificate health, or None to only check it at startup,
+    // for crate::certificate_health::run_certificate_health_monitor
+    pub fn certificate_health_period(&self) -> Option<Duration> {
+        match self.certificate_health_interval {
+            0 => None,
+            seconds => Some(Duration::from_secs(seconds)),
+        }
+    }
+}
+
+// Define the files of one server certificate
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ServerCertificateSettings {
+    // e.g. "ECDSA" or "RSA", for information only, since the key type is read from the file
+    pub key_algorithm: Option<String>,
+    pub private_key_file: Option<String>,
+    pub certificate_chain_file: Option<String>,
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tai::{set_tai_offset, tai_now, tai_offset};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn test_certificate_reload_period() {
+        let settings: Settings =
+            serde_json::from_str(r#"{"certificate_reload_interval": 0}"#).unwrap();
+        assert_eq!(settings.certificate_reload_period(), None);
+        assert_eq!(
+            Settings::default().certificate_reload_period(),
+            Some(Duration::from_secs(3600))
+        );
+    }
+
+    // Restore the process-wide UTC-TAI offset when a test that changes it ends, even if it fails
+    struct RestoreTaiOffset(i64);
+
+    impl Drop for RestoreTaiOffset {
+        fn drop(&mut self) {
+            set_tai_offset(self.0);
+        }
+    }
+
+    #[test]
+    fn test_apply() {
+        let _restore = RestoreTaiOffset(tai_offset());
+        let utc = || {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+        };
+        let settings: Settings = serde_json::from_str(r#"{"tai_offset": 0}"#).unwrap();
+        settings.apply().unwrap();
+        let before = utc();
+        let tai = tai_now().seconds;
+        assert!(tai >= before && tai <= utc());
+
+        Settings::default().apply().unwrap();
+        let before = utc();
+        let tai = tai_now().seconds;
+        assert!(tai >= before + 37 && tai <= utc() + 37);
+
+        // Any origin with credentials is rejected, and the TAI offset left as it was
+        let settings: Settings = serde_json::from_str(
+            r#"{"tai_offset": 0, "api_header_policies": {"node": {"allow_credentials": true}}}"#,
+        )
+        .unwrap();
+        assert!(settings.apply().unwrap_err().starts_with("node API: "));
+        let before = utc();
+        let tai = tai_now().seconds;
+        assert!(tai >= before + 37 && tai <= utc() + 37);
+    }
+}
@@ -0,0 +1,228 @@
+// IS-09 System API, which provides global configuration parameters to all the NMOS Nodes in a
+// facility, such as the IS-04 heartbeat interval and the PTP domain
+// See https://specs.amwa.tv/is-09/releases/v1.0.0/docs/3.0._Global_Configuration_Parameters.html
+// and https://specs.amwa.tv/is-09/releases/v1.0.0/APIs/SystemAPI.html
+
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+
+use mdns_sd::{ServiceDaemon, ServiceInfo};
+use serde::{Deserialize, Serialize};
+use slog::{info, Logger};
+use warp::http::Method;
+use warp::{Filter, Rejection, Reply};
+
+use crate::api_methods::{with_allowed_methods, RouteMethods};
+use crate::header_policy::{make_header_policy, with_header_policy};
+use crate::metrics::{with_request_metrics, Metrics};
+use crate::server_timing::with_server_timing;
+use crate::settings::Settings;
+use crate::tai::make_version;
+
+// The DNS-SD service type of the System API
+// See https://specs.amwa.tv/is-09/releases/v1.0.0/docs/4.0._Discovery.html
+pub const SYSTEM_SERVICE_TYPE: &str = "_nmos-system._tcp.local.";
+
+// IS-04 parameters
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Is04Settings {
+    // How often Nodes should perform a heartbeat against the Registration API, in seconds
+    pub heartbeat_interval: u32,
+}
+
+// PTP parameters, for the Nodes' clocks and media
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PtpSettings {
+    pub announce_receipt_timeout: u32,
+    pub domain_number: u8,
+}
+
+// Target for RFC 3164 (syslog) or RFC 5424 (syslogv2) logging
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SyslogSettings {
+    pub hostname: String,
+    pub port: u16,
+}
+
+// The global configuration resource
+// See https://specs.amwa.tv/is-09/releases/v1.0.0/APIs/schemas/with-refs/global.html
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SystemGlobal {
+    pub id: String,
+    pub version: String,
+    pub label: String,
+    pub description: String,
+    pub tags: BTreeMap<String, Vec<String>>,
+    pub is04: Is04Settings,
+    pub ptp: PtpSettings,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub syslog: Option<SyslogSettings>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub syslogv2: Option<SyslogSettings>,
+}
+
+impl Default for SystemGlobal {
+    // The defaults are those that Nodes use when no System API is available
+    fn default() -> Self {
+        SystemGlobal {
+            id: String::new(),
+            version: "0:0".to_string(),
+            label: String::new(),
+            description: String::new(),
+            tags: BTreeMap::new(),
+            is04: Is04Settings {
+                heartbeat_interval: 5,
+            },
+            ptp: PtpSettings {
+                announce_receipt_timeout: 3,
+                domain_number: 0,
+            },
+            syslog: None,
+            syslogv2: None,
+        }
+    }
+}
+
+impl SystemGlobal {
+    pub fn new(id: &str, label: &str) -> Self {
+        SystemGlobal {
+            id: id.to_string(),
+            version: make_version(),
+            label: label.to_string(),
+            ..Default::default()
+        }
+    }
+
+    // Apply a change to the configuration, updating its version
+    pub fn modify(&mut self, f: impl FnOnce(&mut SystemGlobal)) {
+        f(self);
+        self.version = make_version();
+    }
+}
+
+// Advertise the System API via DNS-SD, with the TXT records required by IS-09
+// See https://specs.amwa.tv/is-09/releases/v1.0.0/docs/4.0._Discovery.html#dns-sd-txt-records
+pub fn advertise_system_api(
+    daemon: &ServiceDaemon,
+    instance_name: &str,
+    host_name: &str,
+    address: IpAddr,
+    port: u16,
+    priority: u32,
+    secure: bool,
+) -> Result<(), mdns_sd::Error> {
+    let priority = priority.to_string();
+    let properties = [
+        ("api_proto", if secure { "https" } else { "http" }),
+        ("api_ver", "v1.0"),
+        ("api_auth", "false"),
+        ("pri", priority.as_str()),
+    ];
+    let info = ServiceInfo::new(
+        SYSTEM_SERVICE_TYPE,
+        instance_name,
+        host_name,
+        address,
+        port,
+        &properties[..],
+    )?;
+    daemon.register(info)
+}
+
+// The methods allowed by each route of the System API
+pub const SYSTEM_API_METHODS: &[RouteMethods] = &[
+    ("/x-nmos/system", &[Method::GET]),
+    ("/x-nmos/system/v1.0", &[Method::GET]),
+    ("/x-nmos/system/v1.0/global", &[Method::GET]),
+];
+
+// Make the routes for the System API, at /x-nmos/system/v1.0
+pub fn make_system_api(
+    global: Arc<Mutex<SystemGlobal>>,
+    settings: &Settings,
+    metrics: Option<Arc<Metrics>>,
+    gate: Logger,
+) -> impl Filter<Extract = (warp::reply::Response,), Error = Rejection> + Clone {
+    let versions = warp::path!("x-nmos" / "system")
+        .and(warp::get())
+        .map(|| warp::reply::json(&["v1.0/"]).into_response());
+
+    let resources = warp::path!("x-nmos" / "system" / "v1.0")
+        .and(warp::get())
+        .map(|| warp::reply::json(&["global/"]).into_response());
+
+    let global_gate = gate.clone();
+    let global = warp::path!("x-nmos" / "system" / "v1.0" / "global")
+        .and(warp::get())
+        .map(move || {
+            let global = global.lock().unwrap().clone();
+            info!(
+                global_gate,
+                "Serving global configuration version: {}", global.version
+            );
+            warp::reply::json(&global).into_response()
+        });
+
+    let api = with_header_policy(
+        with_allowed_methods(
+            versions.or(resources).unify().or(global).unify(),
+            SYSTEM_API_METHODS,
+        ),
+        make_header_policy(settings, "system"),
+    );
+    with_server_timing(with_request_metrics(api, "system", metrics), "system", gate)
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    #[tokio::test]
+    async fn test_global() {
+        let mut global = SystemGlobal::new("3b8be755-08ff-452b-b217-c9151eb21193", "Facility");
+        global.modify(|global| {
+            global.is04.heartbeat_interval = 10;
+            global.syslogv2 = Some(SyslogSettings {
+                hostname: "syslog.example.com".to_string(),
+                port: 6514,
+            });
+        });
+        let gate = Logger::root(slog::Discard, slog::o!());
+        let metrics = Arc::new(Metrics::new().unwrap());
+        let api = make_system_api(
+            Arc::new(Mutex::new(global)),
+            &Settings::default(),
+            Some(metrics.clone()),
+            gate,
+        );
+
+        let response = warp::test::request()
+            .path("/x-nmos/system/v1.0/global")
+            .reply(&api)
+            .await;
+        assert_eq!(response.status(), 200);
+        assert!(response.headers().contains_key("server-timing"));
+        assert_eq!(response.headers()["x-content-type-options"], "nosniff");
+        let body: Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["is04"], json!({ "heartbeat_interval": 10 }));
+        assert_eq!(
+            body["ptp"],
+            json!({ "announce_receipt_timeout": 3, "domain_number": 0 })
+        );
+        assert_eq!(body["syslogv2"]["port"], 6514);
+        assert!(body.get("syslog").is_none());
+
+        let response = warp::test::request()
+            .path("/x-nmos/system/v1.0")
+            .reply(&api)
+            .await;
+        assert_eq!(response.body().as_ref(), br#"["global/"]"#);
+        assert_eq!(
+            metrics.requests.with_label_values(&["system", "200"]).get(),
+            2
+        );
+    }
+}
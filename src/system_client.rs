@@ -0,0 +1,392 @@
+// IS-09 System API client, which discovers the System API via DNS-SD and keeps the Node's copy of
+// the global configuration up to date, so that e.g. the registration client's heartbeat interval
+// follows the facility-wide setting
+// See https://specs.amwa.tv/is-09/releases/v1.0.0/docs/4.0._Discovery.html
+// and https://specs.amwa.tv/is-09/releases/v1.0.0/docs/5.0._Node_Behaviour.html
+
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+
+use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
+use rustls::pki_types::ServerName;
+use slog::{info, warn, Logger};
+use tokio::net::TcpStream;
+use tokio::sync::watch;
+use tokio::time::{sleep, timeout_at, Instant};
+use warp::hyper::client::conn;
+use warp::hyper::{body, header, Body, Client, Request, Response, Uri};
+
+use crate::certificate_revocation::ClientTls;
+use crate::system_api::{SystemGlobal, SYSTEM_SERVICE_TYPE};
+
+// Priorities of 100 or more are reserved for development and testing
+pub const MAX_PRODUCTION_PRIORITY: u32 = 99;
+
+// Define the errors from fetching the global configuration
+#[derive(Debug)]
+pub enum SystemClientError {
+    NotFound,
+    Request(String),
+    Status(u16),
+    Body(String),
+}
+
+impl fmt::Display for SystemClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SystemClientError::NotFound => write!(f, "no System API was discovered"),
+            SystemClientError::Request(message) => write!(f, "request failed: {}", message),
+            SystemClientError::Status(status) => write!(f, "unexpected status: {}", status),
+            SystemClientError::Body(message) => write!(f, "invalid response: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for SystemClientError {}
+
+// Define a discovered System API instance
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SystemService {
+    pub priority: u32,
+    // The base URL of the API, e.g. http://192.0.2.1:8080/x-nmos/system/v1.0
+    pub base_url: String,
+}
+
+// Extract the System API instance from a resolved DNS-SD service, if it supports v1.0
+// An HTTPS instance is identified by its host name, which its certificate must match
+// See https://specs.amwa.tv/bcp-003-01/releases/v1.0.0/docs/1.0._Secure_Communication.html#dns-sd
+fn make_system_service(info: &ServiceInfo) -> Option<SystemService> {
+    let api_proto = info.get_property_val_str("api_proto").unwrap_or("http");
+    if api_proto != "http" && api_proto != "https" {
+        return None;
+    }
+    let api_ver = info.get_property_val_str("api_ver").unwrap_or_default();
+    if !api_ver.split(',').any(|version| version == "v1.0") {
+        return None;
+    }
+    let priority = info.get_property_val_str("pri")?.parse().ok()?;
+    let host = if api_proto == "https" {
+        info.get_hostname().trim_end_matches('.').to_string()
+    } else {
+        match info.get_addresses().iter().min()? {
+            std::net::IpAddr::V4(address) => address.to_string(),
+            std::net::IpAddr::V6(address) => format!("[{}]", address),
+        }
+    };
+    Some(SystemService {
+        priority,
+        base_url: format!(
+            "{}://{}:{}/x-nmos/system/v1.0",
+            api_proto,
+            host,
+            info.get_port()
+        ),
+    })
+}
+
+// Order the discovered instances by priority, lowest value first, optionally ignoring those
+// reserved for development
+pub fn select_system_services(
+    mut services: Vec<SystemService>,
+    allow_development: bool,
+) -> Vec<SystemService> {
+    services.retain(|service| allow_development || service.priority <= MAX_PRODUCTION_PRIORITY);
+    services.sort_by(|a, b| (a.priority, &a.base_url).cmp(&(b.priority, &b.base_url)));
+    services.dedup();
+    services
+}
+
+// Browse for System API instances for the specified time
+pub async fn discover_system_services(
+    daemon: &ServiceDaemon,
+    browse_time: Duration,
+) -> Result<Vec<SystemService>, mdns_sd::Error> {
+    let events = daemon.browse(SYSTEM_SERVICE_TYPE)?;
+    let deadline = Instant::now() + browse_time;
+    let mut services = Vec::new();
+    while let Ok(Ok(event)) = timeout_at(deadline, events.recv_async()).await {
+        if let ServiceEvent::ServiceResolved(info) = event {
+            services.extend(make_system_service(&info));
+        }
+    }
+    let _ = daemon.stop_browse(SYSTEM_SERVICE_TYPE);
+    Ok(services)
+}
+
+fn request_error(error: impl fmt::Display) -> SystemClientError {
+    SystemClientError::Request(error.to_string())
+}
+
+// Make a GET request over HTTPS, verifying the server certificate against the CA certificates of
+// the client-side TLS configuration
+async fn get_https(uri: Uri, tls: &ClientTls) -> Result<Response<Body>, SystemClientError> {
+    let authority = uri
+        .authority()
+        .ok_or_else(|| request_error("missing host"))?
+        .clone();
+    let host = authority
+        .host()
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    let server_name = ServerName::try_from(host.to_string()).map_err(request_error)?;
+    let stream = TcpStream::connect((host, authority.port_u16().unwrap_or(443)))
+        .await
+        .map_err(request_error)?;
+    let stream = tls
+        .connect(server_name, stream)
+        .await
+        .map_err(request_error)?;
+
+    let (mut sender, connection) = conn::handshake(stream).await.map_err(request_error)?;
+    tokio::spawn(connection);
+    let path = uri.path_and_query().map_or("/", |path| path.as_str());
+    let request = Request::get(path)
+        .header(header::HOST, authority.as_str())
+        .body(Body::empty())
+        .map_err(request_error)?;
+    sender.send_request(request).await.map_err(request_error)
+}
+
+// Fetch the global configuration from a System API instance, using the client-side TLS
+// configuration if the base URL is HTTPS
+pub async fn fetch_global(
+    base_url: &str,
+    tls: Option<&ClientTls>,
+) -> Result<SystemGlobal, SystemClientError> {
+    let uri: Uri = format!("{}/global", base_url)
+        .parse()
+        .map_err(request_error)?;
+    let response = match (uri.scheme_str(), tls) {
+        (Some("https"), Some(tls)) => get_https(uri, tls).await?,
+        (Some("https"), None) => return Err(request_error("no client-side TLS configuration")),
+        _ => Client::new().get(uri).await.map_err(request_error)?,
+    };
+    if !response.status().is_success() {
+        return Err(SystemClientError::Status(response.status().as_u16()));
+    }
+    let body = body::to_bytes(response.into_body())
+        .await
+        .map_err(request_error)?;
+    serde_json::from_slice(&body).map_err(|e| SystemClientError::Body(e.to_string()))
+}
+
+// Define the settings for the System API client
+#[derive(Clone)]
+pub struct SystemClientSettings {
+    // How long to browse for System API instances each time
+    pub browse_time: Duration,
+    // How often to refresh the global configuration once it has been fetched
+    pub refresh_interval: Duration,
+    // How long to wait before trying again if no instance could be used
+    pub retry_interval: Duration,
+    pub allow_development: bool,
+    // The client-side TLS configuration, without which HTTPS instances can't be used
+    pub tls: Option<ClientTls>,
+}
+
+impl Default for SystemClientSettings {
+    fn default() -> Self {
+        SystemClientSettings {
+            browse_time: Duration::from_secs(2),
+            refresh_interval: Duration::from_secs(60),
+            retry_interval: Duration::from_secs(10),
+            allow_development: false,
+            tls: None,
+        }
+    }
+}
+
+// Discover the System API instances and fetch the global configuration from the highest priority
+// instance that responds
+pub async fn update_global(
+    daemon: &ServiceDaemon,
+    settings: &SystemClientSettings,
+) -> Result<SystemGlobal, SystemClientError> {
+    let services = discover_system_services(daemon, settings.browse_time)
+        .await
+        .map_err(request_error)?;
+    let mut result = Err(SystemClientError::NotFound);
+    for service in select_system_services(services, settings.allow_development) {
+        result = fetch_global(&service.base_url, settings.tls.as_ref()).await;
+        if result.is_ok() {
+            break;
+        }
+    }
+    result
+}
+
+// Keep the global configuration up to date, publishing changes to the watch channel, e.g. to
+// run_heartbeats
+// The last known configuration, or the defaults, remain in effect while no System API is available
+pub async fn run_system_client(
+    daemon: ServiceDaemon,
+    settings: SystemClientSettings,
+    sender: watch::Sender<SystemGlobal>,
+    gate: Logger,
+) {
+    loop {
+        let wait = match update_global(&daemon, &settings).await {
+            Ok(global) => {
+                let modified = sender.send_if_modified(|current| {
+                    if *current == global {
+                        return false;
+                    }
+                    *current = global;
+                    true
+                });
+                if modified {
+                    info!(
+                        gate,
+                        "Applied global configuration version: {}",
+                        sender.borrow().version
+                    );
+                }
+                settings.refresh_interval
+            }
+            Err(error) => {
+                warn!(gate, "System API unavailable: {}", error);
+                settings.retry_interval
+            }
+        };
+        sleep(wait).await;
+    }
+}
+
+// The IS-04 heartbeat interval currently in effect
+pub fn heartbeat_interval(global: &watch::Receiver<SystemGlobal>) -> Duration {
+    Duration::from_secs(global.borrow().is04.heartbeat_interval.into())
+}
+
+// Invoke the heartbeat, e.g. the registration client's POST to /health/nodes/{nodeId}, each time
+// the heartbeat interval in effect has elapsed, until the heartbeat returns false
+// See https://specs.amwa.tv/is-09/releases/v1.0.0/docs/5.0._Node_Behaviour.html#is-04-heartbeat-interval
+pub async fn run_heartbeats<F, Fut>(global: watch::Receiver<SystemGlobal>, mut heartbeat: F)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = bool>,
+{
+    loop {
+        sleep(heartbeat_interval(&global)).await;
+        if !heartbeat().await {
+            break;
+        }
+    }
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_listener::{make_server_config, serve_tls, CertificateResolver, CipherPolicy};
+    use crate::certificate::Certificate;
+    use crate::settings::Settings;
+    use crate::system_api::make_system_api;
+    use std::sync::{Arc, Mutex};
+
+    fn service(priority: u32, base_url: &str) -> SystemService {
+        SystemService {
+            priority,
+            base_url: base_url.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_select_system_services() {
+        let services = vec![
+            service(100, "http://192.0.2.3/x-nmos/system/v1.0"),
+            service(10, "http://192.0.2.2/x-nmos/system/v1.0"),
+            service(0, "http://192.0.2.1/x-nmos/system/v1.0"),
+        ];
+        let selected = select_system_services(services.clone(), false);
+        assert_eq!(selected, vec![services[2].clone(), services[1].clone()]);
+        assert_eq!(select_system_services(services, true).len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_global() {
+        let mut global = SystemGlobal::new("3b8be755-08ff-452b-b217-c9151eb21193", "Facility");
+        global.modify(|global| global.is04.heartbeat_interval = 12);
+        let gate = Logger::root(slog::Discard, slog::o!());
+        let api = make_system_api(
+            Arc::new(Mutex::new(global.clone())),
+            &Settings::default(),
+            None,
+            gate,
+        );
+        let (address, server) = warp::serve(api).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let base_url = format!("http://{}/x-nmos/system/v1.0", address);
+        let fetched = fetch_global(&base_url, None).await.unwrap();
+        assert_eq!(fetched, global);
+
+        let result = fetch_global(&format!("http://{}/x-nmos/system/v9.9", address), None).await;
+        assert!(matches!(result, Err(SystemClientError::Status(404))));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_global_https() {
+        let mut ca_params = rcgen::CertificateParams::new(Vec::new()).unwrap();
+        ca_params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+        let ca_key = rcgen::KeyPair::generate().unwrap();
+        let ca_certificate = ca_params.self_signed(&ca_key).unwrap();
+        let server_key = rcgen::KeyPair::generate().unwrap();
+        let server_certificate = rcgen::CertificateParams::new(vec!["localhost".to_string()])
+            .unwrap()
+            .signed_by(&server_key, &ca_certificate, &ca_key)
+            .unwrap();
+        let ca = Certificate::from_pem(&ca_certificate.pem()).unwrap();
+        let server =
+            Certificate::new(&server_key.serialize_pem(), &server_certificate.pem()).unwrap();
+
+        let gate = Logger::root(slog::Discard, slog::o!());
+        let global = SystemGlobal::new("3b8be755-08ff-452b-b217-c9151eb21193", "Facility");
+        let settings = Settings {
+            server_secure: true,
+            ..Default::default()
+        };
+        let api = make_system_api(
+            Arc::new(Mutex::new(global.clone())),
+            &settings,
+            None,
+            gate.clone(),
+        );
+        let resolver = Arc::new(CertificateResolver::new(CipherPolicy::Bcp00301));
+        resolver.load(&[server]).unwrap();
+        let tls_config = make_server_config(&settings, resolver, &[])
+            .unwrap()
+            .unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(serve_tls(listener, api, tls_config, None, gate));
+
+        let base_url = format!("https://localhost:{}/x-nmos/system/v1.0", port);
+        let tls = ClientTls::new(&[ca], Vec::new(), None).unwrap();
+        assert_eq!(fetch_global(&base_url, Some(&tls)).await.unwrap(), global);
+        let result = fetch_global(&base_url, None).await;
+        assert!(matches!(result, Err(SystemClientError::Request(_))));
+
+        server.abort();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_run_heartbeats() {
+        let (sender, receiver) = watch::channel(SystemGlobal::default());
+        assert_eq!(heartbeat_interval(&receiver), Duration::from_secs(5));
+
+        // The first heartbeat is after the default interval, and the others follow the updated
+        // global configuration
+        let start = Instant::now();
+        let mut count = 0;
+        run_heartbeats(receiver, || {
+            count += 1;
+            sender.send_modify(|global| global.is04.heartbeat_interval = 12);
+            let more = count < 3;
+            async move { more }
+        })
+        .await;
+        assert_eq!(count, 3);
+        assert_eq!(start.elapsed(), Duration::from_secs(5 + 12 + 12));
+    }
+}
@@ -0,0 +1,188 @@
+// TAI timestamps, of the form "<seconds>:<nanoseconds>", which IS-04 uses for resource versions and
+// IS-05 for activation times
+// See https://specs.amwa.tv/is-04/releases/v1.3.2/docs/APIs_-_Common_Keys.html#version
+
+use std::fmt;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// The difference between TAI and UTC, in seconds, since the leap second at the end of 2016
+// See https://www.ietf.org/timezones/data/leap-seconds.list
+pub const DEFAULT_TAI_OFFSET: i64 = 37;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Tai {
+    pub seconds: u64,
+    pub nanoseconds: u32,
+}
+
+impl Tai {
+    pub const fn new(seconds: u64, nanoseconds: u32) -> Self {
+        Tai {
+            seconds,
+            nanoseconds,
+        }
+    }
+
+    pub fn parse(tai: &str) -> Option<Self> {
+        let (seconds, nanoseconds) = tai.split_once(':')?;
+        let nanoseconds: u32 = nanoseconds.parse().ok()?;
+        if nanoseconds >= 1_000_000_000 {
+            return None;
+        }
+        Some(Tai::new(seconds.parse().ok()?, nanoseconds))
+    }
+
+    // The next timestamp, one nanosecond later
+    pub fn next(&self) -> Self {
+        if self.nanoseconds + 1 < 1_000_000_000 {
+            Tai::new(self.seconds, self.nanoseconds + 1)
+        } else {
+            Tai::new(self.seconds + 1, 0)
+        }
+    }
+}
+
+impl fmt::Display for Tai {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.seconds, self.nanoseconds)
+    }
+}
+
+// A source of the current TAI time, e.g. the system clock, or a PTP clock
+pub trait TaiClock: Send + Sync {
+    fn now(&self) -> Tai;
+}
+
+// The system clock, which is UTC, with the configured UTC-TAI offset applied
+pub struct SystemClock {
+    tai_offset: AtomicI64,
+}
+
+impl SystemClock {
+    pub const fn new(tai_offset: i64) -> Self {
+        SystemClock {
+            tai_offset: AtomicI64::new(tai_offset),
+        }
+    }
+
+    pub fn tai_offset(&self) -> i64 {
+        self.tai_offset.load(Ordering::Relaxed)
+    }
+
+    pub fn set_tai_offset(&self, tai_offset: i64) {
+        self.tai_offset.store(tai_offset, Ordering::Relaxed);
+    }
+}
+
+impl TaiClock for SystemClock {
+    fn now(&self) -> Tai {
+        let utc = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let seconds = utc.as_secs().saturating_add_signed(self.tai_offset());
+        Tai::new(seconds, utc.subsec_nanos())
+    }
+}
+
+// Make resource versions from a clock, each later than the last, even if the clock hasn't moved on,
+// or has gone back, e.g. when the UTC-TAI offset is changed
+pub struct VersionClock<C> {
+    clock: C,
+    most_recent: Mutex<Tai>,
+}
+
+impl<C: TaiClock> VersionClock<C> {
+    pub const fn new(clock: C) -> Self {
+        VersionClock {
+            clock,
+            most_recent: Mutex::new(Tai::new(0, 0)),
+        }
+    }
+
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
+    pub fn make_version(&self) -> Tai {
+        let mut most_recent = self.most_recent.lock().unwrap();
+        let now = self.clock.now();
+        *most_recent = if now > *most_recent {
+            now
+        } else {
+            most_recent.next()
+        };
+        *most_recent
+    }
+}
+
+// The versions of all the resources of this process are made by the one clock, so that every
+// change has a distinct version
+static SYSTEM_VERSION_CLOCK: VersionClock<SystemClock> =
+    VersionClock::new(SystemClock::new(DEFAULT_TAI_OFFSET));
+
+// The UTC-TAI offset of the system clock
+pub fn tai_offset() -> i64 {
+    SYSTEM_VERSION_CLOCK.clock().tai_offset()
+}
+
+// Configure the UTC-TAI offset of the system clock, e.g. from the settings
+pub fn set_tai_offset(tai_offset: i64) {
+    SYSTEM_VERSION_CLOCK.clock().set_tai_offset(tai_offset);
+}
+
+pub fn tai_now() -> Tai {
+    SYSTEM_VERSION_CLOCK.clock().now()
+}
+
+// Make a resource version, later than any made before
+pub fn make_version() -> String {
+    SYSTEM_VERSION_CLOCK.make_version().to_string()
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FixedClock(Mutex<Tai>);
+
+    impl TaiClock for FixedClock {
+        fn now(&self) -> Tai {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    #[test]
+    fn test_make_version() {
+        let versions = VersionClock::new(FixedClock(Mutex::new(Tai::new(1441973902, 999_999_999))));
+        assert_eq!(versions.make_version(), Tai::new(1441973902, 999_999_999));
+        assert_eq!(versions.make_version(), Tai::new(1441973903, 0));
+        assert_eq!(versions.make_version(), Tai::new(1441973903, 1));
+        *versions.clock().0.lock().unwrap() = Tai::new(1441973904, 500);
+        assert_eq!(versions.make_version(), Tai::new(1441973904, 500));
+        // The clock going back doesn't make an earlier version
+        *versions.clock().0.lock().unwrap() = Tai::new(1441973900, 0);
+        assert_eq!(versions.make_version(), Tai::new(1441973904, 501));
+
+        let first = Tai::parse(&make_version()).unwrap();
+        let second = Tai::parse(&make_version()).unwrap();
+        assert!(second > first);
+        assert_eq!(
+            Tai::parse("1441973902:879053935"),
+            Some(Tai::new(1441973902, 879053935))
+        );
+        assert_eq!(Tai::parse("1441973902:1000000000"), None);
+        assert_eq!(Tai::parse("1441973902"), None);
+    }
+
+    #[test]
+    fn test_tai_offset() {
+        let clock = SystemClock::new(0);
+        let utc = clock.now();
+        clock.set_tai_offset(DEFAULT_TAI_OFFSET);
+        let tai = clock.now();
+        assert!(tai.seconds >= utc.seconds + 37 && tai.seconds <= utc.seconds + 38);
+    }
+}
@@ -1,9 +1,9 @@
-use std::collections::HashMap;
-
 // Define a module for NMOS
-mod nmos {
+pub mod nmos {
+    use std::collections::HashMap;
+
     // Define a string enum for resource types
-    #[derive(Debug, PartialEq, Eq, Hash)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
     pub enum ResourceType {
         Node,
         Device,
@@ -23,6 +23,7 @@ mod nmos {
         NcClassManager,
         NcReceieverMonitor,
         NcReceieverMonitorProtected,
+        NcSenderMonitor,
         NcIdentBeacon,
     }
 
@@ -48,6 +49,7 @@ mod nmos {
             map.insert(ResourceType::NcClassManager, "nc_class_manager");
             map.insert(ResourceType::NcReceieverMonitor, "nc_receiver_monitor");
             map.insert(ResourceType::NcReceieverMonitorProtected, "nc_receiver_monitor_protected");
+            map.insert(ResourceType::NcSenderMonitor, "nc_sender_monitor");
             map.insert(ResourceType::NcIdentBeacon, "nc_ident_beacon");
             map
         };
@@ -67,7 +69,7 @@ mod tests {
 
     #[test]
     fn test_all_types() {
-        assert_eq!(nmos::ALL_TYPES.len(), 19);
+        assert_eq!(nmos::ALL_TYPES.len(), 20);
         assert_eq!(nmos::ALL_TYPES[&nmos::ResourceType::Node], "node");
         assert_eq!(nmos::ALL_TYPES[&nmos::ResourceType::Device], "device");
         assert_eq!(nmos::ALL_TYPES[&nmos::ResourceType::Source], "source");
@@ -104,6 +106,10 @@ mod tests {
             nmos::ALL_TYPES[&nmos::ResourceType::NcReceieverMonitorProtected],
             "nc_receiver_monitor_protected"
         );
+        assert_eq!(
+            nmos::ALL_TYPES[&nmos::ResourceType::NcSenderMonitor],
+            "nc_sender_monitor"
+        );
         assert_eq!(
             nmos::ALL_TYPES[&nmos::ResourceType::NcIdentBeacon],
             "nc_ident_beacon"